@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+#ifndef RIGELA_CONTROLLER_H
+#define RIGELA_CONTROLLER_H
+
+#include <wchar.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 调用成功 */
+#define RIGELA_OK 0
+/* 读屏没有运行，或者没有启用自动化接口 */
+#define RIGELA_ERROR_NOT_RUNNING 1
+/* 参数无效，例如传入了空指针或者空字符串 */
+#define RIGELA_ERROR_INVALID_ARGUMENT 2
+/* 读屏拒绝了调用或者调用失败 */
+#define RIGELA_ERROR_CALL_FAILED 3
+/* 控制器内部出错 */
+#define RIGELA_ERROR_INTERNAL 4
+
+/* 朗读一段文字，会打断读屏正在朗读的内容，不等待朗读完毕就返回。 */
+int rigela_speak_text(const wchar_t *text);
+
+/* 逐个字符朗读一段文字，会打断读屏正在朗读的内容，不等待朗读完毕就返回。 */
+int rigela_speak_spelling(const wchar_t *text);
+
+/* 停止读屏所有的朗读。 */
+int rigela_cancel_speech(void);
+
+/* 检测读屏是否正在运行，正在运行则返回RIGELA_OK。 */
+int rigela_is_running(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RIGELA_CONTROLLER_H */
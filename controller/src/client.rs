@@ -0,0 +1,280 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use rigela_utils::{
+    pipe::{connect, PipeStream, Transport},
+    rpc::{RpcError, RpcMessage, RpcRequest, Value},
+};
+use std::{
+    fmt::{Display, Formatter},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    time::timeout,
+};
+
+// 连接读屏的超时时间，读屏没有运行时命名管道会立即失败，这个时间只是为了防止意外的阻塞
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+// 等待读屏回复的超时时间
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 控制器的错误
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControllerError {
+    /// 读屏没有运行，或者没有启用自动化接口
+    NotRunning,
+    /// 读屏拒绝了调用或者调用失败
+    CallFailed(RpcError),
+    /// 控制器内部出错，例如无法创建异步运行时
+    Internal(String),
+}
+
+impl Display for ControllerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotRunning => write!(f, "RigelA is not running."),
+            Self::CallFailed(e) => write!(f, "The call failed. {}", e),
+            Self::Internal(e) => write!(f, "The controller failed. {}", e),
+        }
+    }
+}
+
+type Stream = PipeStream<RpcMessage, Transport>;
+
+/**
+ * 读屏的控制器，通过自动化接口和正在运行的读屏通信。
+ * 连接会被保留下来给之后的调用使用，读屏重启后连接失效时会自动重新连接一次。
+ * */
+pub struct Controller {
+    endpoint: String,
+    // 只在析构时被取出
+    runtime: Option<Runtime>,
+    stream: Mutex<Option<Box<Stream>>>,
+    next_id: AtomicU64,
+}
+
+impl Controller {
+    /**
+     * 创建一个控制器，创建时不会连接读屏。
+     * `endpoint` 自动化接口的端点uri。
+     * */
+    pub fn new(endpoint: &str) -> Result<Self, ControllerError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| ControllerError::Internal(e.to_string()))?;
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            runtime: Some(runtime),
+            stream: None.into(),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /**
+     * 发送一个通知，不等待读屏回复，适合朗读这种需要立即返回的调用。
+     * `method` 方法名称。
+     * `params` 方法的参数。
+     * */
+    pub fn notify(&self, method: &str, params: Value) -> Result<(), ControllerError> {
+        let mut request = RpcRequest::new(0, method, params);
+        request.id = None;
+        self.request(request).map(|_| ())
+    }
+
+    /**
+     * 调用一个方法并等待读屏回复。
+     * `method` 方法名称。
+     * `params` 方法的参数。
+     * */
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, ControllerError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        match self.request(RpcRequest::new(id, method, params))? {
+            Some(r) => r.map_err(ControllerError::CallFailed),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /**
+     * 发送一个请求，如果请求有id，则等待对应的回复。
+     * 缓存的连接可能因为读屏重启已经失效，这时发送会失败，重新连接一次后再试；已经发送出去的请求不会重试，避免重复执行。
+     * */
+    fn request(
+        &self,
+        request: RpcRequest,
+    ) -> Result<Option<Result<Value, RpcError>>, ControllerError> {
+        let mut guard = self.stream.lock().map_err(|_| {
+            // 之前的调用在持有连接时panic了，连接已经被取出，下次调用会重新连接
+            self.stream.clear_poison();
+            ControllerError::Internal("The connection is poisoned.".to_string())
+        })?;
+        for _ in 0..2 {
+            let stream = match guard.take() {
+                Some(s) => s,
+                None => self.connect()?.ok_or(ControllerError::NotRunning)?,
+            };
+            match self.block_on(Self::exchange(stream, request.clone()))? {
+                Exchange::SendFailed => continue,
+                Exchange::Lost => break,
+                Exchange::Replied(stream, reply) => {
+                    *guard = Some(stream);
+                    return Ok(reply);
+                }
+            }
+        }
+        Err(ControllerError::NotRunning)
+    }
+
+    fn connect(&self) -> Result<Option<Box<Stream>>, ControllerError> {
+        self.block_on(async {
+            let transport = timeout(CONNECT_TIMEOUT, connect(&self.endpoint))
+                .await
+                .ok()?
+                .ok()?;
+            Some(Box::new(PipeStream::new(transport)))
+        })
+    }
+
+    /**
+     * 在控制器自己的运行时中执行一个异步任务，并等待他完成。
+     * 调用者可能正在其他的tokio运行时中执行（例如使用rlib的Rust程序），这时不能在当前线程中启动运行时，所以在单独的线程中等待。
+     * `future` 要执行的异步任务。
+     * */
+    fn block_on<F>(&self, future: F) -> Result<F::Output, ControllerError>
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        let Some(runtime) = self.runtime.as_ref() else {
+            return Err(ControllerError::Internal(
+                "The runtime is shut down.".to_string(),
+            ));
+        };
+        if Handle::try_current().is_err() {
+            return Ok(runtime.block_on(future));
+        }
+        thread::scope(|s| s.spawn(|| runtime.block_on(future)).join())
+            .map_err(|_| ControllerError::Internal("The request panicked.".to_string()))
+    }
+
+    async fn exchange(mut stream: Box<Stream>, request: RpcRequest) -> Exchange {
+        let id = request.id.clone();
+        if stream.send(&RpcMessage::Request(request)).await.is_err() {
+            return Exchange::SendFailed;
+        }
+        let Some(id) = id else {
+            return Exchange::Replied(stream, None);
+        };
+        loop {
+            // 跳过之前超时的调用迟到的回复
+            match timeout(REPLY_TIMEOUT, stream.recv()).await {
+                Ok(Ok(RpcMessage::Response(r))) if r.id == id => {
+                    return Exchange::Replied(stream, Some(r.into_result()))
+                }
+                Ok(Ok(_)) => continue,
+                _ => return Exchange::Lost,
+            }
+        }
+    }
+}
+
+impl Drop for Controller {
+    fn drop(&mut self) {
+        // 控制器可能在其他的运行时中被析构，这时不能等待运行时的线程退出
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// 一次请求的结果
+enum Exchange {
+    /// 请求没有发送出去
+    SendFailed,
+    /// 请求已经发送，但没有收到回复
+    Lost,
+    /// 收到了回复，通知没有回复
+    Replied(Box<Stream>, Option<Result<Value, RpcError>>),
+}
+
+#[cfg(test)]
+mod test_client {
+    use crate::client::{Controller, ControllerError};
+    use rigela_utils::{
+        pipe::{PipeStream, TransportListener},
+        rpc::{RpcError, RpcMessage, RpcResponse, Value, METHOD_NOT_FOUND},
+    };
+    use std::{sync::mpsc::channel, thread};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_call() {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            Runtime::new().unwrap().block_on(async move {
+                let mut listener = TransportListener::bind("tcp://127.0.0.1:0").await.unwrap();
+                tx.send(listener.local_endpoint()).unwrap();
+                let mut stream = PipeStream::<RpcMessage, _>::new(listener.accept().await.unwrap());
+                while let Ok(RpcMessage::Request(r)) = stream.recv().await {
+                    let Some(id) = r.id else {
+                        continue;
+                    };
+                    let result = match r.method.as_str() {
+                        "ping" => Ok(Value::from("0.2.0")),
+                        _ => Err(RpcError::new(METHOD_NOT_FOUND, "Method not found.")),
+                    };
+                    let response = RpcMessage::Response(RpcResponse::new(id, result));
+                    stream.send(&response).await.unwrap();
+                }
+            });
+        });
+
+        let controller = Controller::new(&rx.recv().unwrap()).unwrap();
+        // 通知不会收到回复，之后的调用仍然能收到正确的回复
+        controller.notify("speak", Value::Null).unwrap();
+        assert_eq!(
+            Value::from("0.2.0"),
+            controller.call("ping", Value::Null).unwrap()
+        );
+        let Err(ControllerError::CallFailed(e)) = controller.call("unknown", Value::Null) else {
+            panic!("Expected an error.");
+        };
+        assert_eq!(METHOD_NOT_FOUND, e.code);
+    }
+
+    #[test]
+    fn test_not_running() {
+        let controller = Controller::new("tcp://127.0.0.1:1").unwrap();
+        assert_eq!(
+            Err(ControllerError::NotRunning),
+            controller.call("ping", Value::Null)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_runtime() {
+        // 在其他的运行时中调用时不能直接启动控制器的运行时
+        let controller = Controller::new("tcp://127.0.0.1:1").unwrap();
+        assert_eq!(
+            Err(ControllerError::NotRunning),
+            controller.call("ping", Value::Null)
+        );
+    }
+}
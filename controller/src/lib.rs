@@ -0,0 +1,161 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+#![doc = include_str!("../README.md")]
+
+pub mod client;
+
+use client::{Controller, ControllerError};
+use rigela_utils::rpc::{to_value, AUTOMATION_ENDPOINT};
+use serde::Serialize;
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::OnceLock,
+};
+
+/// 调用成功
+pub const RIGELA_OK: i32 = 0;
+/// 读屏没有运行，或者没有启用自动化接口
+pub const RIGELA_ERROR_NOT_RUNNING: i32 = 1;
+/// 参数无效，例如传入了空指针或者空字符串
+pub const RIGELA_ERROR_INVALID_ARGUMENT: i32 = 2;
+/// 读屏拒绝了调用或者调用失败
+pub const RIGELA_ERROR_CALL_FAILED: i32 = 3;
+/// 控制器内部出错
+pub const RIGELA_ERROR_INTERNAL: i32 = 4;
+
+static CONTROLLER: OnceLock<Result<Controller, ControllerError>> = OnceLock::new();
+
+fn get_controller() -> Result<&'static Controller, ControllerError> {
+    CONTROLLER
+        .get_or_init(|| Controller::new(AUTOMATION_ENDPOINT))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/**
+ * 执行导出函数的函数体，panic不能越过FFI边界传给调用者，所以转换成错误代码。
+ * `f` 函数体，返回错误代码。
+ * */
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(RIGELA_ERROR_INTERNAL)
+}
+
+#[derive(Serialize)]
+struct TextParams {
+    text: String,
+}
+
+/**
+ * 把以0结尾的宽字符串转换成String，空指针或者空字符串返回None。
+ * `text` 宽字符串的指针。
+ * */
+unsafe fn from_wide(text: *const u16) -> Option<String> {
+    if text.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *text.add(len) != 0 {
+        len += 1;
+    }
+    if len < 1 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(std::slice::from_raw_parts(
+        text, len,
+    )))
+}
+
+fn to_code(result: Result<(), ControllerError>) -> i32 {
+    match result {
+        Ok(_) => RIGELA_OK,
+        Err(ControllerError::NotRunning) => RIGELA_ERROR_NOT_RUNNING,
+        Err(ControllerError::CallFailed(_)) => RIGELA_ERROR_CALL_FAILED,
+        Err(ControllerError::Internal(_)) => RIGELA_ERROR_INTERNAL,
+    }
+}
+
+unsafe fn notify_text(method: &str, text: *const u16) -> i32 {
+    let Some(text) = from_wide(text) else {
+        return RIGELA_ERROR_INVALID_ARGUMENT;
+    };
+    let params = to_value(TextParams { text }).unwrap_or_default();
+    to_code(get_controller().and_then(|c| c.notify(method, params)))
+}
+
+/**
+ * 朗读一段文字，会打断读屏正在朗读的内容，不等待朗读完毕就返回。
+ * `text` 以0结尾的UTF-16字符串。
+ *
+ * # Safety
+ * `text` 必须是空指针或者指向以0结尾的UTF-16字符串。
+ * */
+#[no_mangle]
+pub unsafe extern "C" fn rigela_speak_text(text: *const u16) -> i32 {
+    guard(|| notify_text("speak", text))
+}
+
+/**
+ * 逐个字符朗读一段文字，会打断读屏正在朗读的内容，不等待朗读完毕就返回。
+ * `text` 以0结尾的UTF-16字符串。
+ *
+ * # Safety
+ * `text` 必须是空指针或者指向以0结尾的UTF-16字符串。
+ * */
+#[no_mangle]
+pub unsafe extern "C" fn rigela_speak_spelling(text: *const u16) -> i32 {
+    guard(|| notify_text("spell", text))
+}
+
+/**
+ * 停止读屏所有的朗读。
+ * */
+#[no_mangle]
+pub extern "C" fn rigela_cancel_speech() -> i32 {
+    guard(|| to_code(get_controller().and_then(|c| c.notify("cancel", Default::default()))))
+}
+
+/**
+ * 检测读屏是否正在运行，正在运行则返回RIGELA_OK。
+ * */
+#[no_mangle]
+pub extern "C" fn rigela_is_running() -> i32 {
+    guard(|| {
+        to_code(
+            get_controller()
+                .and_then(|c| c.call("ping", Default::default()))
+                .map(|_| ()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test_controller {
+    use crate::{from_wide, guard, RIGELA_ERROR_INTERNAL, RIGELA_OK};
+
+    #[test]
+    fn test_from_wide() {
+        let text: Vec<u16> = "你好，RigelA\0".encode_utf16().collect();
+        assert_eq!(Some("你好，RigelA".to_string()), unsafe {
+            from_wide(text.as_ptr())
+        });
+        assert_eq!(None, unsafe { from_wide([0u16].as_ptr()) });
+        assert_eq!(None, unsafe { from_wide(std::ptr::null()) });
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(RIGELA_OK, guard(|| RIGELA_OK));
+        assert_eq!(RIGELA_ERROR_INTERNAL, guard(|| panic!("Unexpected.")));
+    }
+}
@@ -0,0 +1,343 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    configs::items::automation::AutomationConfig,
+    context::{Context, ContextAccessor},
+    talent::Talented,
+};
+use log::{error, info, warn};
+use rigela_utils::{
+    pipe::{PipeStream, PipeStreamError, Transport, TransportListener},
+    rpc::{
+        from_value, to_value, RpcError, RpcMessage, RpcRequest, RpcResponse, Value, ACCESS_DENIED,
+        INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Weak};
+use tokio::sync::Mutex;
+
+/// 朗读的优先级
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SpeakPriority {
+    /// 和读屏自己的输出一样，打断正在朗读的内容
+    #[default]
+    Normal,
+    /// 排在正在朗读的内容之后，不打断
+    Low,
+}
+
+#[derive(Deserialize)]
+struct SpeakParams {
+    text: String,
+    #[serde(default)]
+    priority: SpeakPriority,
+}
+
+#[derive(Deserialize)]
+struct TextParams {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct RunTalentParams {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct FocusInfo {
+    name: String,
+    role: String,
+    class_name: String,
+    automation_id: String,
+    /// 屏幕坐标，顺序是左、上、右、下
+    rect: [i32; 4],
+}
+
+#[derive(Serialize)]
+struct TalentInfo {
+    id: String,
+    doc: String,
+    key: Option<String>,
+}
+
+/**
+ * 自动化接口的访问策略。
+ * 只允许本机的进程连接：命名管道拒绝来自网络的客户端，TCP只能监听回环地址，端点在监听时检查；
+ * 此外外部程序只能执行配置中明确允许的能力。
+ * */
+#[derive(Clone, Debug)]
+struct AccessPolicy {
+    allow_run_talent: bool,
+    allowed_talents: Vec<String>,
+}
+
+impl AccessPolicy {
+    fn new(config: &AutomationConfig) -> Self {
+        Self {
+            allow_run_talent: config.allow_run_talent,
+            allowed_talents: config.allowed_talents.clone(),
+        }
+    }
+
+    /**
+     * 检查是否允许调用一个方法。
+     * `method` 方法名称。
+     * */
+    fn check_method(&self, method: &str) -> Result<(), RpcError> {
+        if method == "run_talent" && !self.allow_run_talent {
+            return Err(RpcError::new(
+                ACCESS_DENIED,
+                "Running talents is not allowed by the access policy.",
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * 检查是否允许执行一个能力。
+     * `id` 能力的id。
+     * */
+    fn check_talent(&self, id: &str) -> Result<(), RpcError> {
+        if !self.allow_run_talent || !self.allowed_talents.iter().any(|t| t == id) {
+            return Err(RpcError::new(
+                ACCESS_DENIED,
+                format!("The talent {} is not allowed by the access policy.", id),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * 启动自动化接口的服务，外部程序可以通过JSON-RPC控制读屏，例如朗读文字、查询焦点或执行能力。
+ * 如果配置中没有启用，则什么都不做。
+ * `context` 读屏框架的上下文环境。
+ * */
+pub(crate) async fn serve(context: Weak<Context>) {
+    let config = context.get_config_manager().get_config().automation_config;
+    if !config.enabled {
+        return;
+    }
+    let policy = AccessPolicy::new(&config);
+    let mut listener = match TransportListener::bind(&config.endpoint).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Can't start the automation server. {}", e);
+            return;
+        }
+    };
+    info!(
+        "The automation server is listening on {}.",
+        listener.local_endpoint()
+    );
+    loop {
+        let transport = match listener.accept().await {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Can't accept the automation client. {}", e);
+                continue;
+            }
+        };
+        let ctx = context.clone();
+        let policy = policy.clone();
+        context
+            .get_work_runtime()
+            .spawn(async move { handle_client(ctx, policy, transport).await });
+    }
+}
+
+/**
+ * 处理一个客户端的所有请求。每个请求在单独的任务中执行，例如等待朗读完毕的speak请求不会阻塞之后的cancel请求。
+ * */
+async fn handle_client(context: Weak<Context>, policy: AccessPolicy, transport: Transport) {
+    let (mut reader, writer) = PipeStream::<RpcMessage, _>::new(transport).split();
+    let writer = Arc::new(Mutex::new(writer));
+    loop {
+        let response = match reader.recv().await {
+            Ok(RpcMessage::Request(request)) => {
+                let ctx = context.clone();
+                let policy = policy.clone();
+                let writer = writer.clone();
+                context.get_work_runtime().spawn(async move {
+                    let RpcRequest {
+                        id, method, params, ..
+                    } = request;
+                    let result = match policy.check_method(&method) {
+                        Ok(_) => call(ctx, &policy, &method, params).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = &result {
+                        warn!("The automation call {} failed. {}", method, e);
+                    }
+                    // 没有id的请求是通知，不需要回复
+                    let Some(id) = id else {
+                        return;
+                    };
+                    let response = RpcMessage::Response(RpcResponse::new(id, result));
+                    writer.lock().await.send(&response).await.unwrap_or(());
+                });
+                continue;
+            }
+            Ok(RpcMessage::Response(r)) => RpcResponse::new(
+                r.id,
+                Err(RpcError::new(INVALID_REQUEST, "Expected a request.")),
+            ),
+            Err(PipeStreamError::DecodeError(e)) => {
+                RpcResponse::new(Value::Null, Err(RpcError::new(PARSE_ERROR, e)))
+            }
+            Err(_) => break,
+        };
+        // 无法回复错误时说明连接已经断开
+        let response = RpcMessage::Response(response);
+        if writer.lock().await.send(&response).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+fn make_result<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e))
+}
+
+/**
+ * 调用一个方法。
+ * `context` 读屏框架的上下文环境。
+ * `policy` 访问策略。
+ * `method` 方法名称。
+ * `params` 方法的参数。
+ * */
+async fn call(
+    context: Weak<Context>,
+    policy: &AccessPolicy,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    match method {
+        "speak" => {
+            let params: SpeakParams = parse_params(params)?;
+            if params.text.is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "The text is empty."));
+            }
+            let completed = match params.priority {
+                SpeakPriority::Normal => context.get_performer().speak(&params.text).await,
+                SpeakPriority::Low => context.get_performer().get_tts().speak(params.text).await,
+            };
+            make_result(completed)
+        }
+        "spell" => {
+            // 逐个字符朗读，空白字符不朗读
+            let params: TextParams = parse_params(params)?;
+            let tts = context.get_performer().get_tts();
+            tts.stop().await;
+            let mut completed = true;
+            for c in params.text.chars().filter(|c| !c.is_whitespace()) {
+                if !tts.speak(c.to_string()).await {
+                    completed = false;
+                    break;
+                }
+            }
+            make_result(completed)
+        }
+        "cancel" => {
+            context.get_performer().get_tts().stop_all().await;
+            make_result(true)
+        }
+        "message" => {
+            // 消息是给用户的简短提示，目前只通过语音输出，以后支持点显器时也会显示在点显器上
+            let params: TextParams = parse_params(params)?;
+            make_result(context.get_performer().speak(&params.text).await)
+        }
+        "ping" => make_result(env!("CARGO_PKG_VERSION")),
+        "get_focus" => {
+            let element = context
+                .get_ui_automation()
+                .get_focused_element()
+                .map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+            let rect = element.get_bounding_rectangle();
+            make_result(FocusInfo {
+                name: element.get_name(),
+                role: element.get_localized_control_type(),
+                class_name: element.get_class_name(),
+                automation_id: element.get_automation_id(),
+                rect: [rect.left, rect.top, rect.right, rect.bottom],
+            })
+        }
+        "list_talents" => {
+            let provider = context.get_talent_provider();
+            let talents: Vec<TalentInfo> = provider
+                .get_talent_ids()
+                .iter()
+                .filter_map(|id| provider.get_talent_by_id(id))
+                .map(|t| TalentInfo {
+                    id: t.get_id(),
+                    doc: t.get_doc(),
+                    key: provider
+                        .get_combo_key_by_id(&t.get_id())
+                        .map(|k| k.to_string()),
+                })
+                .collect();
+            make_result(talents)
+        }
+        "run_talent" => {
+            let params: RunTalentParams = parse_params(params)?;
+            policy.check_talent(&params.id)?;
+            let Some(talent) = context.get_talent_provider().get_talent_by_id(&params.id) else {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("The talent {} does not exist.", params.id),
+                ));
+            };
+            talent.perform(context.clone());
+            make_result(true)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("The method {} does not exist.", method),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test_automation {
+    use crate::{automation::AccessPolicy, configs::items::automation::AutomationConfig};
+    use rigela_utils::rpc::ACCESS_DENIED;
+
+    #[test]
+    fn test_access_policy() {
+        // 默认不允许执行任何能力
+        let policy = AccessPolicy::new(&AutomationConfig::default());
+        assert!(policy.check_method("speak").is_ok());
+        assert_eq!(
+            ACCESS_DENIED,
+            policy.check_method("run_talent").unwrap_err().code
+        );
+        assert!(policy.check_talent("exit").is_err());
+
+        // 只能执行列表中的能力
+        let policy = AccessPolicy::new(&AutomationConfig {
+            allow_run_talent: true,
+            allowed_talents: vec!["cur_time".to_string()],
+            ..Default::default()
+        });
+        assert!(policy.check_method("run_talent").is_ok());
+        assert!(policy.check_talent("cur_time").is_ok());
+        assert_eq!(ACCESS_DENIED, policy.check_talent("exit").unwrap_err().code);
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use log::LevelFilter;
+use std::{path::PathBuf, str::FromStr};
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+/// 命令行的帮助信息，在解析命令行之前还没有加载语言配置，所以只提供英文版本
+pub(crate) const USAGE: &str = "\
+Usage: rigela-main [OPTIONS]
+
+Options:
+  --config <PATH>      Use the specified config file instead of config.toml in the data directory.
+  --data-dir <PATH>    Store the config, logs, libraries and resources in the specified directory.
+  --log-level <LEVEL>  Set the log level: off, error, warn, info, debug or trace.
+  --no-sound           Don't play any sound effects.
+  --safe-mode          Start with the default config, don't switch profiles and don't save any changes.
+  --minimal            Don't start the proxy32 process and don't inject the peeper module.
+  --replace            Exit the running instance and start a new one.
+  --quit               Ask the running instance to exit, then exit.
+  -h, --help           Print this help.";
+
+/// 命令行参数
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CliArgs {
+    /// 配置文件的路径
+    pub(crate) config: Option<PathBuf>,
+    /// 数据目录的路径
+    pub(crate) data_dir: Option<PathBuf>,
+    /// 日志的级别
+    pub(crate) log_level: Option<LevelFilter>,
+    /// 不播放音效
+    pub(crate) no_sound: bool,
+    /// 安全模式，使用默认配置，不切换配置方案，也不保存任何修改
+    pub(crate) safe_mode: bool,
+    /// 最小模式，不启动proxy32进程，也不注入peeper模块
+    pub(crate) minimal: bool,
+    /// 退出正在运行的读屏，然后启动新的实例
+    pub(crate) replace: bool,
+    /// 请求正在运行的读屏退出，然后退出
+    pub(crate) quit: bool,
+    /// 显示帮助信息
+    pub(crate) help: bool,
+}
+
+impl CliArgs {
+    /**
+     * 解析当前进程的命令行参数。
+     * */
+    pub(crate) fn from_env() -> Result<Self, String> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    /**
+     * 解析命令行参数，带有值的选项支持`--name value`和`--name=value`两种写法。
+     * `args` 命令行参数，不包括程序名称。
+     * */
+    pub(crate) fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut result = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((n, v)) if n.starts_with("--") => (n.to_string(), Some(v.to_string())),
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| format!("The option {} requires a value.", name))
+            };
+            match name.as_str() {
+                "--config" => result.config = Some(value()?.into()),
+                "--data-dir" => result.data_dir = Some(value()?.into()),
+                "--log-level" => {
+                    let level = value()?;
+                    result.log_level = Some(
+                        LevelFilter::from_str(&level)
+                            .map_err(|_| format!("The log level {} is invalid.", level))?,
+                    )
+                }
+                "--no-sound" => result.no_sound = true,
+                "--safe-mode" => result.safe_mode = true,
+                "--minimal" => result.minimal = true,
+                "--replace" => result.replace = true,
+                "--quit" => result.quit = true,
+                "-h" | "--help" => result.help = true,
+                // 更新器更新主程序后使用这个参数重新启动读屏，由gui模块处理
+                "--updated" => {}
+                _ => return Err(format!("The option {} is unknown.", name)),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/**
+ * 连接到启动本程序的命令行窗口，发布版本是窗口程序，没有自己的控制台，不连接的话帮助信息无法显示出来。
+ * */
+pub(crate) fn attach_console() {
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) }.unwrap_or(());
+}
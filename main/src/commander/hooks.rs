@@ -16,7 +16,7 @@ use crate::{
         combo_keys::{ComboKey, State},
         keys::Keys,
     },
-    configs::operations::get_mouse_read_state,
+    configs::{items::hotkeys::HotKeysConfig, operations::get_mouse_read_state},
     context::{Context, ContextAccessor},
     talent::{mouse::mouse_read, Talent},
 };
@@ -76,37 +76,53 @@ pub(crate) fn set_keyboard_hook(context: Weak<Context>) -> WindowsHook {
 
         // 存储按键到缓冲
         let mut map = key_track.write();
-        map.insert(key, pressed);
+        // 按住一个键时系统会重复发送按下的消息
+        let repeated = map.insert(key, pressed) == Some(true) && pressed;
         let cur_combo_key: ComboKey = map
             .iter()
             .filter_map(|(k, v)| if *v { Some(k.clone()) } else { None })
             .collect::<Vec<Keys>>()
             .into();
 
-        let mut talent_cache: Option<Talent> = None;
+        // 远程协助的领导者控制跟随者时，除了切换控制的热键，所有的按键都发送给跟随者
+        let session = context.get_remote_session();
+        if session.is_controlling()
+            && !is_remote_control_key(&context, &cur_combo_key)
+            && session.forward_key(info.vkCode, info.scanCode, is_extended, pressed)
+        {
+            return LRESULT(1);
+        }
+
+        // 键盘帮助模式，拦截所有的按键，只朗读按键的名称和对应的能力
+        if mng.is_help_mode() {
+            drop(map); // 执行能力时可能会再次进入钩子，先释放锁
+            if pressed {
+                ignore_capital_key.store(true, Ordering::Relaxed);
+            }
+            if !repeated {
+                process_help_key(context.clone(), key, cur_combo_key, pressed);
+            }
+            return LRESULT(1);
+        }
+
+        // 松开进入命令层时按住的修饰键以后，再按下时不再忽略
+        if !pressed && key.is_modifierkey() {
+            mng.release_layer_modifiers(cur_combo_key.modify_keys);
+        }
+
         let mut combo_key: Option<ComboKey> = None;
         match pressed {
             // 松开按键，需要排除大写锁定键，由后面的大写锁定键代码专门处理
             false if info.vkCode as u16 != VK_CAPITAL.0 => {
                 if !key.is_modifierkey() {
-                    // 松开按键，检测组合热键的长按
-                    let ck_long = ComboKey {
-                        state: State::LongPress,
-                        ..cur_combo_key
-                    };
-                    // 如果程序技能存在长按的热键组，发送到热键组管理检测当前按键松开是否为长按组合热键
-                    combo_key = match context
-                        .get_talent_provider()
-                        .get_talent_by_combo_key(&ck_long)
+                    // 松开按键，检测组合热键的长按，当前按住的键已经不包括松开的键，所以单独传入松开的键
+                    let released = ComboKey::new(key, cur_combo_key.modify_keys, State::Idle);
+                    if let Some(talent) = mng
+                        .process_combo_key(&released, pressed)
+                        .and_then(|k| context.get_talent_provider().get_talent_by_combo_key(&k))
                     {
-                        Some(talent) => {
-                            talent_cache = Some(talent);
-                            mng.process_combo_key(&cur_combo_key, pressed)
-                        }
-                        None => None,
-                    };
-                    if combo_key.is_some() {
-                        return execute(context.clone(), talent_cache.unwrap());
+                        drop(map); // 执行能力时可能会再次进入钩子，先释放锁
+                        return execute(context.clone(), talent);
                     }
                 }
 
@@ -121,24 +137,23 @@ pub(crate) fn set_keyboard_hook(context: Weak<Context>) -> WindowsHook {
                 // 保存最后按下的键
                 mng.set_last_pressed_key(&key);
 
+                // 在命令层中，按下的键只在层的热键表中查找，进入层时按住的修饰键会被忽略
                 if !key.is_modifierkey() {
-                    // 键位按下时，检测组合热键是单机还是双击
-                    let ck_single = ComboKey {
-                        state: State::SinglePress,
-                        ..cur_combo_key
-                    };
-                    let ck_double = ComboKey {
-                        state: State::DoublePress,
-                        ..cur_combo_key
-                    };
-
-                    // 如果程序技能存在双击的热键组，发送到热键组管理检测当前按键按下是否为双击组合热键
-                    combo_key = match context
-                        .get_talent_provider()
-                        .get_talent_by_combo_key(&ck_double)
-                    {
-                        Some(_) => mng.process_combo_key(&cur_combo_key, pressed),
-                        None => Some(ck_single),
+                    if let Some(layer) = mng.get_layer() {
+                        drop(map); // 执行能力时可能会再次进入钩子，先释放锁
+                        return process_layer_key(
+                            context.clone(),
+                            &layer,
+                            &mng.get_layer_key(&cur_combo_key.change_state(State::SinglePress)),
+                        );
+                    }
+                }
+
+                if !key.is_modifierkey() {
+                    // 键位按下时，识别组合热键是单击、双击还是三击，按住不放时重复的按下按单击处理
+                    combo_key = match repeated {
+                        true => Some(cur_combo_key.change_state(State::SinglePress)),
+                        false => mng.process_combo_key(&cur_combo_key, pressed),
                     }
                 }
             }
@@ -147,12 +162,21 @@ pub(crate) fn set_keyboard_hook(context: Weak<Context>) -> WindowsHook {
         }
 
         if let Some(combo_key) = combo_key {
-            if let Some(talent) = context
-                .get_talent_provider()
-                .get_talent_by_combo_key(&combo_key)
+            if let Some(talent) = fallback_combo_keys(&combo_key)
+                .into_iter()
+                .find_map(|k| context.get_talent_provider().get_talent_by_combo_key(&k))
             {
                 return execute(context.clone(), talent);
             }
+            // 没有对应的能力时，检测是否是进入命令层的热键
+            let hotkeys = context.get_config_manager().get_section::<HotKeysConfig>();
+            if let Some(layer) = fallback_combo_keys(&combo_key)
+                .into_iter()
+                .find_map(|k| hotkeys.find_layer_by_key("", &k))
+            {
+                mng.enter_layer(&layer.name, combo_key.modify_keys);
+                return LRESULT(1);
+            }
         }
 
         // 大写锁定键处理
@@ -228,3 +252,183 @@ fn execute(context: Weak<Context>, talent: Talent) -> LRESULT {
     }
     LRESULT(1)
 }
+
+/**
+获取组合键以及按下次数更少的组合键，查找能力时依次使用：双击没有对应的能力时按单击处理；三击没有对应的能力时也直接按单击处理，不会执行双击的能力。
+`combo_key` 识别出的组合键。
+*/
+fn fallback_combo_keys(combo_key: &ComboKey) -> Vec<ComboKey> {
+    let states = match combo_key.state {
+        State::TriplePress => vec![State::TriplePress, State::SinglePress],
+        State::DoublePress => vec![State::DoublePress, State::SinglePress],
+        s => vec![s],
+    };
+    states
+        .into_iter()
+        .map(|s| combo_key.change_state(s))
+        .collect()
+}
+
+/**
+处理键盘帮助模式中的按键，朗读按键的名称和对应能力的说明，只有切换帮助模式的能力会被执行。
+按下时识别单击、双击和三击，松开时如果是长按，并且有对应长按的能力，再朗读一次。
+`context` 读屏的上下文环境。
+`key` 按下或松开的键。
+`combo_key` 当前按住的所有键组成的组合键，松开时不包括松开的键。
+`pressed` 是否是按下。
+*/
+fn process_help_key(context: Weak<Context>, key: Keys, combo_key: ComboKey, pressed: bool) {
+    let mng = context.get_commander().get_keyboard_manager();
+    let provider = context.get_talent_provider();
+    let text = match pressed {
+        // 单独按下修饰键只朗读键名
+        true if key.is_modifierkey() => String::from(key),
+        true => {
+            let Some(combo_key) = mng.process_combo_key(&combo_key, pressed) else {
+                return;
+            };
+            let hotkeys = context.get_config_manager().get_section::<HotKeysConfig>();
+            let combo_key = fallback_combo_keys(&combo_key)
+                .into_iter()
+                .find(|k| {
+                    provider.get_talent_by_combo_key(k).is_some()
+                        || hotkeys.find_layer_by_key("", k).is_some()
+                })
+                .unwrap_or(combo_key.change_state(State::SinglePress));
+            if let Some(talent) = provider.get_talent_by_combo_key(&combo_key) {
+                if talent.get_id() == "input_help" {
+                    talent.perform(context.clone());
+                    return;
+                }
+            }
+            describe_combo_key(&context, &combo_key)
+        }
+        false if key.is_modifierkey() => return,
+        false => {
+            let released = ComboKey::new(key, combo_key.modify_keys, State::Idle);
+            match mng.process_combo_key(&released, pressed) {
+                Some(long) if provider.get_talent_by_combo_key(&long).is_some() => {
+                    describe_combo_key(&context, &long)
+                }
+                _ => return,
+            }
+        }
+    };
+
+    let ctx = context.clone();
+    context.get_work_runtime().spawn(async move {
+        ctx.get_performer().speak(&text).await;
+    });
+}
+
+/**
+获取组合键的朗读文字，包括键名和对应能力的说明，例如“RigelA + F12 双击 朗读当前日期”。
+`context` 读屏的上下文环境。
+`combo_key` 要朗读的组合键。
+*/
+fn describe_combo_key(context: &Weak<Context>, combo_key: &ComboKey) -> String {
+    let mut parts = vec![];
+    if !combo_key.modify_keys.is_empty() {
+        parts.push(combo_key.modify_keys.to_string());
+    }
+    parts.push(String::from(combo_key.main_key));
+    let mut text = parts.join(" + ");
+    match combo_key.state {
+        State::DoublePress => text += &format!(" {}", t!("program.input_help_double")),
+        State::TriplePress => text += &format!(" {}", t!("program.input_help_triple")),
+        State::LongPress => text += &format!(" {}", t!("program.input_help_long")),
+        _ => {}
+    }
+
+    if let Some(talent) = context
+        .get_talent_provider()
+        .get_talent_by_combo_key(combo_key)
+    {
+        text += &format!(" {}", talent.get_doc());
+    } else if let Some(layer) = context
+        .get_config_manager()
+        .get_section::<HotKeysConfig>()
+        .find_layer_by_key("", combo_key)
+    {
+        text += &format!(" {}", t!("program.input_help_layer", name = layer.name));
+    }
+    text
+}
+
+/**
+处理在命令层中按下的键。
+按下Esc键退出命令层；按下子层的热键进入子层；按下层中能力的热键执行能力并退出命令层；按下其他的键提示错误并退出命令层。
+命令层中的按键都会被拦截，避免误输入到前台程序。
+`context` 读屏的上下文环境。
+`layer` 当前所在的层的名称。
+`combo_key` 当前按下的组合键，已经去掉了进入层时按住的修饰键。
+*/
+fn process_layer_key(context: Weak<Context>, layer: &str, combo_key: &ComboKey) -> LRESULT {
+    let mng = context.get_commander().get_keyboard_manager();
+    if combo_key.main_key == Keys::VkEscape && combo_key.modify_keys.is_empty() {
+        mng.exit_layer(Some("boundary.wav"));
+        return LRESULT(1);
+    }
+
+    let hotkeys = context.get_config_manager().get_section::<HotKeysConfig>();
+    if let Some(sub) = hotkeys.find_layer_by_key(layer, combo_key) {
+        mng.enter_layer(&sub.name, combo_key.modify_keys);
+        return LRESULT(1);
+    }
+
+    let talent = hotkeys
+        .get_layer(layer)
+        .and_then(|l| l.get_talent_id(combo_key))
+        .and_then(|id| context.get_talent_provider().get_talent_by_id(id));
+    match talent {
+        Some(talent) => {
+            mng.exit_layer(None);
+            execute(context, talent);
+        }
+        None => mng.exit_layer(Some("error.wav")),
+    }
+    LRESULT(1)
+}
+
+/**
+判断按键是否是切换远程控制的热键，这个热键永远由本机处理，否则领导者无法停止控制跟随者。
+`context` 读屏的上下文环境。
+`combo_key` 当前按下的组合键。
+*/
+fn is_remote_control_key(context: &Weak<Context>, combo_key: &ComboKey) -> bool {
+    let combo_key = combo_key.change_state(State::SinglePress);
+    context
+        .get_talent_provider()
+        .get_talent_by_combo_key(&combo_key)
+        .is_some_and(|t| t.get_id() == "remote_toggle_control")
+}
+
+#[cfg(test)]
+mod test_hooks {
+    use crate::commander::{
+        hooks::fallback_combo_keys,
+        keyboard::{
+            combo_keys::{ComboKey, State},
+            keys::Keys,
+            modify_keys::ModifierKeys,
+        },
+    };
+
+    #[test]
+    fn test_fallback_combo_keys() {
+        let key = |state| ComboKey::new(Keys::VkH, ModifierKeys::RIGELA, state);
+        // 三击没有对应的能力时直接按单击处理，不会当成双击
+        assert_eq!(
+            vec![key(State::TriplePress), key(State::SinglePress)],
+            fallback_combo_keys(&key(State::TriplePress))
+        );
+        assert_eq!(
+            vec![key(State::DoublePress), key(State::SinglePress)],
+            fallback_combo_keys(&key(State::DoublePress))
+        );
+        assert_eq!(
+            vec![key(State::LongPress)],
+            fallback_combo_keys(&key(State::LongPress))
+        );
+    }
+}
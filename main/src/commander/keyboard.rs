@@ -13,18 +13,23 @@
 
 pub(crate) mod combo_keys;
 pub(crate) mod keys;
+pub(crate) mod layer;
 pub(crate) mod modify_keys;
+pub(crate) mod recognizer;
 
 use crate::{
     commander::keyboard::{
-        combo_keys::{
-            ComboKey,
-            State::{DoublePress, LongPress, SinglePress},
-        },
+        combo_keys::ComboKey,
         keys::Keys,
+        layer::LayerState,
+        modify_keys::ModifierKeys,
+        recognizer::{ComboKeyRecognizer, PressTimings, SystemClock},
     },
+    configs::items::hotkeys::HotKeysConfig,
     context::{Context, ContextAccessor},
+    performer::sound::SoundArgument::Single,
 };
+use log::info;
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -39,28 +44,29 @@ pub(crate) type KeyCallbackFn = Arc<dyn Fn(Keys, bool) + Send + Sync>;
 
 /// 键盘管理器
 pub(crate) struct KeyboardManager {
-    // 元组包含： 按键键名， 是否按下
-    pressed_cache: Arc<(Mutex<Keys>, AtomicBool)>,
-    release_cache: Arc<(Mutex<Keys>, AtomicBool)>,
+    // 识别单击、双击、三击和长按
+    recognizer: Mutex<ComboKeyRecognizer>,
     last_pressed_key: Mutex<Keys>,
     key_callback_fns: Mutex<Vec<(Vec<Keys>, KeyCallbackFn)>>,
+    // 命令层的状态
+    layer: Arc<Mutex<LayerState>>,
+    // 键盘帮助模式，所有的按键只朗读名称和对应的能力，不会执行
+    help_mode: AtomicBool,
     context: OnceLock<Weak<Context>>,
 }
 
-macro_rules! change_cache {
-    ($cache:expr, $key_str:expr, $state: expr) => {{
-        *$cache.0.lock().unwrap() = $key_str
-    };
-    $cache.1.store($state, Ordering::Relaxed);};
-}
-
 impl KeyboardManager {
     pub(crate) fn new() -> Self {
         Self {
-            pressed_cache: (Keys::VkNone.into(), AtomicBool::new(false)).into(),
-            release_cache: (Keys::VkNone.into(), AtomicBool::new(false)).into(),
+            recognizer: ComboKeyRecognizer::new(
+                Arc::new(SystemClock::new()),
+                PressTimings::default(),
+            )
+            .into(),
             last_pressed_key: Keys::VkNone.into(),
             key_callback_fns: Mutex::new(vec![]),
+            layer: Mutex::new(LayerState::new()).into(),
+            help_mode: AtomicBool::new(false),
             context: OnceLock::new(),
         }
     }
@@ -74,41 +80,24 @@ impl KeyboardManager {
         self.context.set(context).unwrap();
     }
 
-    /// 组合键处理
+    /**
+    修改识别双击、三击和长按的时间阈值。
+    `timings` 时间阈值。
+    */
+    pub(crate) fn set_press_timings(&self, timings: PressTimings) {
+        self.recognizer.lock().unwrap().set_timings(timings);
+    }
+
+    /**
+    组合键处理，按下时返回单击、双击或者三击的组合键，松开时如果是长按，返回长按的组合键。
+    `key` 组合键，松开时只使用其中的主键。
+    `pressed` 是否是按下。
+    */
     pub(crate) fn process_combo_key(&self, key: &ComboKey, pressed: bool) -> Option<ComboKey> {
+        let mut recognizer = self.recognizer.lock().unwrap();
         match pressed {
-            true if key.main_key == { self.pressed_cache.0.lock().unwrap().clone() }
-                && self.pressed_cache.1.load(Ordering::Acquire) =>
-            {
-                // 产生双击，把按压缓存的状态设为默认
-                change_cache!(self.pressed_cache, Keys::VkNone, false);
-                Some(key.change_state(DoublePress))
-            }
-
-            true => {
-                // 第一次按下某个键， 按压缓存设为按下，释放缓存设为松开， 启动延时任务。
-                change_cache!(self.pressed_cache, key.main_key.clone(), true);
-                change_cache!(self.release_cache, key.main_key.clone(), false);
-
-                //  200毫秒后， 按压状态松开
-                self.pressed_delay();
-                // 500毫秒后， 释放状态为长按
-                self.release_delay(&key.main_key);
-
-                Some(key.change_state(SinglePress))
-            }
-
-            false => {
-                // 松开任意键， 复原释放缓存
-                change_cache!(self.release_cache, Keys::VkNone, false);
-
-                match key.main_key == { *self.release_cache.0.lock().unwrap() }
-                    && self.release_cache.1.load(Ordering::Acquire)
-                {
-                    true => Some(key.change_state(LongPress)),
-                    false => None,
-                }
-            }
+            true => Some(recognizer.press(key)),
+            false => recognizer.release(key),
         }
     }
 
@@ -147,50 +136,119 @@ impl KeyboardManager {
         self.key_callback_fns.lock().unwrap().clone()
     }
 
-    // 处理大小写锁定键
-    pub(crate) fn capital_handle(&self, state: bool, hook_toggle: &AtomicBool) {
-        hook_toggle.store(true, Ordering::Relaxed);
-        send_key(VK_CAPITAL);
-        hook_toggle.store(false, Ordering::Relaxed);
+    /// 判断是否处于键盘帮助模式。
+    pub(crate) fn is_help_mode(&self) -> bool {
+        self.help_mode.load(Ordering::Acquire)
+    }
 
+    /**
+    开启或关闭键盘帮助模式，同时退出当前的命令层。
+    `enabled` 是否开启。
+    */
+    pub(crate) fn set_help_mode(&self, enabled: bool) {
+        self.exit_layer(None);
+        self.help_mode.store(enabled, Ordering::Release);
+    }
+
+    /// 获取当前所在的命令层的名称，不在任何层中时返回None。
+    pub(crate) fn get_layer(&self) -> Option<String> {
+        self.layer.lock().unwrap().get_name().map(str::to_string)
+    }
+
+    /**
+    获取在命令层中按下的热键，进入层时按住的修饰键在松开之前会被忽略，例如`RigelA+G`之后直接按`RigelA+H`和按`H`是一样的。
+    `key` 当前按下的组合键。
+    */
+    pub(crate) fn get_layer_key(&self, key: &ComboKey) -> ComboKey {
+        self.layer.lock().unwrap().get_key(key)
+    }
+
+    /**
+    松开修饰键时调用，松开以后再按下的修饰键在命令层中不再忽略。
+    `modify_keys` 当前还按住的修饰键。
+    */
+    pub(crate) fn release_layer_modifiers(&self, modify_keys: ModifierKeys) {
+        self.layer.lock().unwrap().release(modify_keys);
+    }
+
+    /**
+    进入一个命令层，接下来按下的键在这个层的热键表中查找，层不存在时不做任何操作。
+    进入时播放提示音，层设置了超时的话，超时后自动退出。
+    `name` 层的名称。
+    `held` 进入层的热键中的修饰键。
+    */
+    pub(crate) fn enter_layer(&self, name: &str, held: ModifierKeys) {
+        let Some(context) = self.context.get() else {
+            return;
+        };
+        let hotkeys = context.get_config_manager().get_section::<HotKeysConfig>();
+        let Some(layer) = hotkeys.get_layer(name) else {
+            return;
+        };
+        let generation = self.layer.lock().unwrap().enter(&layer.name, held);
+        info!("Entered the keymap layer {}.", name);
+        self.play_cue("tip.wav");
+
+        if layer.timeout > 0 {
+            self.layer_timeout_delay(generation, layer.timeout);
+        }
+    }
+
+    /**
+    退出当前的命令层，回到普通的热键。
+    `cue` 退出时播放的提示音，执行了层中的能力时不需要播放。
+    */
+    pub(crate) fn exit_layer(&self, cue: Option<&'static str>) {
+        if !self.layer.lock().unwrap().exit() {
+            return;
+        }
+        if let Some(cue) = cue {
+            self.play_cue(cue);
+        }
+    }
+
+    // 播放命令层的提示音
+    fn play_cue(&self, cue: &'static str) {
         let Some(context) = self.context.get() else {
             return;
         };
         let ctx = context.clone();
         context.get_work_runtime().spawn(async move {
-            let info = if !state { "大写" } else { "小写" };
-            ctx.get_performer().speak(&info.to_string()).await;
+            ctx.get_performer().play_sound(Single(cue)).await;
         });
     }
 
-    // 按键按下延时处理
-    fn pressed_delay(&self) {
+    // 命令层超时处理
+    fn layer_timeout_delay(&self, generation: u64, timeout: u64) {
         let Some(context) = self.context.get() else {
             return;
         };
-        let pressed_cache = self.pressed_cache.clone();
+        let layer = self.layer.clone();
+        let ctx = context.clone();
 
         context.get_work_runtime().spawn(async move {
-            // 延时200毫秒后， 取消双击
-            sleep(Duration::from_millis(200)).await;
-            pressed_cache.1.store(false, Ordering::SeqCst);
+            sleep(Duration::from_millis(timeout)).await;
+            // 这段时间内没有再进入过其他的层（包括同一个层），才退出
+            let expired = layer.lock().unwrap().expire(generation);
+            if expired {
+                ctx.get_performer().play_sound(Single("boundary.wav")).await;
+            }
         });
     }
 
-    // 按键释放延时处理
-    fn release_delay(&self, key: &Keys) {
+    // 处理大小写锁定键
+    pub(crate) fn capital_handle(&self, state: bool, hook_toggle: &AtomicBool) {
+        hook_toggle.store(true, Ordering::Relaxed);
+        send_key(VK_CAPITAL);
+        hook_toggle.store(false, Ordering::Relaxed);
+
         let Some(context) = self.context.get() else {
             return;
         };
-        let release_cache = self.release_cache.clone();
-        let key2 = key.clone();
-
+        let ctx = context.clone();
         context.get_work_runtime().spawn(async move {
-            sleep(Duration::from_millis(500)).await;
-            // 如果持续500毫秒,释放缓存键和传入的按键相同，则释放的缓存状态为长按
-            if key2 == { *release_cache.0.lock().unwrap() } {
-                release_cache.1.store(true, Ordering::SeqCst);
-            }
+            let info = if !state { "大写" } else { "小写" };
+            ctx.get_performer().speak(&info.to_string()).await;
         });
     }
 }
@@ -21,7 +21,7 @@ use serde::{Deserialize, Serialize};
 use crate::commander::keyboard::{keys::Keys, modify_keys::ModifierKeys};
 
 /// 定义组合键
-/// Example: combo_keys!("RigelA", Keys::VkEsc), combo_keys!("RigelA", Keys::F12, double),
+/// Example: combo_keys!("RigelA", Keys::VkEsc), combo_keys!("RigelA", Keys::F12, double), combo_keys!("RigelA", Keys::F12, triple),
 #[macro_export]
 macro_rules! combo_key {
     ($key: path) => {
@@ -30,6 +30,9 @@ macro_rules! combo_key {
     ($key: path, double) => {
         ComboKey::new($key, ModifierKeys::empty(), State::DoublePress)
     };
+    ($key: path, triple) => {
+        ComboKey::new($key, ModifierKeys::empty(), State::TriplePress)
+    };
     ($key: path, long) => {
         ComboKey::new($key, ModifierKeys::empty(), State::LongPress)
     };
@@ -39,6 +42,9 @@ macro_rules! combo_key {
     ($mdf: literal, $key: path, double) => {
         ComboKey::new($key, ModifierKeys::from($mdf), State::DoublePress)
     };
+    ($mdf: literal, $key: path, triple) => {
+        ComboKey::new($key, ModifierKeys::from($mdf), State::TriplePress)
+    };
     ($mdf: literal, $key: path, long) => {
         ComboKey::new($key, ModifierKeys::from($mdf), State::LongPress)
     };
@@ -49,6 +55,7 @@ pub(crate) enum State {
     Idle,
     SinglePress,
     DoublePress,
+    TriplePress,
     LongPress,
 }
 
@@ -111,6 +118,7 @@ impl Display for ComboKey {
         let state = match self.state {
             State::SinglePress => "",
             State::DoublePress => "(Double)",
+            State::TriplePress => "(Triple)",
             State::LongPress => "(Long)",
             _ => "",
         };
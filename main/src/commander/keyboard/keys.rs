@@ -889,7 +889,8 @@ macro_rules! impl_keys_str_into {
             fn from(value: Keys) -> Self {
                 match value {
                     $($v => t!($k).to_string(),) *
-                    _ => unreachable!()
+                    // 没有翻译的键（例如游戏手柄的键）使用枚举的名称，键盘帮助模式中可能按下任何键
+                    _ => format!("{:?}", value)
                 }
             }
         }
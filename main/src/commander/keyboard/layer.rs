@@ -0,0 +1,164 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::commander::keyboard::{combo_keys::ComboKey, modify_keys::ModifierKeys};
+
+/**
+ * 命令层的状态。
+ * 进入层的热键通常带有修饰键，例如`RigelA+G`，用户不一定会先松开它们再按层中的键，
+ * 所以进入层时还按住的修饰键在松开之前会被忽略，`RigelA+G`之后直接按`RigelA+H`和先松开再按`H`是一样的。
+ * */
+#[derive(Debug)]
+pub(crate) struct LayerState {
+    // 当前所在的命令层
+    name: Option<String>,
+    // 进入层的次数，用于判断超时任务是否已经过期
+    generation: u64,
+    // 进入层时按住，并且之后还没有松开过的修饰键
+    held: ModifierKeys,
+}
+
+impl LayerState {
+    pub(crate) fn new() -> Self {
+        Self {
+            name: None,
+            generation: 0,
+            held: ModifierKeys::empty(),
+        }
+    }
+
+    /// 获取当前所在的命令层的名称
+    pub(crate) fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /**
+     * 进入一个命令层，返回这次进入的序号，超时的时候用来判断是否又进入过其他的层。
+     * 从一个层进入子层时，之前忽略的修饰键仍然会被忽略。
+     * `name` 层的名称。
+     * `held` 进入层的热键中的修饰键。
+     * */
+    pub(crate) fn enter(&mut self, name: &str, held: ModifierKeys) -> u64 {
+        self.name = Some(name.to_string());
+        self.held |= held;
+        self.generation += 1;
+        self.generation
+    }
+
+    /// 退出当前的命令层，返回之前是否在层中
+    pub(crate) fn exit(&mut self) -> bool {
+        self.held = ModifierKeys::empty();
+        self.name.take().is_some()
+    }
+
+    /**
+     * 命令层超时的时候退出，返回是否退出了。这段时间内又进入过其他的层（包括同一个层）时不会退出。
+     * `generation` 进入层时返回的序号。
+     * */
+    pub(crate) fn expire(&mut self, generation: u64) -> bool {
+        self.generation == generation && self.exit()
+    }
+
+    /**
+     * 修饰键被松开以后，再次按下时不再忽略。
+     * `modify_keys` 当前还按住的修饰键。
+     * */
+    pub(crate) fn release(&mut self, modify_keys: ModifierKeys) {
+        self.held &= modify_keys;
+    }
+
+    /**
+     * 获取在层中按下的热键，去掉进入层时就按住的修饰键。
+     * `key` 当前按下的组合键。
+     * */
+    pub(crate) fn get_key(&self, key: &ComboKey) -> ComboKey {
+        ComboKey::new(
+            key.main_key,
+            key.modify_keys.difference(self.held),
+            key.state,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commander::keyboard::{combo_keys::State, keys::Keys};
+
+    fn key(modify_keys: ModifierKeys, main_key: Keys) -> ComboKey {
+        ComboKey::new(main_key, modify_keys, State::SinglePress)
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut state = LayerState::new();
+        let first = state.enter("goto", ModifierKeys::empty());
+        let second = state.enter("goto", ModifierKeys::empty());
+        // 重新进入同一个层以后，之前的超时任务已经过期
+        assert!(!state.expire(first));
+        assert_eq!(Some("goto"), state.get_name());
+        assert!(state.expire(second));
+        assert_eq!(None, state.get_name());
+        // 已经退出的层不会再次超时
+        assert!(!state.expire(second));
+
+        // 执行了能力退出以后再进入，旧的超时任务也不会让新的层退出
+        let third = state.enter("goto", ModifierKeys::empty());
+        assert!(state.exit());
+        let fourth = state.enter("goto", ModifierKeys::empty());
+        assert!(!state.expire(third));
+        assert!(state.expire(fourth));
+    }
+
+    #[test]
+    fn test_held_modifiers() {
+        let mut state = LayerState::new();
+        state.enter("goto", ModifierKeys::RIGELA);
+        // 还按住RigelA时按下H
+        assert_eq!(
+            key(ModifierKeys::empty(), Keys::VkH),
+            state.get_key(&key(ModifierKeys::RIGELA, Keys::VkH))
+        );
+        // 其他的修饰键仍然有效
+        assert_eq!(
+            key(ModifierKeys::SHIFT, Keys::VkH),
+            state.get_key(&key(ModifierKeys::RIGELA | ModifierKeys::SHIFT, Keys::VkH))
+        );
+        // 松开以后再按下的RigelA不会被忽略
+        state.release(ModifierKeys::empty());
+        assert_eq!(
+            key(ModifierKeys::RIGELA, Keys::VkH),
+            state.get_key(&key(ModifierKeys::RIGELA, Keys::VkH))
+        );
+    }
+
+    #[test]
+    fn test_nested_modifiers() {
+        let mut state = LayerState::new();
+        state.enter("goto", ModifierKeys::RIGELA);
+        // 进入子层时又按住了Shift
+        state.enter("goto_web", ModifierKeys::SHIFT);
+        assert_eq!(Some("goto_web"), state.get_name());
+        assert_eq!(
+            key(ModifierKeys::empty(), Keys::VkH),
+            state.get_key(&key(ModifierKeys::RIGELA | ModifierKeys::SHIFT, Keys::VkH))
+        );
+        // 退出以后不再忽略任何修饰键
+        state.exit();
+        state.enter("goto", ModifierKeys::empty());
+        assert_eq!(
+            key(ModifierKeys::RIGELA, Keys::VkH),
+            state.get_key(&key(ModifierKeys::RIGELA, Keys::VkH))
+        );
+    }
+}
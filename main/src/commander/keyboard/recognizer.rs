@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::commander::keyboard::combo_keys::{ComboKey, State};
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// 时钟，组合键识别器通过它获取当前的时间，测试时可以使用手动调整的时钟
+pub(crate) trait Clock: Send + Sync {
+    /// 获取从某个固定的时间点开始经过的时间
+    fn now(&self) -> Duration;
+}
+
+/// 系统时钟，从创建的时候开始计时
+pub(crate) struct SystemClock(Instant);
+
+impl SystemClock {
+    pub(crate) fn new() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// 识别多次按下和长按的时间阈值
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PressTimings {
+    /// 连续两次按下同一个组合键的间隔小于这个时间时，识别为双击或者三击
+    pub(crate) multi_press_interval: Duration,
+    /// 按住一个键超过这个时间后松开，识别为长按
+    pub(crate) long_press_duration: Duration,
+}
+
+impl Default for PressTimings {
+    fn default() -> Self {
+        Self {
+            multi_press_interval: Duration::from_millis(200),
+            long_press_duration: Duration::from_millis(500),
+        }
+    }
+}
+
+/**
+ * 组合键识别器，根据按下和松开的时间识别单击、双击、三击和长按。
+ * 识别器不启动任何定时任务，所有的判断都在按下和松开的时候根据时钟完成，所以结果是确定的。
+ * */
+pub(crate) struct ComboKeyRecognizer {
+    clock: Arc<dyn Clock>,
+    timings: PressTimings,
+    // 最后一次按下的组合键、按下的时间和连续按下的次数
+    last_press: Option<(ComboKey, Duration, u32)>,
+}
+
+impl ComboKeyRecognizer {
+    /**
+     * 创建一个组合键识别器。
+     * `clock` 获取时间的时钟。
+     * `timings` 时间阈值。
+     * */
+    pub(crate) fn new(clock: Arc<dyn Clock>, timings: PressTimings) -> Self {
+        Self {
+            clock,
+            timings,
+            last_press: None,
+        }
+    }
+
+    /**
+     * 修改时间阈值。
+     * `timings` 时间阈值。
+     * */
+    pub(crate) fn set_timings(&mut self, timings: PressTimings) {
+        self.timings = timings;
+    }
+
+    /**
+     * 处理组合键的按下，返回识别出的单击、双击或者三击，三击以后重新开始计数。
+     * 按住不放时系统重复发送的按下消息不应该传给识别器，否则无法识别长按。
+     * `key` 按下的组合键，状态会被忽略。
+     * */
+    pub(crate) fn press(&mut self, key: &ComboKey) -> ComboKey {
+        let key = key.change_state(State::Idle);
+        let now = self.clock.now();
+        let count = match self.last_press {
+            Some((k, t, c)) if k == key && c < 3 && now - t < self.timings.multi_press_interval => {
+                c + 1
+            }
+            _ => 1,
+        };
+        self.last_press = Some((key, now, count));
+        key.change_state(match count {
+            3 => State::TriplePress,
+            2 => State::DoublePress,
+            _ => State::SinglePress,
+        })
+    }
+
+    /**
+     * 处理组合键的松开，如果松开的是最后按下的主键，并且按住的时间超过阈值，返回长按的组合键。
+     * 先松开修饰键再松开主键也可以识别，返回的组合键包含按下时的修饰键。
+     * `key` 松开的主键。
+     * */
+    pub(crate) fn release(&mut self, key: &ComboKey) -> Option<ComboKey> {
+        let (k, t, _) = self.last_press?;
+        if k.main_key != key.main_key {
+            return None;
+        }
+        if self.clock.now() - t < self.timings.long_press_duration {
+            return None;
+        }
+        // 长按以后不再和下一次按下组成双击
+        self.last_press = None;
+        Some(k.change_state(State::LongPress))
+    }
+}
+
+impl Debug for ComboKeyRecognizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComboKeyRecognizer")
+            .field("timings", &self.timings)
+            .field("last_press", &self.last_press)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commander::keyboard::{keys::Keys, modify_keys::ModifierKeys};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct ManualClock(Mutex<Duration>);
+
+    impl ManualClock {
+        fn advance(&self, millis: u64) {
+            *self.0.lock().unwrap() += Duration::from_millis(millis);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Duration {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn make_recognizer() -> (Arc<ManualClock>, ComboKeyRecognizer) {
+        let clock = Arc::new(ManualClock::default());
+        let recognizer = ComboKeyRecognizer::new(clock.clone(), PressTimings::default());
+        (clock, recognizer)
+    }
+
+    fn key(main_key: Keys) -> ComboKey {
+        ComboKey::new(main_key, ModifierKeys::RIGELA, State::Idle)
+    }
+
+    #[test]
+    fn test_multi_press() {
+        let (clock, mut r) = make_recognizer();
+        assert_eq!(r.press(&key(Keys::VkF12)).state, State::SinglePress);
+        clock.advance(100);
+        assert_eq!(r.press(&key(Keys::VkF12)).state, State::DoublePress);
+        clock.advance(100);
+        assert_eq!(r.press(&key(Keys::VkF12)).state, State::TriplePress);
+        clock.advance(100);
+        assert_eq!(r.press(&key(Keys::VkF12)).state, State::SinglePress);
+    }
+
+    #[test]
+    fn test_multi_press_timeout() {
+        let (clock, mut r) = make_recognizer();
+        r.press(&key(Keys::VkF12));
+        clock.advance(200);
+        assert_eq!(r.press(&key(Keys::VkF12)).state, State::SinglePress);
+        clock.advance(50);
+        assert_eq!(r.press(&key(Keys::VkF11)).state, State::SinglePress);
+
+        r.set_timings(PressTimings {
+            multi_press_interval: Duration::from_millis(400),
+            ..Default::default()
+        });
+        clock.advance(300);
+        assert_eq!(r.press(&key(Keys::VkF11)).state, State::DoublePress);
+    }
+
+    #[test]
+    fn test_long_press() {
+        let (clock, mut r) = make_recognizer();
+        r.press(&key(Keys::VkF12));
+        clock.advance(499);
+        assert_eq!(r.release(&key(Keys::VkF12)), None);
+
+        r.press(&key(Keys::VkF10));
+        clock.advance(500);
+        assert_eq!(r.release(&key(Keys::VkF11)), None);
+        let released = ComboKey::new(Keys::VkF10, ModifierKeys::empty(), State::Idle);
+        assert_eq!(
+            r.release(&released),
+            Some(key(Keys::VkF10).change_state(State::LongPress))
+        );
+
+        // 长按以后再按下是单击
+        assert_eq!(r.press(&key(Keys::VkF10)).state, State::SinglePress);
+    }
+}
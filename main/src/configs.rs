@@ -11,35 +11,151 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
+pub(crate) mod bundle;
 pub(crate) mod items;
+pub(crate) mod migration;
 pub(crate) mod operations;
+pub(crate) mod overlay;
+pub(crate) mod watcher;
 
+use crate::configs::items::automation::AutomationConfig;
 use crate::configs::items::general::GeneralConfig;
 use crate::configs::items::hotkeys::HotKeysConfig;
+use crate::configs::items::ime::ImeConfig;
+use crate::configs::items::log::LogConfig;
 use crate::configs::items::mouse::MouseConfig;
 use crate::configs::items::navigation::NavigationConfig;
+use crate::configs::items::profile::ProfileConfig;
+use crate::configs::items::remote::RemoteConfig;
 use crate::configs::items::tts::TtsConfig;
+use crate::configs::items::window_text::WindowTextConfig;
+use crate::configs::migration::{deserialize_lossy, migrate, CONFIG_VERSION};
+use crate::configs::overlay::{apply_profile, merge_three, split_config};
 use arc_swap::ArcSwap;
+use chrono::Local;
 use log::error as err_log;
 use log::info;
 use serde::{Deserialize, Serialize};
-use std::fs::{read_to_string, File};
-use std::io::Write;
+use std::fmt::{Debug, Formatter};
+use std::fs::{copy, metadata, read_to_string, File};
+use std::io::{ErrorKind, Write};
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::{sleep, spawn};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+use toml::Table;
 
-/// 配置项目的根元素
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+/// 配置项目的根元素，配置文件中缺少的配置项使用默认值，避免整个配置文件被重新生成
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub(crate) struct ConfigRoot {
+    // 配置文件的版本号，用于升级旧版本的配置文件
+    pub(crate) version: u32,
     pub(crate) tts_config: TtsConfig,
     pub(crate) mouse_config: MouseConfig,
     pub(crate) hotkeys_config: HotKeysConfig,
     pub(crate) general_config: GeneralConfig,
     pub(crate) navigation_config: NavigationConfig,
+    pub(crate) ime_config: ImeConfig,
+    pub(crate) window_text_config: WindowTextConfig,
+    pub(crate) automation_config: AutomationConfig,
+    pub(crate) remote_config: RemoteConfig,
+    pub(crate) log_config: LogConfig,
+    // 应用程序的配置方案，放在最后，因为toml要求表数组写在普通的表之后
+    pub(crate) profiles: Vec<ProfileConfig>,
+}
+
+impl Default for ConfigRoot {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            tts_config: Default::default(),
+            mouse_config: Default::default(),
+            hotkeys_config: Default::default(),
+            general_config: Default::default(),
+            navigation_config: Default::default(),
+            ime_config: Default::default(),
+            window_text_config: Default::default(),
+            automation_config: Default::default(),
+            remote_config: Default::default(),
+            log_config: Default::default(),
+            profiles: Default::default(),
+        }
+    }
+}
+
+impl ConfigRoot {
+    /**
+     * 修正从配置文件读取到的配置，例如把明文的会话密码替换成校验值、把超出范围的值限制在范围内，返回是否有修改，有修改时需要写回配置文件。
+     * */
+    pub(crate) fn sanitize(&mut self) -> bool {
+        // 每一项都要修正，不能因为前面的修改而短路
+        let hotkeys = self.hotkeys_config.sanitize();
+        let remote = self.remote_config.sanitize();
+        hotkeys || remote
+    }
+}
+
+/// 可以单独读取和订阅的配置节
+pub(crate) trait ConfigSection: Clone + PartialEq + Send + Sync + 'static {
+    /// 从配置的根元素中取出这个配置节
+    fn select(root: &ConfigRoot) -> &Self;
+}
+
+macro_rules! impl_config_section {
+    ($($t:ty => $field:ident),* $(,)?) => {
+        $(impl ConfigSection for $t {
+            fn select(root: &ConfigRoot) -> &Self {
+                &root.$field
+            }
+        })*
+    };
+}
+
+impl_config_section!(
+    TtsConfig => tts_config,
+    MouseConfig => mouse_config,
+    HotKeysConfig => hotkeys_config,
+    GeneralConfig => general_config,
+    NavigationConfig => navigation_config,
+    ImeConfig => ime_config,
+    WindowTextConfig => window_text_config,
+    LogConfig => log_config,
+);
+
+type ConfigListener = Arc<dyn Fn(&ConfigRoot, &ConfigRoot) + Send + Sync>;
+
+// 配置改变的监听器
+#[derive(Default)]
+struct ConfigListeners(Mutex<Vec<ConfigListener>>);
+
+impl Debug for ConfigListeners {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigListeners").finish()
+    }
+}
+
+// 配置文件最后一次被读取或写入时的状态，用于区分外部程序的修改和自己的写入
+#[derive(Debug, Default)]
+struct FileState {
+    // 文件的修改时间
+    modified: Option<SystemTime>,
+    // 文件的内容
+    content: String,
+    // 文件被外部修改成了无法读取的内容，覆盖之前需要先备份
+    invalid: bool,
+    // 写入文件之前合并了外部的修改，这些修改还没有生效
+    merged: bool,
+}
+
+/// 重新加载配置文件时的错误
+#[derive(Debug)]
+pub(crate) struct ReloadError {
+    // 出错的行号，从1开始
+    pub(crate) line: usize,
+    pub(crate) message: String,
 }
 
 /// 配置管理器
@@ -47,70 +163,305 @@ pub(crate) struct ConfigRoot {
 pub(crate) struct ConfigManager {
     // 配置文件的路径
     pub(crate) path: PathBuf,
-    // 当前的配置
+    // 全局配置，也就是配置文件中的内容
     config: Arc<ArcSwap<ConfigRoot>>,
+    // 当前生效的配置，包含了配置方案覆盖的配置项
+    effective: ArcSwap<ConfigRoot>,
+    // 当前生效的配置方案的名称
+    profile: ArcSwap<Option<String>>,
     // 更新配置的操作时间,不要求过于精确，时间戳就可以，Instant是纳秒级别的
     update_time: Arc<AtomicU64>,
     // 延时写入是否完成
     write_finished: Arc<AtomicBool>,
+    // 生效的配置改变时需要通知的监听器
+    listeners: ConfigListeners,
+    // 配置文件的状态
+    file: Arc<Mutex<FileState>>,
+    // 安全模式，使用默认配置，不切换配置方案，也不读写配置文件
+    safe_mode: bool,
 }
 
 impl ConfigManager {
     /**
      * 创建一个配置管理器
+     * `path` 配置文件的路径。
+     * `safe_mode` 是否使用安全模式。
      * */
-    pub(crate) fn new(path: PathBuf) -> Self {
+    pub(crate) fn new(path: PathBuf, safe_mode: bool) -> Self {
         Self {
             path,
             config: Default::default(),
+            effective: Default::default(),
+            profile: Default::default(),
             update_time: AtomicU64::new(0).into(),
             write_finished: AtomicBool::new(true).into(),
+            listeners: Default::default(),
+            file: Default::default(),
+            safe_mode,
         }
     }
 
     /// 初始化当前配置，从配置文件获取配置信息
     pub(crate) fn apply(&self) {
-        self.config.store(Arc::new(self.read()));
+        if self.safe_mode {
+            info!("Running in safe mode, the config file is ignored.");
+        } else {
+            self.config.store(Arc::new(self.read()));
+        }
+        self.refresh();
 
         // 设置当前程序显示语言
-        let lang: String = self.get_config().general_config.lang.clone().into();
+        let lang: String = self.get_section::<GeneralConfig>().lang.into();
         rust_i18n::set_locale(&lang);
         info!("The current locale of the user is {lang}.");
     }
 
-    /// 获取当前的配置
+    /// 获取当前生效的配置
     pub(crate) fn get_config(&self) -> ConfigRoot {
-        self.config.load().deref().deref().clone()
+        self.effective.load().deref().deref().clone()
+    }
+
+    /**
+     * 获取当前生效的配置中的一个配置节，只复制这个配置节，适合在频繁发生的事件中使用。
+     * 例如`get_section::<MouseConfig>()`。
+     * */
+    pub(crate) fn get_section<T: ConfigSection>(&self) -> T {
+        T::select(&self.effective.load()).clone()
+    }
+
+    /**
+     * 订阅一个配置节的改变，修改了设置、切换了配置方案或者重新加载了配置文件，只要生效的配置节和之前不同就会调用回调函数。
+     * 回调函数在修改配置的线程中同步调用，耗时的操作应该放到异步任务中。
+     * `listener` 回调函数，参数是改变后的配置节。
+     * */
+    pub(crate) fn subscribe<T: ConfigSection>(
+        &self,
+        listener: impl Fn(&T) + Send + Sync + 'static,
+    ) {
+        let listener: ConfigListener = Arc::new(move |old, new| {
+            let section = T::select(new);
+            if T::select(old) != section {
+                listener(section);
+            }
+        });
+        self.listeners.0.lock().unwrap().push(listener);
     }
 
-    /// 修改当前的配置，修改完写入配置文件
+    /**
+     * 修改当前的配置，修改完写入配置文件。
+     * 如果有生效的配置方案，被方案覆盖的配置项会修改到方案中，其他的修改到全局配置中。
+     * `config` 修改后的配置，通常是先用get_config获取再修改的。
+     * */
     pub(crate) fn set_config(&self, config: &ConfigRoot) {
-        self.config.store(Arc::new(config.clone()));
+        let profile = self.profile.load();
+        let base = split_config(
+            &self.config.load(),
+            &self.get_config(),
+            config,
+            profile.as_deref(),
+        );
+        self.config.store(Arc::new(base));
+        self.refresh();
 
         self.update_time.store(get_time_stamp(), Ordering::Relaxed);
         self.write();
     }
 
+    /**
+     * 根据前台程序切换配置方案，返回生效的方案是否改变。
+     * `process_name` 前台程序的可执行文件名称。
+     * `class_name` 前台窗口的类名。
+     * */
+    pub(crate) fn switch_profile(&self, process_name: &str, class_name: &str) -> bool {
+        if self.safe_mode {
+            return false;
+        }
+        let name = self
+            .config
+            .load()
+            .profiles
+            .iter()
+            .find(|p| p.is_match(process_name, class_name))
+            .map(|p| p.name.clone());
+        if self.profile.load().deref().deref() == &name {
+            return false;
+        }
+        info!("The active profile is changed to {:?}.", name);
+        self.profile.store(Arc::new(name));
+        self.refresh();
+        true
+    }
+
+    /// 获取当前生效的配置方案的名称
+    pub(crate) fn get_profile_name(&self) -> Option<String> {
+        self.profile.load().deref().deref().clone()
+    }
+
+    /// 获取全局配置，也就是配置文件中的内容，不包含配置方案的覆盖
+    pub(crate) fn get_global_config(&self) -> ConfigRoot {
+        self.config.load().deref().deref().clone()
+    }
+
+    /**
+     * 使用新的全局配置代替当前的全局配置，并立即写入配置文件，例如导入设置包的时候。
+     * `config` 新的全局配置。
+     * */
+    pub(crate) fn replace_config(&self, config: ConfigRoot) {
+        self.config.store(Arc::new(config));
+        self.refresh();
+        self.save_config();
+    }
+
+    // 重新计算生效的配置，生效的方案已经被删除时回到全局配置
+    fn refresh(&self) {
+        let config = self.config.load();
+        let profile = self.profile.load();
+        if let Some(name) = profile.as_deref() {
+            if !config.profiles.iter().any(|p| p.name == name) {
+                self.profile.store(Arc::new(None));
+            }
+        }
+        let effective = Arc::new(apply_profile(&config, self.profile.load().as_deref()));
+        let old = self.effective.swap(effective.clone());
+
+        // 先复制监听器再调用，回调函数中可以再次修改配置或者订阅
+        let listeners = self.listeners.0.lock().unwrap().clone();
+        for listener in listeners {
+            listener(&old, &effective);
+        }
+    }
+
     /*
-     * 读取配置数据。如果不存在配置文件，写入默认配置
+     * 读取配置数据。如果不存在配置文件，写入默认配置。
+     * 旧版本的配置文件会被升级；无效的配置项使用默认值代替，其他的配置项仍然保留，无法完整读取的配置文件会先备份再重新写入。
      * */
     pub(crate) fn read(&self) -> ConfigRoot {
-        _read_config(&self.path).unwrap_or_else(|_| {
-            err_log!("The config file is invalid, this will be regenerated.");
-            _write_config(&self.path, &ConfigRoot::default()).unwrap_or_else(|_| {
-                err_log!("Can't write the default config file.");
-            });
-            info!("Using default config.");
-            Default::default()
-        })
+        let data = match read_to_string(&self.path) {
+            Ok(d) => d,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("The config file does not exist, using default config.");
+                _write_config(&self.path, &ConfigRoot::default(), &self.file)
+                    .unwrap_or_else(|_| err_log!("Can't write the default config file."));
+                return Default::default();
+            }
+            Err(e) => {
+                // 配置文件可能正在被其他程序使用，不要覆盖它
+                err_log!("Can't read the config file, using default config. {}", e);
+                return Default::default();
+            }
+        };
+        let mut table = match data.parse::<Table>() {
+            Ok(t) => t,
+            Err(e) => {
+                err_log!(
+                    "The config file is invalid, this will be regenerated. {}",
+                    e
+                );
+                _backup_config(&self.path);
+                _write_config(&self.path, &ConfigRoot::default(), &self.file)
+                    .unwrap_or_else(|_| err_log!("Can't write the default config file."));
+                return Default::default();
+            }
+        };
+
+        self.record_file(data);
+
+        let version = migrate(&mut table);
+        let (mut config, lossy) = deserialize_lossy(table);
+        if lossy {
+            err_log!("Some items of the config file are invalid, the default values are used.");
+            _backup_config(&self.path);
+        }
+        let sanitized = config.sanitize();
+        if lossy || sanitized || version < CONFIG_VERSION {
+            _write_config(&self.path, &config, &self.file)
+                .unwrap_or_else(|_| err_log!("Can't write the config file."));
+        }
+        config
+    }
+
+    // 记录读取到的配置文件的内容和修改时间
+    fn record_file(&self, content: String) {
+        let mut file = self.file.lock().unwrap();
+        file.modified = metadata(&self.path).and_then(|m| m.modified()).ok();
+        file.content = content;
+        file.invalid = false;
+    }
+
+    /**
+     * 如果配置文件被外部程序修改过，重新加载它，没有修改过则返回None。
+     * 新的配置和内存中还没有写入的修改合并，两边修改了同一个配置项时以文件中的为准，合并后的配置通过正常的修改流程生效，所以订阅者都会收到通知。
+     * 文件无法解析时保留当前的配置，返回出错的位置，之后写入配置时会先备份这个文件。
+     * */
+    pub(crate) fn reload_if_changed(&self) -> Option<Result<(), ReloadError>> {
+        if self.safe_mode {
+            return None;
+        }
+        let mut file = self.file.lock().unwrap();
+        if file.merged {
+            // 外部的修改已经在写入文件的时候合并到了全局配置中，只需要让它生效
+            file.merged = false;
+            drop(file);
+            info!("The config file was changed externally before writing, the changes are merged.");
+            self.refresh();
+            return Some(Ok(()));
+        }
+        let modified = metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified.is_none() || modified == file.modified {
+            return None;
+        }
+        file.modified = modified;
+        let content = read_to_string(&self.path).ok()?;
+        if content == file.content {
+            return None;
+        }
+
+        let mut theirs = match content.parse::<Table>() {
+            Ok(t) => t,
+            Err(e) => {
+                // 同一个错误只报告一次，修改时间改变后才会再次检查
+                file.invalid = true;
+                let start = e.span().map_or(0, |s| s.start);
+                return Some(Err(ReloadError {
+                    line: content[..start].matches('\n').count() + 1,
+                    message: e.message().to_string(),
+                }));
+            }
+        };
+        info!("The config file is changed externally, reloading.");
+        migrate(&mut theirs);
+        let merged = merge_file(&file.content, &self.config.load(), &theirs);
+        // 内存中还有文件里没有的修改，稍后写回文件
+        let unsaved = merged != theirs;
+        let (mut config, lossy) = deserialize_lossy(merged);
+        if lossy {
+            err_log!("Some items of the config file are invalid, the default values are used.");
+        }
+        let sanitized = config.sanitize();
+        file.content = content;
+        file.invalid = false;
+        // 释放锁以后再通知订阅者，回调函数中可能会再次修改配置
+        drop(file);
+
+        self.config.store(Arc::new(config));
+        self.refresh();
+        if unsaved || lossy || sanitized {
+            self.update_time.store(get_time_stamp(), Ordering::Relaxed);
+            self.write();
+        }
+        Some(Ok(()))
     }
 
     // 延时写出配置数据。
     fn write(&self) {
+        if self.safe_mode {
+            return;
+        }
         let path = self.path.clone();
         let config = self.config.clone();
         let update_time = self.update_time.clone();
         let write_finished = self.write_finished.clone();
+        let file = self.file.clone();
 
         // 写出操作再新的线程进行
         spawn(move || {
@@ -129,8 +480,8 @@ impl ConfigManager {
             }
 
             // 开始执行配置写入
-            let cfg = config.load();
-            _write_config(&path, &cfg).unwrap_or_else(|_| err_log!("Can't write the config file."));
+            _flush_config(&path, &config, &file)
+                .unwrap_or_else(|_| err_log!("Can't write the config file."));
 
             write_finished.store(true, Ordering::SeqCst);
         });
@@ -138,32 +489,124 @@ impl ConfigManager {
 
     /// 直接保存配置，不延时
     pub(crate) fn save_config(&self) {
+        if self.safe_mode {
+            return;
+        }
         let path = self.path.clone();
         let config = self.config.clone();
+        let file = self.file.clone();
 
         let _job = spawn(move || {
-            let cfg = config.load();
-            _write_config(&path, &cfg).unwrap_or_else(|_| err_log!("写入配置文件失败"));
+            _flush_config(&path, &config, &file).unwrap_or_else(|_| err_log!("写入配置文件失败"));
         })
         .join();
     }
 }
 
-// 写出配置到指定文件
-fn _write_config(path: &PathBuf, config: &ConfigRoot) -> Result<(), Box<dyn std::error::Error>> {
+// 写出配置到指定文件，并记录文件的状态
+fn _write_config(
+    path: &PathBuf,
+    config: &ConfigRoot,
+    state: &Mutex<FileState>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // 记录完文件的状态再释放锁，避免把自己的写入当成外部的修改
+    _write_locked(path, config, &mut state.lock().unwrap())
+}
+
+/*
+ * 把全局配置写出到文件。
+ * 文件监视器定时检查文件，如果文件在这之间被外部程序修改过，先和全局配置三方合并再写入，避免覆盖外部的修改，合并后的配置在下次检查时生效。
+ * */
+fn _flush_config(
+    path: &PathBuf,
+    config: &ArcSwap<ConfigRoot>,
+    state: &Mutex<FileState>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = state.lock().unwrap();
+    if let Some(theirs) = read_external_changes(path, &mut state) {
+        info!("The config file is changed externally, merging the changes before writing.");
+        // 合并期间其他线程可能修改了配置，rcu会使用最新的配置重新合并
+        config.rcu(|ours| {
+            let (mut merged, lossy) = deserialize_lossy(merge_file(&state.content, ours, &theirs));
+            if lossy {
+                err_log!("Some items of the config file are invalid, the default values are used.");
+            }
+            merged.sanitize();
+            merged
+        });
+        state.merged = true;
+    }
+    _write_locked(path, &config.load(), &mut state)
+}
+
+/*
+ * 读取上次读取或写入之后被外部程序修改过的配置文件，没有修改过时返回None。
+ * 无法解析的文件也返回None，并标记为无效，覆盖之前会先备份。
+ * */
+fn read_external_changes(path: &Path, state: &mut FileState) -> Option<Table> {
+    let modified = metadata(path).and_then(|m| m.modified()).ok();
+    if modified.is_none() || modified == state.modified {
+        return None;
+    }
+    let content = read_to_string(path).ok()?;
+    if content == state.content {
+        return None;
+    }
+    match content.parse::<Table>() {
+        Ok(mut theirs) => {
+            migrate(&mut theirs);
+            Some(theirs)
+        }
+        Err(_) => {
+            state.invalid = true;
+            None
+        }
+    }
+}
+
+/*
+ * 三方合并外部修改后的配置文件和全局配置，两边修改了同一个配置项时以文件中的为准。
+ * `base` 上次读取或写入的文件内容。
+ * `ours` 全局配置。
+ * `theirs` 外部修改后的配置文件，已经升级到当前版本。
+ * */
+fn merge_file(base: &str, ours: &ConfigRoot, theirs: &Table) -> Table {
+    let mut base = base.parse::<Table>().unwrap_or_default();
+    migrate(&mut base);
+    let ours = Table::try_from(ours).unwrap_or_default();
+    merge_three(&base, &ours, theirs)
+}
+
+// 写出配置到指定文件，调用者需要持有文件状态的锁
+fn _write_locked(
+    path: &PathBuf,
+    config: &ConfigRoot,
+    state: &mut FileState,
+) -> Result<(), Box<dyn std::error::Error>> {
     let cfg = toml::to_string(config)?;
-    let mut file = File::create(&path)?;
-    file.write_all(&cfg.into_bytes())?;
+    if state.invalid {
+        _backup_config(path);
+        state.invalid = false;
+    }
+    File::create(path)?.write_all(cfg.as_bytes())?;
+    state.modified = metadata(path).and_then(|m| m.modified()).ok();
+    state.content = cfg;
 
     Ok(())
 }
 
-// 读取配置文件
-fn _read_config(path: &PathBuf) -> Result<ConfigRoot, Box<dyn std::error::Error>> {
-    let mut data = read_to_string(path.clone())?;
-    let cfg = toml::from_str::<ConfigRoot>(data.as_mut_str())?;
-
-    Ok(cfg)
+// 备份无法完整读取的配置文件，文件名中带有时间，不会覆盖之前的备份
+fn _backup_config(path: &Path) {
+    let name = format!(
+        "{}.{}.bak",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        Local::now().format("%Y%m%d%H%M%S")
+    );
+    let backup = path.with_file_name(name);
+    match copy(path, &backup) {
+        Ok(_) => info!("The config file is backed up to {}.", backup.display()),
+        Err(e) => err_log!("Can't back up the config file. {}", e),
+    }
 }
 
 // 计算时间戳， 单位：秒
@@ -173,3 +616,170 @@ fn get_time_stamp() -> u64 {
         .expect("Time went backwards")
         .as_secs()
 }
+
+#[cfg(test)]
+mod test_configs {
+    use crate::configs::{_flush_config, ConfigManager, ConfigRoot};
+    use rigela_utils::remote::is_password_hash;
+    use std::{
+        fs::{create_dir_all, remove_dir_all, write, File},
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    // 在临时目录中创建默认的配置文件，然后读取它
+    fn manager(name: &str) -> ConfigManager {
+        let dir = std::env::temp_dir().join(format!("rigela-test-configs-{}", name));
+        remove_dir_all(&dir).unwrap_or(());
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        write(&path, toml::to_string(&ConfigRoot::default()).unwrap()).unwrap();
+        let manager = ConfigManager::new(path, false);
+        manager.apply();
+        manager
+    }
+
+    // 模拟外部程序修改配置文件，修改时间推后，保证和读取时的不同
+    fn write_external(path: &Path, content: &str, secs: u64) {
+        write(path, content).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn external_config(f: impl FnOnce(&mut ConfigRoot)) -> String {
+        let mut config = ConfigRoot::default();
+        f(&mut config);
+        toml::to_string(&config).unwrap()
+    }
+
+    #[test]
+    fn test_reload_unchanged() {
+        let manager = manager("unchanged");
+        assert!(manager.reload_if_changed().is_none());
+    }
+
+    #[test]
+    fn test_reload_theirs() {
+        let manager = manager("theirs");
+        write_external(
+            &manager.path,
+            &external_config(|c| c.tts_config.pitch = 30),
+            10,
+        );
+        assert!(matches!(manager.reload_if_changed(), Some(Ok(()))));
+        assert_eq!(30, manager.get_config().tts_config.pitch);
+        // 同一次修改只加载一次
+        assert!(manager.reload_if_changed().is_none());
+    }
+
+    #[test]
+    fn test_reload_merge() {
+        let manager = manager("merge");
+        let mut config = manager.get_config();
+        config.tts_config.speed = 70;
+        config.mouse_config.is_read = true;
+        manager.set_config(&config);
+        // 文件中修改了另一个配置项和同一个配置项
+        let content = external_config(|c| {
+            c.tts_config.pitch = 30;
+            c.mouse_config.is_read = false;
+            c.tts_config.speed = 90;
+        });
+        write_external(&manager.path, &content, 10);
+        assert!(matches!(manager.reload_if_changed(), Some(Ok(()))));
+        let config = manager.get_config();
+        assert_eq!(30, config.tts_config.pitch);
+        assert_eq!(90, config.tts_config.speed);
+        // 文件中的值和修改前相同，所以保留内存中的修改
+        assert!(config.mouse_config.is_read);
+    }
+
+    #[test]
+    fn test_reload_ours() {
+        let manager = manager("ours");
+        let mut config = manager.get_config();
+        config.tts_config.speed = 70;
+        manager.set_config(&config);
+        write_external(
+            &manager.path,
+            &external_config(|c| c.tts_config.pitch = 30),
+            10,
+        );
+        assert!(matches!(manager.reload_if_changed(), Some(Ok(()))));
+        let config = manager.get_config();
+        assert_eq!(70, config.tts_config.speed);
+        assert_eq!(30, config.tts_config.pitch);
+    }
+
+    #[test]
+    fn test_flush_after_external_edit() {
+        let manager = manager("flush");
+        let mut config = manager.get_config();
+        config.tts_config.speed = 70;
+        manager.set_config(&config);
+        // 文件监视器检查之前，文件被外部修改，然后延时写入开始执行
+        write_external(
+            &manager.path,
+            &external_config(|c| c.tts_config.pitch = 30),
+            10,
+        );
+        _flush_config(&manager.path, &manager.config, &manager.file).unwrap();
+        let content = std::fs::read_to_string(&manager.path).unwrap();
+        let saved: ConfigRoot = toml::from_str(&content).unwrap();
+        assert_eq!(70, saved.tts_config.speed);
+        assert_eq!(30, saved.tts_config.pitch);
+        // 合并的修改在下次检查时生效，之后不会再重复加载
+        assert!(matches!(manager.reload_if_changed(), Some(Ok(()))));
+        let config = manager.get_config();
+        assert_eq!(70, config.tts_config.speed);
+        assert_eq!(30, config.tts_config.pitch);
+        assert!(manager.reload_if_changed().is_none());
+    }
+
+    #[test]
+    fn test_read_password() {
+        let manager = manager("password");
+        let path = manager.path.clone();
+        write(
+            &path,
+            external_config(|c| c.remote_config.password = "secret".to_string()),
+        )
+        .unwrap();
+        // 读取时明文的会话密码被替换成校验值，并立即写回文件
+        let manager = ConfigManager::new(path.clone(), false);
+        manager.apply();
+        let password = manager.get_config().remote_config.password;
+        assert!(is_password_hash(&password));
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains(&password));
+        assert!(!content.contains("\"secret\""));
+        assert!(!format!("{:?}", manager.get_config()).contains(&password));
+    }
+
+    #[test]
+    fn test_reload_parse_error() {
+        let manager = manager("parse-error");
+        let path = manager.path.clone();
+        write_external(
+            &path,
+            "version = 1\n[tts_config]\nspeed = \npitch = 30\n",
+            10,
+        );
+        let Some(Err(e)) = manager.reload_if_changed() else {
+            panic!("The parse error is not reported.");
+        };
+        assert_eq!(3, e.line);
+        assert!(!e.message.is_empty());
+        // 保留当前的配置，同一个错误只报告一次
+        assert_eq!(50, manager.get_config().tts_config.pitch);
+        assert!(manager.reload_if_changed().is_none());
+        // 修正以后可以正常加载
+        write_external(&path, &external_config(|c| c.tts_config.pitch = 30), 20);
+        assert!(matches!(manager.reload_if_changed(), Some(Ok(()))));
+        assert_eq!(30, manager.get_config().tts_config.pitch);
+    }
+}
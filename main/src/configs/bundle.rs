@@ -0,0 +1,673 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::configs::{
+    items::profile::ProfileConfig,
+    migration::{deserialize_lossy, migrate, CONFIG_VERSION},
+    overlay::{merge_table, split_changes},
+    ConfigRoot,
+};
+use chrono::Local;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    fs::{create_dir_all, read, write, File},
+    io::{Error as IoError, Read, Seek, Write},
+    path::{Component, Path},
+};
+use toml::{Table, Value};
+use zip::{result::ZipError, write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+const MANIFEST_NAME: &str = "manifest.toml";
+const CONFIG_NAME: &str = "config.toml";
+const RESOURCES_DIR: &str = "resources";
+
+// 设置包的格式版本，格式不兼容时增加
+const BUNDLE_FORMAT: u32 = 1;
+
+// 设置包中单个文件的最大长度，超过的设置包视为无效，避免读取恶意的压缩包耗尽内存
+const MAX_ENTRY_SIZE: u64 = 16 * 1024 * 1024;
+// 设置包中所有文件的最大总长度
+const MAX_TOTAL_SIZE: u64 = 64 * 1024 * 1024;
+
+// 音效方案的文件扩展名，资源目录中的其他文件（例如帮助文档）会自动下载，不需要导出
+const SOUND_EXTENSIONS: [&str; 3] = ["wav", "mp3", "ogg"];
+
+/// 设置包的清单，记录生成设置包的版本和每个文件的校验和
+#[derive(Debug, Deserialize, Serialize)]
+struct Manifest {
+    format: u32,
+    app_version: String,
+    config_version: u32,
+    created: String,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ManifestFile {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+/// 设置包的错误
+#[derive(Debug)]
+pub(crate) enum BundleError {
+    Io(IoError),
+    Zip(ZipError),
+    /// 清单无法读取
+    InvalidManifest(String),
+    /// 设置包的格式比当前程序支持的新
+    UnsupportedFormat(u32),
+    /// 设置包中的配置比当前程序支持的新
+    NewerConfig(u32),
+    /// 文件的内容和清单中的校验和不一致，设置包可能已经损坏
+    ChecksumMismatch(String),
+    /// 清单中列出的文件不存在
+    MissingFile(String),
+    /// 文件的路径不安全，例如包含`..`
+    InvalidPath(String),
+    /// 配置文件无法读取
+    InvalidConfig(String),
+    /// 文件太大
+    TooLarge(String),
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Zip(e) => write!(f, "{}", e),
+            Self::InvalidManifest(e) => write!(f, "The manifest is invalid. {}", e),
+            Self::UnsupportedFormat(v) => write!(f, "The bundle format {} is not supported.", v),
+            Self::NewerConfig(v) => write!(f, "The config version {} is not supported.", v),
+            Self::ChecksumMismatch(p) => write!(f, "The checksum of {} does not match.", p),
+            Self::MissingFile(p) => write!(f, "The file {} is missing.", p),
+            Self::InvalidPath(p) => write!(f, "The path {} is invalid.", p),
+            Self::InvalidConfig(e) => write!(f, "The config is invalid. {}", e),
+            Self::TooLarge(p) => write!(f, "The file {} is too large.", p),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<IoError> for BundleError {
+    fn from(value: IoError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ZipError> for BundleError {
+    fn from(value: ZipError) -> Self {
+        Self::Zip(value)
+    }
+}
+
+/// 导入设置包的方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ImportMode {
+    /// 只修改设置包中和默认值不同的配置项，其他配置项和同名以外的配置方案保持不变
+    Merge,
+    /// 使用设置包中的配置代替当前的全部配置
+    Replace,
+}
+
+/// 导入设置包之前的预览
+#[derive(Debug)]
+pub(crate) struct ImportPreview {
+    /// 会被修改的配置节，例如`tts_config`
+    pub(crate) sections: Vec<String>,
+    /// 会被添加或者覆盖的音效文件数量
+    pub(crate) sounds: usize,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    digest(&SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// 检查设置包中的路径，只允许相对路径，防止解压到数据目录之外
+fn check_path(path: &str) -> Result<(), BundleError> {
+    let valid = !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    match valid {
+        true => Ok(()),
+        false => Err(BundleError::InvalidPath(path.to_string())),
+    }
+}
+
+// 清除配置中的密码等机密信息，包括配置方案中覆盖的机密信息，设置包通常会分享给别人
+fn clear_secrets(config: &mut Table) {
+    let remove_password = |t: &mut Table| {
+        if let Some(Value::Table(r)) = t.get_mut("remote_config") {
+            r.remove("password");
+        }
+    };
+    remove_password(config);
+    if let Some(Value::Array(profiles)) = config.get_mut("profiles") {
+        for p in profiles.iter_mut() {
+            if let Some(Value::Table(o)) = p.get_mut("overlay") {
+                remove_password(o);
+            }
+        }
+    }
+}
+
+/**
+ * 导出设置包，包含配置文件（其中有热键和配置方案）、音效方案和清单。
+ * 配置中的机密信息（例如远程协助的会话密码）不会被导出。
+ * `target` 设置包的路径。
+ * `config` 要导出的全局配置。
+ * `data_dir` 读屏的数据目录。
+ * */
+pub(crate) fn export_bundle(
+    target: &Path,
+    config: &ConfigRoot,
+    data_dir: &Path,
+) -> Result<(), BundleError> {
+    write_bundle(File::create(target)?, config, data_dir)
+}
+
+// 把设置包写入文件或者内存
+fn write_bundle(
+    writer: impl Write + Seek,
+    config: &ConfigRoot,
+    data_dir: &Path,
+) -> Result<(), BundleError> {
+    let mut config =
+        Table::try_from(config).map_err(|e| BundleError::InvalidConfig(e.to_string()))?;
+    clear_secrets(&mut config);
+    let mut files = vec![(
+        CONFIG_NAME.to_string(),
+        toml::to_string(&config)
+            .map_err(|e| BundleError::InvalidConfig(e.to_string()))?
+            .into_bytes(),
+    )];
+    let resources = data_dir.join(RESOURCES_DIR);
+    if resources.exists() {
+        for entry in resources.read_dir()? {
+            let path = entry?.path();
+            let is_sound = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .is_some_and(|e| SOUND_EXTENSIONS.contains(&e.as_str()));
+            if path.is_file() && is_sound {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                files.push((format!("{}/{}", RESOURCES_DIR, name), read(&path)?));
+            }
+        }
+    }
+
+    let manifest = Manifest {
+        format: BUNDLE_FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        config_version: CONFIG_VERSION,
+        created: Local::now().to_rfc3339(),
+        files: files
+            .iter()
+            .map(|(path, data)| ManifestFile {
+                path: path.clone(),
+                size: data.len() as u64,
+                sha256: sha256_hex(data),
+            })
+            .collect(),
+    };
+    let manifest =
+        toml::to_string(&manifest).map_err(|e| BundleError::InvalidManifest(e.to_string()))?;
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::DEFLATE);
+    zip.start_file(MANIFEST_NAME, options)?;
+    zip.write_all(manifest.as_bytes())?;
+    for (path, data) in files.iter() {
+        zip.start_file(path.as_str(), options)?;
+        zip.write_all(data)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// 已经校验过的设置包
+#[derive(Debug)]
+pub(crate) struct SettingsBundle {
+    // 生成设置包的读屏版本，旧版本导出的设置包没有清单
+    app_version: Option<String>,
+    // 升级到当前版本的配置
+    config: Table,
+    // 音效文件的名称和数据
+    sounds: Vec<(String, Vec<u8>)>,
+}
+
+impl SettingsBundle {
+    /**
+     * 打开并校验设置包，配置会被升级到当前的版本。
+     * 也可以打开旧版本导出的没有清单的压缩包，其中的配置文件和资源文件都在根目录。
+     * `source` 设置包的路径。
+     * */
+    pub(crate) fn open(source: &Path) -> Result<Self, BundleError> {
+        Self::read(File::open(source)?)
+    }
+
+    // 从文件或者内存中读取并校验设置包
+    fn read(reader: impl Read + Seek) -> Result<Self, BundleError> {
+        let mut archive = ZipArchive::new(reader)?;
+        let mut entries = vec![];
+        let mut total = 0;
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().replace('\\', "/");
+            check_path(&name)?;
+            // 压缩包中记录的长度可能是伪造的，所以按照实际读出的长度限制
+            let mut data = vec![];
+            file.take(MAX_ENTRY_SIZE + 1).read_to_end(&mut data)?;
+            total += data.len() as u64;
+            if data.len() as u64 > MAX_ENTRY_SIZE || total > MAX_TOTAL_SIZE {
+                return Err(BundleError::TooLarge(name));
+            }
+            entries.push((name, data));
+        }
+        let take = |entries: &mut Vec<(String, Vec<u8>)>, name: &str| {
+            let index = entries.iter().position(|(n, _)| n == name)?;
+            Some(entries.remove(index).1)
+        };
+
+        let (app_version, config, sounds) = match take(&mut entries, MANIFEST_NAME) {
+            Some(data) => {
+                let manifest: Manifest = String::from_utf8_lossy(&data)
+                    .parse::<Table>()
+                    .and_then(|t| t.try_into())
+                    .map_err(|e| BundleError::InvalidManifest(e.to_string()))?;
+                if manifest.format > BUNDLE_FORMAT {
+                    return Err(BundleError::UnsupportedFormat(manifest.format));
+                }
+                if manifest.config_version > CONFIG_VERSION {
+                    return Err(BundleError::NewerConfig(manifest.config_version));
+                }
+                // 只使用清单中列出并且校验通过的文件
+                let mut files = vec![];
+                for f in manifest.files.iter() {
+                    check_path(&f.path)?;
+                    let Some(data) = take(&mut entries, &f.path) else {
+                        return Err(BundleError::MissingFile(f.path.clone()));
+                    };
+                    if data.len() as u64 != f.size || sha256_hex(&data) != f.sha256 {
+                        return Err(BundleError::ChecksumMismatch(f.path.clone()));
+                    }
+                    files.push((f.path.clone(), data));
+                }
+                let config = take(&mut files, CONFIG_NAME)
+                    .ok_or_else(|| BundleError::MissingFile(CONFIG_NAME.to_string()))?;
+                let prefix = format!("{}/", RESOURCES_DIR);
+                let sounds = files
+                    .into_iter()
+                    .filter_map(|(n, d)| Some((n.strip_prefix(&prefix)?.to_string(), d)))
+                    .collect();
+                (Some(manifest.app_version), config, sounds)
+            }
+            None => {
+                let config = take(&mut entries, CONFIG_NAME)
+                    .ok_or_else(|| BundleError::MissingFile(CONFIG_NAME.to_string()))?;
+                (None, config, entries)
+            }
+        };
+        let sounds: Vec<(String, Vec<u8>)> = sounds
+            .into_iter()
+            .filter(|(n, _)| {
+                !n.contains('/')
+                    && Path::new(n)
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .is_some_and(|e| SOUND_EXTENSIONS.contains(&e.as_str()))
+            })
+            .collect();
+
+        let mut config = String::from_utf8_lossy(&config)
+            .parse::<Table>()
+            .map_err(|e| BundleError::InvalidConfig(e.to_string()))?;
+        let version = migrate(&mut config);
+        if version > CONFIG_VERSION {
+            return Err(BundleError::NewerConfig(version));
+        }
+        Ok(Self {
+            app_version,
+            config,
+            sounds,
+        })
+    }
+
+    /// 获取生成设置包的读屏版本，旧版本导出的设置包返回None
+    pub(crate) fn get_app_version(&self) -> Option<&str> {
+        self.app_version.as_deref()
+    }
+
+    /**
+     * 计算导入后的全局配置。
+     * 合并时只应用设置包中和默认值不同的配置项，同名的配置方案会被设置包中的代替，其他的配置方案保留。
+     * 两种方式都保留当前的机密信息（例如远程协助的会话密码），不使用设置包中的。
+     * `current` 当前的全局配置。
+     * `mode` 导入的方式。
+     * */
+    pub(crate) fn resolve(&self, current: &ConfigRoot, mode: ImportMode) -> ConfigRoot {
+        let mut incoming = self.config.clone();
+        clear_secrets(&mut incoming);
+        let mut config = match mode {
+            ImportMode::Replace => deserialize_lossy(incoming).0,
+            ImportMode::Merge => Self::merge(current, incoming),
+        };
+        config.remote_config.password = current.remote_config.password.clone();
+        config
+    }
+
+    // 把设置包中的配置合并到当前的配置
+    fn merge(current: &ConfigRoot, mut incoming: Table) -> ConfigRoot {
+        let mut table = Table::try_from(current).unwrap_or_default();
+        let profiles = incoming.remove("profiles");
+        // 只合并设置包中和默认值不同的配置项，否则导出的完整配置会覆盖当前所有的修改
+        let default = Table::try_from(ConfigRoot::default()).unwrap_or_default();
+        let mut changes = Table::new();
+        split_changes(&default, &incoming, &mut changes, None);
+        merge_table(&mut table, &changes);
+        let (mut config, _) = deserialize_lossy(table);
+        let profiles: Vec<ProfileConfig> =
+            profiles.and_then(|p| p.try_into().ok()).unwrap_or_default();
+        for profile in profiles {
+            match config.profiles.iter_mut().find(|p| p.name == profile.name) {
+                Some(p) => *p = profile,
+                None => config.profiles.push(profile),
+            }
+        }
+        config
+    }
+
+    /**
+     * 预览导入后会修改哪些内容。
+     * `current` 当前的全局配置。
+     * `mode` 导入的方式。
+     * */
+    pub(crate) fn preview(&self, current: &ConfigRoot, mode: ImportMode) -> ImportPreview {
+        let old = Table::try_from(current).unwrap_or_default();
+        let new = Table::try_from(self.resolve(current, mode)).unwrap_or_default();
+        let sections = new
+            .iter()
+            .filter(|(k, v)| k.as_str() != "version" && old.get(*k) != Some(*v))
+            .map(|(k, _)| k.clone())
+            .collect();
+        ImportPreview {
+            sections,
+            sounds: self.sounds.len(),
+        }
+    }
+
+    /**
+     * 把音效文件写入数据目录，同名的文件会被覆盖。
+     * `data_dir` 读屏的数据目录。
+     * */
+    pub(crate) fn install_sounds(&self, data_dir: &Path) -> Result<(), BundleError> {
+        let dir = data_dir.join(RESOURCES_DIR);
+        create_dir_all(&dir)?;
+        for (name, data) in self.sounds.iter() {
+            write(dir.join(name), data)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_bundle {
+    use crate::configs::{
+        bundle::{
+            check_path, write_bundle, BundleError, ImportMode, SettingsBundle, CONFIG_NAME,
+            MANIFEST_NAME, MAX_ENTRY_SIZE,
+        },
+        items::profile::ProfileConfig,
+        ConfigRoot,
+    };
+    use std::{
+        fs::{create_dir_all, remove_dir_all, write},
+        io::{Cursor, Read, Write},
+        path::{Path, PathBuf},
+    };
+    use toml::{Table, Value};
+    use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+    fn profile(name: &str) -> ProfileConfig {
+        ProfileConfig {
+            name: name.to_string(),
+            process_name: format!("{}.exe", name),
+            ..Default::default()
+        }
+    }
+
+    // 导出的配置修改了语速和远程协助的密码，并且有一个配置方案
+    fn exported_config() -> ConfigRoot {
+        let mut config = ConfigRoot::default();
+        config.tts_config.speed = 80;
+        config.remote_config.password = "secret".to_string();
+        config.profiles.push(profile("exported"));
+        config
+    }
+
+    // 当前的配置开启了朗读鼠标，并且有一个不同名的配置方案
+    fn current_config() -> ConfigRoot {
+        let mut config = ConfigRoot::default();
+        config.mouse_config.is_read = true;
+        config.remote_config.password = "current".to_string();
+        config.profiles.push(profile("current"));
+        config
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rigela-test-bundle-{}", name));
+        remove_dir_all(&dir).unwrap_or(());
+        dir
+    }
+
+    fn export(config: &ConfigRoot, data_dir: &Path) -> Vec<u8> {
+        let mut data = Cursor::new(vec![]);
+        write_bundle(&mut data, config, data_dir).unwrap();
+        data.into_inner()
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (name, data) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    // 逐个修改设置包中的文件，返回None的文件会被删除
+    fn rewrite(bundle: &[u8], f: impl Fn(&str, Vec<u8>) -> Option<Vec<u8>>) -> Vec<u8> {
+        let mut archive = ZipArchive::new(Cursor::new(bundle)).unwrap();
+        let mut entries = vec![];
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let name = file.name().to_string();
+            let mut data = vec![];
+            file.read_to_end(&mut data).unwrap();
+            if let Some(data) = f(&name, data) {
+                entries.push((name, data));
+            }
+        }
+        let entries: Vec<(&str, &[u8])> = entries
+            .iter()
+            .map(|(n, d)| (n.as_str(), d.as_slice()))
+            .collect();
+        zip(&entries)
+    }
+
+    fn open(bundle: Vec<u8>) -> Result<SettingsBundle, BundleError> {
+        SettingsBundle::read(Cursor::new(bundle))
+    }
+
+    #[test]
+    fn test_check_path() {
+        assert!(check_path("config.toml").is_ok());
+        assert!(check_path("resources/tip.wav").is_ok());
+        for path in ["", "../evil.wav", "resources/../../evil.wav", "/evil.wav"] {
+            assert!(matches!(check_path(path), Err(BundleError::InvalidPath(_))));
+        }
+    }
+
+    #[test]
+    fn test_zip_slip() {
+        let bundle = zip(&[
+            (CONFIG_NAME, b"".as_slice()),
+            ("../evil.wav", b"".as_slice()),
+        ]);
+        assert!(matches!(open(bundle), Err(BundleError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = temp_dir("round-trip");
+        create_dir_all(dir.join("resources")).unwrap();
+        write(dir.join("resources/tip.wav"), b"wav").unwrap();
+        write(dir.join("resources/help.md"), b"md").unwrap();
+        let bundle = open(export(&exported_config(), &dir)).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Some(env!("CARGO_PKG_VERSION")), bundle.get_app_version());
+        assert_eq!(
+            vec!["tip.wav".to_string()],
+            bundle
+                .sounds
+                .iter()
+                .map(|(n, _)| n.clone())
+                .collect::<Vec<_>>()
+        );
+        // 密码不能被导出
+        let remote = bundle.config.get("remote_config").and_then(Value::as_table);
+        assert_eq!(None, remote.and_then(|t| t.get("password")));
+
+        let preview = bundle.preview(&current_config(), ImportMode::Merge);
+        assert_eq!(1, preview.sounds);
+        assert!(preview.sections.contains(&"tts_config".to_string()));
+        assert!(!preview.sections.contains(&"mouse_config".to_string()));
+        assert!(!preview.sections.contains(&"remote_config".to_string()));
+    }
+
+    #[test]
+    fn test_merge_and_replace() {
+        let bundle = open(export(&exported_config(), &temp_dir("merge"))).unwrap();
+        let current = current_config();
+
+        let merged = bundle.resolve(&current, ImportMode::Merge);
+        assert_eq!(80, merged.tts_config.speed);
+        assert!(merged.mouse_config.is_read);
+        assert_eq!("current", merged.remote_config.password);
+        let names: Vec<&str> = merged.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(vec!["current", "exported"], names);
+
+        let replaced = bundle.resolve(&current, ImportMode::Replace);
+        assert_eq!(80, replaced.tts_config.speed);
+        assert!(!replaced.mouse_config.is_read);
+        assert_eq!("current", replaced.remote_config.password);
+        let names: Vec<&str> = replaced.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(vec!["exported"], names);
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let dir = temp_dir("checksum");
+        create_dir_all(dir.join("resources")).unwrap();
+        write(dir.join("resources/tip.wav"), b"wav").unwrap();
+        let bundle = export(&ConfigRoot::default(), &dir);
+        remove_dir_all(&dir).unwrap();
+
+        // 长度相同但内容不同
+        let tampered = rewrite(&bundle, |name, data| match name.ends_with(".wav") {
+            true => Some(b"xyz".to_vec()),
+            false => Some(data),
+        });
+        assert!(
+            matches!(open(tampered), Err(BundleError::ChecksumMismatch(p)) if p == "resources/tip.wav")
+        );
+        // 长度不同
+        let tampered = rewrite(&bundle, |name, mut data| {
+            if name == CONFIG_NAME {
+                data.push(b'\n');
+            }
+            Some(data)
+        });
+        assert!(
+            matches!(open(tampered), Err(BundleError::ChecksumMismatch(p)) if p == CONFIG_NAME)
+        );
+        // 清单中列出的文件不存在
+        let missing = rewrite(&bundle, |name, data| match name.ends_with(".wav") {
+            true => None,
+            false => Some(data),
+        });
+        assert!(
+            matches!(open(missing), Err(BundleError::MissingFile(p)) if p == "resources/tip.wav")
+        );
+    }
+
+    #[test]
+    fn test_newer_bundle() {
+        let bundle = export(&ConfigRoot::default(), &temp_dir("newer"));
+        let with_manifest = |key: &'static str| {
+            rewrite(&bundle, move |name, data| {
+                if name != MANIFEST_NAME {
+                    return Some(data);
+                }
+                let mut manifest: Table = String::from_utf8(data).unwrap().parse().unwrap();
+                manifest.insert(key.to_string(), Value::from(100));
+                Some(manifest.to_string().into_bytes())
+            })
+        };
+        assert!(matches!(
+            open(with_manifest("format")),
+            Err(BundleError::UnsupportedFormat(100))
+        ));
+        assert!(matches!(
+            open(with_manifest("config_version")),
+            Err(BundleError::NewerConfig(100))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_bundle() {
+        let bundle = zip(&[
+            (CONFIG_NAME, b"[tts_config]\nspeed = 20\n".as_slice()),
+            ("tip.wav", b"wav".as_slice()),
+            ("help.md", b"md".as_slice()),
+        ]);
+        let bundle = open(bundle).unwrap();
+        assert_eq!(None, bundle.get_app_version());
+        assert_eq!(1, bundle.sounds.len());
+        let config = bundle.resolve(&current_config(), ImportMode::Merge);
+        assert_eq!(20, config.tts_config.speed);
+        assert!(config.mouse_config.is_read);
+    }
+
+    #[test]
+    fn test_too_large() {
+        let large = vec![0u8; MAX_ENTRY_SIZE as usize + 1];
+        let bundle = zip(&[(CONFIG_NAME, b"".as_slice()), ("tip.wav", &large)]);
+        assert!(matches!(open(bundle), Err(BundleError::TooLarge(p)) if p == "tip.wav"));
+    }
+}
@@ -11,8 +11,14 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
+pub(crate) mod automation;
 pub(crate) mod general;
 pub(crate) mod hotkeys;
+pub(crate) mod ime;
+pub(crate) mod log;
 pub(crate) mod mouse;
 pub(crate) mod navigation;
+pub(crate) mod profile;
+pub(crate) mod remote;
 pub(crate) mod tts;
+pub(crate) mod window_text;
@@ -0,0 +1,40 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use rigela_utils::rpc::AUTOMATION_ENDPOINT;
+use serde::{Deserialize, Serialize};
+
+/// 自动化接口的配置项
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct AutomationConfig {
+    /// 是否启用自动化接口
+    pub(crate) enabled: bool,
+    /// 监听的端点，只能是本机的命名管道或者TCP回环地址
+    pub(crate) endpoint: String,
+    /// 是否允许外部程序执行能力（run_talent方法）
+    pub(crate) allow_run_talent: bool,
+    /// 允许外部程序执行的能力的id，不在列表中的能力即使允许执行能力也不能执行
+    pub(crate) allowed_talents: Vec<String>,
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: AUTOMATION_ENDPOINT.to_string(),
+            allow_run_talent: false,
+            allowed_talents: vec![],
+        }
+    }
+}
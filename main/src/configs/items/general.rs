@@ -16,11 +16,13 @@ use std::fmt::Display;
 use win_wrap::common::get_user_default_locale_name;
 
 /// 常规配置项
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub(crate) struct GeneralConfig {
     pub(crate) run_on_startup: bool,
     pub(crate) auto_check_update: bool,
     pub(crate) lang: Lang,
+    pub(crate) keyboard_layout: KeyboardLayout,
 }
 
 impl Default for GeneralConfig {
@@ -29,6 +31,7 @@ impl Default for GeneralConfig {
             run_on_startup: false,
             auto_check_update: true,
             lang: Lang::Zh,
+            keyboard_layout: KeyboardLayout::Desktop,
         }
     }
 }
@@ -75,3 +78,32 @@ impl Default for Lang {
         Self::FollowSystem
     }
 }
+
+/// 键盘布局，决定导航和鼠标等能力的默认热键
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+pub(crate) enum KeyboardLayout {
+    /// 台式机布局，使用小键盘
+    Desktop,
+    /// 笔记本布局，没有小键盘，使用RigelA+Shift加字母键
+    Laptop,
+}
+
+impl KeyboardLayout {
+    /// 所有的键盘布局
+    pub(crate) const ALL: [Self; 2] = [Self::Desktop, Self::Laptop];
+
+    /// 获取键盘布局的名称
+    pub(crate) fn get_name(&self) -> String {
+        match self {
+            Self::Desktop => t!("configs.keyboard_layout_desktop"),
+            Self::Laptop => t!("configs.keyboard_layout_laptop"),
+        }
+        .to_string()
+    }
+}
+
+impl Default for KeyboardLayout {
+    fn default() -> Self {
+        Self::Desktop
+    }
+}
@@ -11,19 +11,263 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
-use crate::commander::keyboard::combo_keys::ComboKey;
+use crate::{
+    combo_key,
+    commander::keyboard::{
+        combo_keys::{ComboKey, State},
+        keys::Keys::*,
+        modify_keys::ModifierKeys,
+        recognizer::PressTimings,
+    },
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::RangeInclusive, time::Duration};
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+// 连续按下的最大间隔允许的范围（毫秒），太小时无法识别双击，太大时单击要等很久才能确认
+const MULTI_PRESS_INTERVAL_RANGE: RangeInclusive<u64> = 50..=1000;
+// 长按时间允许的范围（毫秒），太小时普通的按键也会被识别为长按
+const LONG_PRESS_DURATION_RANGE: RangeInclusive<u64> = 200..=5000;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub(crate) struct HotKeysConfig {
     pub(crate) talent_keys: HashMap<String, ComboKey>,
+    /// 连续按下同一个热键的最大间隔毫秒数，小于这个间隔识别为双击或者三击
+    pub(crate) multi_press_interval: u64,
+    /// 按住热键超过这个毫秒数后松开识别为长按
+    pub(crate) long_press_duration: u64,
+    pub(crate) layers: Vec<LayerConfig>,
 }
 
 impl Default for HotKeysConfig {
     fn default() -> Self {
+        let timings = PressTimings::default();
         Self {
             talent_keys: HashMap::new(),
+            multi_press_interval: timings.multi_press_interval.as_millis() as u64,
+            long_press_duration: timings.long_press_duration.as_millis() as u64,
+            layers: vec![LayerConfig::query()],
+        }
+    }
+}
+
+impl HotKeysConfig {
+    /**
+     * 把识别双击和长按的时间限制在合理的范围内，返回是否有修改。
+     * */
+    pub(crate) fn sanitize(&mut self) -> bool {
+        let clamp = |value: &mut u64, range: RangeInclusive<u64>| {
+            let clamped = (*value).clamp(*range.start(), *range.end());
+            std::mem::replace(value, clamped) != clamped
+        };
+        let interval = clamp(&mut self.multi_press_interval, MULTI_PRESS_INTERVAL_RANGE);
+        let duration = clamp(&mut self.long_press_duration, LONG_PRESS_DURATION_RANGE);
+        interval || duration
+    }
+
+    /// 获取识别双击、三击和长按的时间阈值
+    pub(crate) fn get_press_timings(&self) -> PressTimings {
+        PressTimings {
+            multi_press_interval: Duration::from_millis(self.multi_press_interval),
+            long_press_duration: Duration::from_millis(self.long_press_duration),
+        }
+    }
+
+    /**
+     * 通过名称获取命令层。
+     * `name` 层的名称。
+     * */
+    pub(crate) fn get_layer(&self, name: &str) -> Option<&LayerConfig> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    /**
+     * 查找在指定的层中按下热键后进入的子层。
+     * `parent` 当前所在的层，空字符串表示不在任何层中。
+     * `key` 按下的热键。
+     * */
+    pub(crate) fn find_layer_by_key(&self, parent: &str, key: &ComboKey) -> Option<&LayerConfig> {
+        self.layers
+            .iter()
+            .find(|l| l.parent == parent && &l.key == key)
+    }
+}
+
+/// 命令层，进入层以后，接下来按下的键在层自己的热键表中查找，例如先按`RigelA+G`再按`H`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct LayerConfig {
+    /// 层的名称，不能重复
+    pub(crate) name: String,
+    /// 上一级层的名称，为空时在普通的热键中进入这个层
+    pub(crate) parent: String,
+    /// 进入这个层的热键
+    pub(crate) key: ComboKey,
+    /// 没有按键时自动退出的毫秒数，为0时不会自动退出
+    pub(crate) timeout: u64,
+    /// 层中的能力热键，键是能力的ID
+    pub(crate) talent_keys: HashMap<String, ComboKey>,
+}
+
+impl Default for LayerConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            parent: String::new(),
+            key: Default::default(),
+            timeout: 3000,
+            talent_keys: HashMap::new(),
+        }
+    }
+}
+
+impl LayerConfig {
+    /**
+     * 默认提供的“查询”层，按`RigelA+G`进入，再按一个字母朗读时间、日期等信息。
+     * */
+    fn query() -> Self {
+        let talent_keys = [
+            ("current_time", combo_key!(VkT)),
+            ("current_date", combo_key!(VkD)),
+            ("current_cpu_usage", combo_key!(VkC)),
+            ("current_profile", combo_key!(VkP)),
+            ("view_window_title", combo_key!(VkW)),
+        ];
+        Self {
+            name: "查询".to_string(),
+            key: combo_key!("RigelA", VkG),
+            talent_keys: talent_keys
+                .into_iter()
+                .map(|(id, key)| (id.to_string(), key))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /**
+     * 通过热键获取层中能力的ID。
+     * `key` 按下的热键。
+     * */
+    pub(crate) fn get_talent_id(&self, key: &ComboKey) -> Option<&str> {
+        self.talent_keys
+            .iter()
+            .find(|(_, k)| *k == key)
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test_hotkeys {
+    use crate::commander::keyboard::{
+        combo_keys::{ComboKey, State},
+        keys::Keys,
+        modify_keys::ModifierKeys,
+    };
+    use crate::configs::items::hotkeys::{HotKeysConfig, LayerConfig};
+
+    fn key(modify_keys: ModifierKeys, main_key: Keys) -> ComboKey {
+        ComboKey::new(main_key, modify_keys, State::SinglePress)
+    }
+
+    fn layer(name: &str, parent: &str, key: ComboKey) -> LayerConfig {
+        LayerConfig {
+            name: name.to_string(),
+            parent: parent.to_string(),
+            key,
+            ..Default::default()
         }
     }
+
+    // RigelA+G进入“跳转”层，在跳转层中按W进入“网页”子层
+    fn hotkeys() -> HotKeysConfig {
+        let mut goto = layer("goto", "", key(ModifierKeys::RIGELA, Keys::VkG));
+        goto.talent_keys.insert(
+            "current_time".to_string(),
+            key(ModifierKeys::empty(), Keys::VkH),
+        );
+        let mut web = layer("web", "goto", key(ModifierKeys::empty(), Keys::VkW));
+        web.talent_keys
+            .insert("web_home".to_string(), key(ModifierKeys::SHIFT, Keys::VkH));
+        HotKeysConfig {
+            layers: vec![goto, web],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_layer_by_key() {
+        let hotkeys = hotkeys();
+        let entry = key(ModifierKeys::RIGELA, Keys::VkG);
+        assert_eq!(
+            Some("goto"),
+            hotkeys
+                .find_layer_by_key("", &entry)
+                .map(|l| l.name.as_str())
+        );
+        // 子层只能从上一级层进入
+        let sub = key(ModifierKeys::empty(), Keys::VkW);
+        assert!(hotkeys.find_layer_by_key("", &sub).is_none());
+        assert_eq!(
+            Some("web"),
+            hotkeys
+                .find_layer_by_key("goto", &sub)
+                .map(|l| l.name.as_str())
+        );
+        assert!(hotkeys.find_layer_by_key("web", &entry).is_none());
+        // 修饰键和按下的状态都要相同
+        assert!(hotkeys
+            .find_layer_by_key("", &key(ModifierKeys::empty(), Keys::VkG))
+            .is_none());
+        assert!(hotkeys
+            .find_layer_by_key("", &entry.change_state(State::DoublePress))
+            .is_none());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut hotkeys = HotKeysConfig::default();
+        assert!(!hotkeys.sanitize());
+        hotkeys.multi_press_interval = 0;
+        hotkeys.long_press_duration = u64::MAX;
+        assert!(hotkeys.sanitize());
+        assert_eq!(50, hotkeys.multi_press_interval);
+        assert_eq!(5000, hotkeys.long_press_duration);
+        assert!(!hotkeys.sanitize());
+    }
+
+    #[test]
+    fn test_default_layer() {
+        // 默认的配置中可以直接进入“查询”层
+        let hotkeys = HotKeysConfig::default();
+        let query = hotkeys
+            .find_layer_by_key("", &key(ModifierKeys::RIGELA, Keys::VkG))
+            .unwrap();
+        assert_eq!(
+            Some("current_time"),
+            query.get_talent_id(&key(ModifierKeys::empty(), Keys::VkT))
+        );
+    }
+
+    #[test]
+    fn test_get_talent_id() {
+        let hotkeys = hotkeys();
+        let goto = hotkeys.get_layer("goto").unwrap();
+        assert_eq!(
+            Some("current_time"),
+            goto.get_talent_id(&key(ModifierKeys::empty(), Keys::VkH))
+        );
+        assert!(goto
+            .get_talent_id(&key(ModifierKeys::RIGELA, Keys::VkH))
+            .is_none());
+        // 每个层只查找自己的热键
+        let web = hotkeys.get_layer("web").unwrap();
+        assert!(web
+            .get_talent_id(&key(ModifierKeys::empty(), Keys::VkH))
+            .is_none());
+        assert_eq!(
+            Some("web_home"),
+            web.get_talent_id(&key(ModifierKeys::SHIFT, Keys::VkH))
+        );
+        assert!(hotkeys.get_layer("missing").is_none());
+    }
 }
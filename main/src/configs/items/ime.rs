@@ -0,0 +1,56 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * 输入法候选的朗读方式。
+ * */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) enum CandidateReadMode {
+    /// 只朗读选中的候选
+    Selected,
+    /// 翻页时朗读整页的候选，并带上序号，例如“1 是, 2 事, 3 市”
+    Page,
+}
+
+impl Default for CandidateReadMode {
+    fn default() -> Self {
+        Self::Selected
+    }
+}
+
+/// 输入法配置项
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct ImeConfig {
+    /// 是否朗读组字串（例如拼音）
+    pub(crate) read_composition: bool,
+    /// 候选的朗读方式
+    pub(crate) candidate_mode: CandidateReadMode,
+    /// 候选翻页时是否报告页码
+    pub(crate) announce_page: bool,
+    /// 是否报告候选窗口的打开和关闭
+    pub(crate) announce_candidate_window: bool,
+}
+
+impl Default for ImeConfig {
+    fn default() -> Self {
+        Self {
+            read_composition: true,
+            candidate_mode: CandidateReadMode::default(),
+            announce_page: true,
+            announce_candidate_window: false,
+        }
+    }
+}
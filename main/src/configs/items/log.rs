@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use log::LevelFilter;
+use rigela_utils::logger::LogOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 日志的配置项
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct LogConfig {
+    /// 记录到日志文件的最低级别
+    pub(crate) level: LogLevel,
+    /// 按模块设置的级别，例如`"rigela_main::performer" = "debug"`，它的子模块也使用这个级别
+    pub(crate) modules: BTreeMap<String, LogLevel>,
+    /// 日志文件超过1MB后滚动，保留的归档数量，0表示滚动时直接删除
+    pub(crate) archives: u32,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Info,
+            modules: Default::default(),
+            archives: 3,
+        }
+    }
+}
+
+impl LogConfig {
+    /// 转换成日志库的选项
+    pub(crate) fn to_options(&self) -> LogOptions {
+        LogOptions {
+            level: self.level.into(),
+            modules: self
+                .modules
+                .iter()
+                .map(|(k, v)| (k.clone(), (*v).into()))
+                .collect(),
+            archives: self.archives,
+        }
+    }
+}
+
+/// 日志级别，从低到高排列
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// 所有的日志级别
+    pub(crate) const ALL: [Self; 6] = [
+        Self::Off,
+        Self::Error,
+        Self::Warn,
+        Self::Info,
+        Self::Debug,
+        Self::Trace,
+    ];
+
+    /// 获取下一个日志级别，最高的级别之后回到最低的级别
+    pub(crate) fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|l| *l == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// 获取日志级别的本地化名称
+    pub(crate) fn get_name(&self) -> String {
+        match self {
+            Self::Off => t!("configs.log_level_off"),
+            Self::Error => t!("configs.log_level_error"),
+            Self::Warn => t!("configs.log_level_warn"),
+            Self::Info => t!("configs.log_level_info"),
+            Self::Debug => t!("configs.log_level_debug"),
+            Self::Trace => t!("configs.log_level_trace"),
+        }
+        .to_string()
+    }
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Off => Self::Off,
+            LogLevel::Error => Self::Error,
+            LogLevel::Warn => Self::Warn,
+            LogLevel::Info => Self::Info,
+            LogLevel::Debug => Self::Debug,
+            LogLevel::Trace => Self::Trace,
+        }
+    }
+}
@@ -16,7 +16,7 @@ use serde::{Deserialize, Serialize};
 /**
  * 导航模式。
  * */
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub(crate) enum NavigationMode {
     /// 线性模式
     Linear,
@@ -33,16 +33,20 @@ impl Default for NavigationMode {
 }
 
 /// 导航配置项
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub(crate) struct NavigationConfig {
     /// 导航模式
     pub(crate) mode: NavigationMode,
+    /// 是否收集前台程序使用GDI输出的文字，用于浏览不支持MSAA和UIA的程序，只有前台窗口所在的进程会开启GDI钩子
+    pub(crate) use_display_model: bool,
 }
 
 impl Default for NavigationConfig {
     fn default() -> Self {
         Self {
             mode: NavigationMode::default(),
+            use_display_model: true,
         }
     }
 }
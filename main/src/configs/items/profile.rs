@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use serde::{Deserialize, Serialize};
+use toml::Table;
+
+/// 应用程序的配置方案，前台程序匹配时，覆盖的配置项会代替全局配置生效
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub(crate) struct ProfileConfig {
+    /// 方案的名称，不能重复
+    pub(crate) name: String,
+    /// 匹配的可执行文件名称，例如`code.exe`，不区分大小写，为空时不按程序匹配
+    #[serde(default)]
+    pub(crate) process_name: String,
+    /// 匹配的前台窗口类名，不区分大小写，为空时不按窗口类匹配
+    #[serde(default)]
+    pub(crate) class_name: String,
+    /// 覆盖的配置项，结构和配置文件的根元素相同，但只需要写出要修改的部分，例如`[profiles.overlay.tts_config]`
+    #[serde(default)]
+    pub(crate) overlay: Table,
+}
+
+impl ProfileConfig {
+    /**
+     * 判断方案是否匹配前台程序，可执行文件名称和窗口类名都设置时必须同时匹配。
+     * `process_name` 前台程序的可执行文件名称。
+     * `class_name` 前台窗口的类名。
+     * */
+    pub(crate) fn is_match(&self, process_name: &str, class_name: &str) -> bool {
+        if self.process_name.is_empty() && self.class_name.is_empty() {
+            return false;
+        }
+        (self.process_name.is_empty() || self.process_name.eq_ignore_ascii_case(process_name))
+            && (self.class_name.is_empty() || self.class_name.eq_ignore_ascii_case(class_name))
+    }
+}
+
+#[cfg(test)]
+mod test_profile {
+    use crate::configs::items::profile::ProfileConfig;
+
+    fn profile(process_name: &str, class_name: &str) -> ProfileConfig {
+        ProfileConfig {
+            name: "test".to_string(),
+            process_name: process_name.to_string(),
+            class_name: class_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_match() {
+        let p = profile("Code.exe", "");
+        assert!(p.is_match("code.exe", "Chrome_WidgetWin_1"));
+        assert!(p.is_match("CODE.EXE", ""));
+        assert!(!p.is_match("notepad.exe", "Chrome_WidgetWin_1"));
+
+        let p = profile("", "Notepad");
+        assert!(p.is_match("notepad.exe", "notepad"));
+        assert!(p.is_match("", "NOTEPAD"));
+        assert!(!p.is_match("notepad.exe", "Edit"));
+
+        // 两个条件都设置时必须同时匹配
+        let p = profile("notepad.exe", "notepad");
+        assert!(p.is_match("Notepad.exe", "Notepad"));
+        assert!(!p.is_match("notepad.exe", "Edit"));
+        assert!(!p.is_match("code.exe", "Notepad"));
+
+        // 没有设置条件的方案不会匹配任何程序
+        assert!(!profile("", "").is_match("notepad.exe", "Notepad"));
+    }
+}
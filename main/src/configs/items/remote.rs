@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use rigela_utils::remote::{hash_password, is_password_hash, DEFAULT_REMOTE_PORT};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// 远程协助的配置项
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct RemoteConfig {
+    /// 会话密码，双方必须相同，为空时不允许建立远程会话。
+    /// 用户在配置文件中填写明文密码，读取配置时会被替换成密码的校验值；导出设置包时会被清除。
+    pub(crate) password: String,
+    /// 作为跟随者时监听的端口
+    pub(crate) port: u16,
+    /// 作为领导者时连接的跟随者地址，例如`192.168.1.2:6837`，可以省略端口
+    pub(crate) address: String,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            password: String::new(),
+            port: DEFAULT_REMOTE_PORT,
+            address: String::new(),
+        }
+    }
+}
+
+impl Debug for RemoteConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // 密码不能出现在日志中
+        f.debug_struct("RemoteConfig")
+            .field("password", &"***")
+            .field("port", &self.port)
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+impl RemoteConfig {
+    /**
+     * 获取会话密码的校验值，没有设置密码时返回None。
+     * */
+    pub(crate) fn password_hash(&self) -> Option<String> {
+        if self.password.is_empty() {
+            None
+        } else if is_password_hash(&self.password) {
+            Some(self.password.clone())
+        } else {
+            Some(hash_password(&self.password))
+        }
+    }
+
+    /**
+     * 把明文的会话密码替换成校验值，返回是否有修改。
+     * */
+    pub(crate) fn sanitize(&mut self) -> bool {
+        match self.password_hash() {
+            Some(hash) if hash != self.password => {
+                self.password = hash;
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -14,7 +14,7 @@
 use serde::{Deserialize, Serialize};
 
 /// 属性条目
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub(crate) enum TtsPropertyItem {
     Speed,
     Pitch,
@@ -23,7 +23,8 @@ pub(crate) enum TtsPropertyItem {
 }
 
 /// 语音TTS的配置项
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub(crate) struct TtsConfig {
     pub(crate) speed: i32,
     pub(crate) volume: i32,
@@ -0,0 +1,36 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// 传统窗口文字变化的配置项
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct WindowTextConfig {
+    /// 是否朗读状态栏的变化
+    pub(crate) announce_status_bar: bool,
+    /// 是否朗读静态文本（标签）的变化
+    pub(crate) announce_label: bool,
+    /// 是否朗读没有键盘焦点的编辑框的变化，例如计算器的结果框
+    pub(crate) announce_edit: bool,
+}
+
+impl Default for WindowTextConfig {
+    fn default() -> Self {
+        Self {
+            announce_status_bar: true,
+            announce_label: true,
+            announce_edit: false,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::configs::ConfigRoot;
+use log::info;
+use toml::{Table, Value};
+
+/// 当前配置文件的版本号，修改配置的结构时需要增加版本号，并在MIGRATIONS中添加对应的迁移函数
+pub(crate) const CONFIG_VERSION: u32 = 1;
+
+/// 迁移函数，下标为i的函数把版本i的配置升级到版本i+1
+const MIGRATIONS: [fn(&mut Table); CONFIG_VERSION as usize] = [migrate_v0];
+
+// 版本0是没有版本号的旧配置文件，版本1只增加了有默认值的配置项，没有改名或者删除配置项，所以只需要写入版本号。
+// 以后改名或者删除配置项时，在MIGRATIONS末尾添加把旧的配置项转换成新结构的函数
+fn migrate_v0(_table: &mut Table) {}
+
+/**
+ * 把配置升级到当前版本，返回配置原来的版本号。
+ * 比当前版本更新的配置（例如安装了旧版本的读屏）不会被修改，无法识别的配置项在读取时会被忽略。
+ * `table` 配置文件的内容。
+ * */
+pub(crate) fn migrate(table: &mut Table) -> u32 {
+    migrate_with(table, &MIGRATIONS)
+}
+
+/**
+ * 依次执行从配置的版本开始的迁移函数，返回配置原来的版本号。
+ * `table` 配置文件的内容。
+ * `migrations` 迁移函数，下标为i的函数把版本i的配置升级到版本i+1。
+ * */
+fn migrate_with(table: &mut Table, migrations: &[fn(&mut Table)]) -> u32 {
+    let version = table
+        .get("version")
+        .and_then(Value::as_integer)
+        .unwrap_or(0)
+        .clamp(0, u32::MAX as i64) as u32;
+    for (i, f) in migrations.iter().enumerate().skip(version as usize) {
+        f(table);
+        info!("The config is migrated from version {} to {}.", i, i + 1);
+    }
+    let latest = migrations.len() as u32;
+    if version < latest {
+        table.insert("version".to_string(), Value::from(latest as i64));
+    }
+    version
+}
+
+/**
+ * 把配置文件的内容转换成配置，无效的配置项使用默认值代替，其他的配置项仍然保留。
+ * 第二个返回值表示是否有配置项因为无效被丢弃。
+ * `table` 配置文件的内容。
+ * */
+pub(crate) fn deserialize_lossy(table: Table) -> (ConfigRoot, bool) {
+    if let Ok(config) = table.clone().try_into::<ConfigRoot>() {
+        return (config, false);
+    }
+    let mut valid = Table::try_from(ConfigRoot::default()).unwrap_or_default();
+    merge_valid(&mut valid, &mut vec![], &table);
+    (valid.try_into().unwrap_or_default(), true)
+}
+
+/**
+ * 逐个合并配置项，只保留合并后整个配置仍然能被读取的配置项，子表会递归合并。
+ * `root` 配置的根，合并前必须是能被读取的。
+ * `path` 当前合并的子表在根中的路径。
+ * `source` 要合并的配置项。
+ * */
+fn merge_valid(root: &mut Table, path: &mut Vec<String>, source: &Table) {
+    for (k, v) in source {
+        let mut candidate = root.clone();
+        let Some(target) = table_at(&mut candidate, path) else {
+            return;
+        };
+        if let (Some(Value::Table(_)), Value::Table(s)) = (target.get(k), v) {
+            path.push(k.clone());
+            merge_valid(root, path, s);
+            path.pop();
+            continue;
+        }
+        target.insert(k.clone(), v.clone());
+        if candidate.clone().try_into::<ConfigRoot>().is_ok() {
+            *root = candidate;
+        }
+    }
+}
+
+// 获取路径对应的子表
+fn table_at<'a>(table: &'a mut Table, path: &[String]) -> Option<&'a mut Table> {
+    path.iter()
+        .try_fold(table, |t, k| t.get_mut(k).and_then(Value::as_table_mut))
+}
+
+#[cfg(test)]
+mod test_migration {
+    use crate::configs::{
+        migration::{deserialize_lossy, migrate, migrate_with, CONFIG_VERSION},
+        ConfigRoot,
+    };
+    use toml::{Table, Value};
+
+    fn parse(text: &str) -> Table {
+        text.parse().unwrap()
+    }
+
+    // 测试用的迁移：版本0的语速叫rate
+    fn rename_rate(table: &mut Table) {
+        let Some(Value::Table(tts)) = table.get_mut("tts_config") else {
+            return;
+        };
+        if let Some(rate) = tts.remove("rate") {
+            tts.insert("speed".to_string(), rate);
+        }
+    }
+
+    // 测试用的迁移：版本1的语速范围是0到10
+    fn scale_speed(table: &mut Table) {
+        let Some(Value::Table(tts)) = table.get_mut("tts_config") else {
+            return;
+        };
+        if let Some(Value::Integer(speed)) = tts.get("speed").cloned() {
+            tts.insert("speed".to_string(), Value::from(speed * 10));
+        }
+    }
+
+    #[test]
+    fn test_migration_chain() {
+        let migrations: [fn(&mut Table); 2] = [rename_rate, scale_speed];
+        // 没有版本号的配置依次执行所有的迁移
+        let mut table = parse("[tts_config]\nrate = 3\n");
+        assert_eq!(0, migrate_with(&mut table, &migrations));
+        assert_eq!(parse("version = 2\n[tts_config]\nspeed = 30\n"), table);
+        // 版本1的配置只执行后面的迁移
+        let mut table = parse("version = 1\n[tts_config]\nspeed = 3\n");
+        assert_eq!(1, migrate_with(&mut table, &migrations));
+        assert_eq!(parse("version = 2\n[tts_config]\nspeed = 30\n"), table);
+        // 已经是最新版本或者更新的配置不会被修改
+        for text in [
+            "version = 2\n[tts_config]\nspeed = 3\n",
+            "version = 9\n[tts_config]\nrate = 3\n",
+        ] {
+            let mut table = parse(text);
+            migrate_with(&mut table, &migrations);
+            assert_eq!(parse(text), table);
+        }
+    }
+
+    #[test]
+    fn test_migrate_old_version() {
+        let mut table = parse("[tts_config]\nspeed = 80\n");
+        assert_eq!(0, migrate(&mut table));
+        assert_eq!(
+            Some(CONFIG_VERSION as i64),
+            table.get("version").and_then(Value::as_integer)
+        );
+        let (config, lossy) = deserialize_lossy(table);
+        assert!(!lossy);
+        assert_eq!(80, config.tts_config.speed);
+    }
+
+    #[test]
+    fn test_invalid_field() {
+        let table =
+            parse("[tts_config]\nspeed = \"fast\"\npitch = 30\n[mouse_config]\nis_read = true\n");
+        let (config, lossy) = deserialize_lossy(table);
+        assert!(lossy);
+        // 无效的配置项使用默认值，同一个表和其他表中的配置项仍然保留
+        assert_eq!(
+            ConfigRoot::default().tts_config.speed,
+            config.tts_config.speed
+        );
+        assert_eq!(30, config.tts_config.pitch);
+        assert!(config.mouse_config.is_read);
+    }
+
+    #[test]
+    fn test_invalid_section() {
+        let table = parse("mouse_config = 5\n[tts_config]\npitch = 30\n");
+        let (config, lossy) = deserialize_lossy(table);
+        assert!(lossy);
+        assert!(!config.mouse_config.is_read);
+        assert_eq!(30, config.tts_config.pitch);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let table = parse(
+            "removed = 1\n[tts_config]\npitch = 30\nremoved = true\n[removed_config]\nx = 1\n",
+        );
+        let (config, lossy) = deserialize_lossy(table);
+        // 无法识别的配置项会被忽略，不算作无效的配置
+        assert!(!lossy);
+        assert_eq!(30, config.tts_config.pitch);
+    }
+}
@@ -14,8 +14,11 @@
 use crate::{
     commander::keyboard::combo_keys::ComboKey,
     configs::items::{
-        general::{GeneralConfig, Lang},
+        general::{GeneralConfig, KeyboardLayout, Lang},
+        hotkeys::HotKeysConfig,
+        log::{LogConfig, LogLevel},
         mouse::MouseConfig,
+        profile::ProfileConfig,
     },
     context::{Context, ContextAccessor},
 };
@@ -27,8 +30,7 @@ use std::{collections::HashMap, sync::Weak};
 pub(crate) fn get_mouse_read_state(context: Weak<Context>) -> bool {
     context
         .get_config_manager()
-        .get_config()
-        .mouse_config
+        .get_section::<MouseConfig>()
         .is_read
 }
 
@@ -45,8 +47,7 @@ pub(crate) fn apply_mouse_config(context: Weak<Context>, is_read: bool) {
 pub(crate) fn get_hotkeys(context: Weak<Context>) -> HashMap<String, ComboKey> {
     context
         .get_config_manager()
-        .get_config()
-        .hotkeys_config
+        .get_section::<HotKeysConfig>()
         .talent_keys
         .clone()
 }
@@ -64,8 +65,7 @@ pub(crate) fn save_hotkeys(context: Weak<Context>, hotkeys: HashMap<String, Comb
 pub(crate) fn get_run_on_startup(context: Weak<Context>) -> bool {
     context
         .get_config_manager()
-        .get_config()
-        .general_config
+        .get_section::<GeneralConfig>()
         .run_on_startup
 }
 
@@ -83,8 +83,7 @@ pub(crate) fn save_run_on_startup(context: Weak<Context>, run_on_startup: bool)
 pub(crate) fn get_auto_check_update(context: Weak<Context>) -> bool {
     context
         .get_config_manager()
-        .get_config()
-        .general_config
+        .get_section::<GeneralConfig>()
         .auto_check_update
 }
 
@@ -102,8 +101,7 @@ pub(crate) fn save_auto_check_update(context: Weak<Context>, auto_check_update:
 pub(crate) fn get_lang(context: Weak<Context>) -> Lang {
     context
         .get_config_manager()
-        .get_config()
-        .general_config
+        .get_section::<GeneralConfig>()
         .lang
 }
 
@@ -116,3 +114,55 @@ pub(crate) fn save_lang(context: Weak<Context>, lang: &Lang) {
     };
     context.get_config_manager().set_config(&config);
 }
+
+/// 获取当前的键盘布局
+pub(crate) fn get_keyboard_layout(context: Weak<Context>) -> KeyboardLayout {
+    context
+        .get_config_manager()
+        .get_section::<GeneralConfig>()
+        .keyboard_layout
+}
+
+/// 保存键盘布局，保存后立即生效
+pub(crate) fn save_keyboard_layout(context: Weak<Context>, layout: KeyboardLayout) {
+    let mut config = context.get_config_manager().get_config();
+    config.general_config = GeneralConfig {
+        keyboard_layout: layout,
+        ..config.general_config
+    };
+    context.get_config_manager().set_config(&config);
+}
+
+// ------  配置方案  -------
+
+/// 获取所有的配置方案
+pub(crate) fn get_profiles(context: Weak<Context>) -> Vec<ProfileConfig> {
+    context.get_config_manager().get_config().profiles
+}
+
+/// 保存所有的配置方案
+pub(crate) fn save_profiles(context: Weak<Context>, profiles: Vec<ProfileConfig>) {
+    let mut config = context.get_config_manager().get_config();
+    config.profiles = profiles;
+    context.get_config_manager().set_config(&config);
+}
+
+// ------  日志配置  -------
+
+/// 获取当前的日志级别
+pub(crate) fn get_log_level(context: Weak<Context>) -> LogLevel {
+    context
+        .get_config_manager()
+        .get_section::<LogConfig>()
+        .level
+}
+
+/// 保存日志级别，保存后立即生效
+pub(crate) fn save_log_level(context: Weak<Context>, level: LogLevel) {
+    let mut config = context.get_config_manager().get_config();
+    config.log_config = LogConfig {
+        level,
+        ..config.log_config
+    };
+    context.get_config_manager().set_config(&config);
+}
@@ -0,0 +1,295 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::configs::ConfigRoot;
+use log::error;
+use toml::{Table, Value};
+
+/**
+ * 把覆盖的配置项合并到配置中，子表会递归合并，其他的值直接替换。
+ * `table` 要修改的配置。
+ * `overlay` 覆盖的配置项。
+ * */
+pub(crate) fn merge_table(table: &mut Table, overlay: &Table) {
+    for (k, v) in overlay {
+        match (table.get_mut(k), v) {
+            (Some(Value::Table(t)), Value::Table(o)) => merge_table(t, o),
+            _ => {
+                table.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+/**
+ * 找出修改后的配置和修改前的差异，被覆盖的配置项的修改写入覆盖表，其他的修改写入全局配置。
+ * `old` 修改前生效的配置。
+ * `new` 修改后的配置。
+ * `base` 全局配置。
+ * `overlay` 生效的配置方案覆盖的配置项，没有生效的方案时为None。
+ * */
+pub(crate) fn split_changes(
+    old: &Table,
+    new: &Table,
+    base: &mut Table,
+    mut overlay: Option<&mut Table>,
+) {
+    for (k, v) in new {
+        let old_v = old.get(k);
+        if old_v == Some(v) {
+            continue;
+        }
+        if let (Some(Value::Table(o)), Value::Table(n)) = (old_v, v) {
+            if let Value::Table(b) = base
+                .entry(k.clone())
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                let sub_overlay = match overlay.as_deref_mut().and_then(|t| t.get_mut(k)) {
+                    Some(Value::Table(t)) => Some(t),
+                    _ => None,
+                };
+                split_changes(o, n, b, sub_overlay);
+                continue;
+            }
+        }
+        match overlay.as_deref_mut() {
+            Some(t) if t.contains_key(k) => t.insert(k.clone(), v.clone()),
+            _ => base.insert(k.clone(), v.clone()),
+        };
+    }
+    for k in old.keys().filter(|k| !new.contains_key(*k)) {
+        if let Some(t) = overlay.as_deref_mut() {
+            t.remove(k);
+        }
+        base.remove(k);
+    }
+}
+
+/**
+ * 把覆盖的配置项合并到配置中，覆盖的配置项不符合配置的格式时返回错误。
+ * `base` 全局配置。
+ * `overlay` 覆盖的配置项。
+ * */
+pub(crate) fn merge_config(base: &ConfigRoot, overlay: &Table) -> Result<ConfigRoot, String> {
+    let mut table = to_table(base)?;
+    merge_table(&mut table, overlay);
+    let config = table.try_into::<ConfigRoot>().map_err(|e| e.to_string())?;
+    // 配置方案不能修改方案列表本身
+    Ok(ConfigRoot {
+        profiles: base.profiles.clone(),
+        ..config
+    })
+}
+
+/**
+ * 计算配置方案生效后的配置，方案不存在时返回全局配置。
+ * `base` 全局配置。
+ * `profile` 配置方案的名称。
+ * */
+pub(crate) fn apply_profile(base: &ConfigRoot, profile: Option<&str>) -> ConfigRoot {
+    let Some(profile) = profile.and_then(|n| base.profiles.iter().find(|p| p.name == n)) else {
+        return base.clone();
+    };
+    merge_config(base, &profile.overlay).unwrap_or_else(|e| {
+        error!("Can't apply the profile {}. {}", profile.name, e);
+        base.clone()
+    })
+}
+
+/**
+ * 把对生效配置的修改拆分到全局配置和配置方案中，返回新的全局配置。
+ * 被生效的方案覆盖的配置项写入方案，例如在方案中加快语速不会影响其他程序；方案列表本身总是写入全局配置。
+ * `base` 全局配置。
+ * `old` 修改前生效的配置。
+ * `new` 修改后的配置。
+ * `profile` 生效的配置方案的名称。
+ * */
+pub(crate) fn split_config(
+    base: &ConfigRoot,
+    old: &ConfigRoot,
+    new: &ConfigRoot,
+    profile: Option<&str>,
+) -> ConfigRoot {
+    let mut profiles = new.profiles.clone();
+    let overlay = profile
+        .and_then(|n| profiles.iter_mut().find(|p| p.name == n))
+        .map(|p| &mut p.overlay);
+    let result = to_table(base).and_then(|mut table| {
+        split_changes(&to_table(old)?, &to_table(new)?, &mut table, overlay);
+        table.try_into::<ConfigRoot>().map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(config) => ConfigRoot { profiles, ..config },
+        Err(e) => {
+            error!("Can't split the changes of the config. {}", e);
+            new.clone()
+        }
+    }
+}
+
+// 把配置转换成表，不包含方案列表
+fn to_table(config: &ConfigRoot) -> Result<Table, String> {
+    let mut table = Table::try_from(config).map_err(|e| e.to_string())?;
+    table.remove("profiles");
+    Ok(table)
+}
+
+/**
+ * 三方合并配置，用于合并配置文件的外部修改和内存中还没有写入文件的修改。
+ * 只有一方修改过的配置项使用修改后的值，双方都修改过的配置项以外部的修改为准。
+ * `base` 双方修改前的配置。
+ * `ours` 内存中的配置。
+ * `theirs` 外部修改后的配置。
+ * */
+pub(crate) fn merge_three(base: &Table, ours: &Table, theirs: &Table) -> Table {
+    let mut merged = Table::new();
+    for k in ours.keys().chain(theirs.keys()) {
+        if merged.contains_key(k) {
+            continue;
+        }
+        let (b, o, t) = (base.get(k), ours.get(k), theirs.get(k));
+        let value = match (b, o, t) {
+            _ if o == b => t.cloned(),
+            _ if t == b => o.cloned(),
+            (Some(Value::Table(b)), Some(Value::Table(o)), Some(Value::Table(t))) => {
+                Some(Value::Table(merge_three(b, o, t)))
+            }
+            _ => t.cloned(),
+        };
+        if let Some(v) = value {
+            merged.insert(k.clone(), v);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod test_overlay {
+    use crate::configs::{
+        items::profile::ProfileConfig,
+        overlay::{apply_profile, merge_config, merge_three, split_changes, split_config},
+        ConfigRoot,
+    };
+    use toml::Table;
+
+    fn parse(text: &str) -> Table {
+        text.parse().unwrap()
+    }
+
+    // 全局配置中有一个覆盖了语速的配置方案
+    fn base_config() -> ConfigRoot {
+        let mut config = ConfigRoot::default();
+        config.profiles.push(ProfileConfig {
+            name: "fast".to_string(),
+            process_name: "code.exe".to_string(),
+            overlay: parse("[tts_config]\nspeed = 80\n"),
+            ..Default::default()
+        });
+        config
+    }
+
+    #[test]
+    fn test_split_changes() {
+        let old = parse("[tts_config]\nspeed = 80\npitch = 50\n");
+        let new = parse("[tts_config]\nspeed = 90\npitch = 30\n");
+        // 被方案覆盖的语速写入方案，没有覆盖的音调写入全局配置
+        let mut base = parse("[tts_config]\nspeed = 50\npitch = 50\n");
+        let mut overlay = parse("[tts_config]\nspeed = 80\n");
+        split_changes(&old, &new, &mut base, Some(&mut overlay));
+        assert_eq!(parse("[tts_config]\nspeed = 50\npitch = 30\n"), base);
+        assert_eq!(parse("[tts_config]\nspeed = 90\n"), overlay);
+        // 没有生效的方案时全部写入全局配置
+        let mut base = parse("[tts_config]\nspeed = 80\npitch = 50\n");
+        split_changes(&old, &new, &mut base, None);
+        assert_eq!(new, base);
+    }
+
+    #[test]
+    fn test_merge_config() {
+        let base = base_config();
+        let config = merge_config(&base, &base.profiles[0].overlay).unwrap();
+        assert_eq!(80, config.tts_config.speed);
+        assert_eq!(base.tts_config.pitch, config.tts_config.pitch);
+        assert_eq!(base.profiles, config.profiles);
+        // 覆盖的配置项不符合格式
+        assert!(merge_config(&base, &parse("[tts_config]\nspeed = \"fast\"\n")).is_err());
+        // 方案不能修改方案列表本身
+        let config = merge_config(&base, &parse("profiles = []\n")).unwrap();
+        assert_eq!(base.profiles, config.profiles);
+    }
+
+    #[test]
+    fn test_split_config() {
+        let base = base_config();
+        let old = apply_profile(&base, Some("fast"));
+        assert_eq!(80, old.tts_config.speed);
+
+        // 修改被方案覆盖的配置项只影响方案
+        let mut new = old.clone();
+        new.tts_config.speed = 90;
+        let result = split_config(&base, &old, &new, Some("fast"));
+        assert_eq!(base.tts_config.speed, result.tts_config.speed);
+        assert_eq!(
+            parse("[tts_config]\nspeed = 90\n"),
+            result.profiles[0].overlay
+        );
+        assert_eq!(90, apply_profile(&result, Some("fast")).tts_config.speed);
+
+        // 修改没有被覆盖的配置项写入全局配置，方案保持不变
+        let mut new = old.clone();
+        new.mouse_config.is_read = true;
+        let result = split_config(&base, &old, &new, Some("fast"));
+        assert!(result.mouse_config.is_read);
+        assert_eq!(base.profiles, result.profiles);
+    }
+
+    #[test]
+    fn test_merge_three() {
+        let base = parse("[tts_config]\nspeed = 50\npitch = 50\n");
+        // 只有内存中修改过
+        let ours = parse("[tts_config]\nspeed = 70\npitch = 50\n");
+        assert_eq!(ours, merge_three(&base, &ours, &base));
+        // 只有文件中修改过
+        let theirs = parse("[tts_config]\nspeed = 50\npitch = 30\n");
+        assert_eq!(theirs, merge_three(&base, &base, &theirs));
+        // 双方修改了不同的配置项，两边的修改都保留
+        assert_eq!(
+            parse("[tts_config]\nspeed = 70\npitch = 30\n"),
+            merge_three(&base, &ours, &theirs)
+        );
+        // 双方修改了同一个配置项，以文件中的为准
+        let theirs = parse("[tts_config]\nspeed = 90\npitch = 50\n");
+        assert_eq!(theirs, merge_three(&base, &ours, &theirs));
+    }
+
+    #[test]
+    fn test_merge_three_nested() {
+        let base = parse("[log_config]\nlevel = \"info\"\n[log_config.modules]\na = \"info\"\n");
+        let ours = parse(
+            "[log_config]\nlevel = \"info\"\n[log_config.modules]\na = \"debug\"\nb = \"warn\"\n",
+        );
+        let theirs = parse(
+            "[log_config]\nlevel = \"error\"\n[log_config.modules]\na = \"trace\"\n[mouse_config]\nis_read = true\n",
+        );
+        assert_eq!(
+            parse("[log_config]\nlevel = \"error\"\n[log_config.modules]\na = \"trace\"\nb = \"warn\"\n[mouse_config]\nis_read = true\n"),
+            merge_three(&base, &ours, &theirs)
+        );
+        // 一方删除了配置项，另一方没有修改时保持删除
+        let theirs = parse("[log_config]\nlevel = \"info\"\n");
+        assert_eq!(
+            parse("[log_config]\nlevel = \"info\"\n"),
+            merge_three(&base, &base, &theirs)
+        );
+    }
+}
@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::context::{Context, ContextAccessor};
+use log::error;
+use std::{sync::Weak, time::Duration};
+use tokio::time::sleep;
+
+// 检查配置文件是否被修改的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/**
+ * 监视配置文件，被外部程序（例如文本编辑器）修改后重新加载，不需要重启读屏。
+ * 重新加载成功后提示用户，文件无法解析时朗读出错的行号和原因，并继续使用当前的配置。
+ * `context` 读屏框架的上下文环境。
+ * */
+pub(crate) async fn watch(context: Weak<Context>) {
+    loop {
+        sleep(WATCH_INTERVAL).await;
+        if context.upgrade().is_none() {
+            break;
+        }
+        let msg = match context.get_config_manager().reload_if_changed() {
+            None => continue,
+            Some(Ok(_)) => t!("configs.reloaded"),
+            Some(Err(e)) => {
+                error!(
+                    "The config file is invalid at line {}. {}",
+                    e.line, e.message
+                );
+                t!("configs.reload_failed", line = e.line, error = e.message)
+            }
+        };
+        context.get_performer().speak(&msg).await;
+    }
+}
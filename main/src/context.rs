@@ -12,9 +12,10 @@
  */
 
 use crate::{
-    commander::Commander, configs::ConfigManager, event_core::EventCore, gui::GuiProvider,
-    navigator::UiNavigator, performer::Performer, resources::ResourceProvider,
-    talent::TalentProvider, tasks::TaskManager, terminator::Terminator,
+    cli::CliArgs, commander::Commander, configs::ConfigManager, event_core::EventCore,
+    gui::GuiProvider, navigator::UiNavigator, performer::Performer, remote::RemoteSession,
+    resources::ResourceProvider, talent::TalentProvider, tasks::TaskManager,
+    terminator::Terminator,
 };
 use a11y::{ia2::Ia2, jab::Jab};
 use log::info;
@@ -31,6 +32,7 @@ const CONFIG_FILE_NAME: &str = "config.toml";
 /// 核心上下文对象，通过此对象可以访问整个读屏框架的API
 #[derive(Debug)]
 pub(crate) struct Context {
+    cli_args: Arc<CliArgs>,
     commander: Arc<Commander>,
     config_manager: Arc<ConfigManager>,
     event_core: Arc<EventCore>,
@@ -44,6 +46,7 @@ pub(crate) struct Context {
     performer: Arc<Performer>,
     #[cfg(target_arch = "x86_64")]
     proxy32process: Arc<Proxy32Process>,
+    remote_session: Arc<RemoteSession>,
     talent_provider: Arc<TalentProvider>,
     task_manager: Arc<TaskManager>,
     terminator: Arc<Terminator>,
@@ -54,14 +57,24 @@ pub(crate) struct Context {
 impl Context {
     /**
      * 创建一个框架上下文环境。
+     * `work_runtime` 工作线程的运行时。
+     * `terminator` 终结者对象。
+     * `cli_args` 命令行参数。
      * */
-    pub(crate) fn new(work_runtime: &'static Runtime, terminator: Arc<Terminator>) -> Self {
+    pub(crate) fn new(
+        work_runtime: &'static Runtime,
+        terminator: Arc<Terminator>,
+        cli_args: CliArgs,
+    ) -> Self {
         // 创建一个指挥官，用于下发操作命令
         let commander = Commander::new();
 
-        // 配置管理器
-        let path = get_rigela_program_directory().join(CONFIG_FILE_NAME);
-        let config_manager = ConfigManager::new(path);
+        // 配置管理器，命令行可以指定其他的配置文件
+        let path = cli_args
+            .config
+            .clone()
+            .unwrap_or_else(|| get_rigela_program_directory().join(CONFIG_FILE_NAME));
+        let config_manager = ConfigManager::new(path, cli_args.safe_mode);
 
         // 创建表演者对象（用于把各种信息转换成用户可以感知的形式，例如语音、音效等）
         let performer = Performer::new();
@@ -85,6 +98,9 @@ impl Context {
         // 创建资源提供者
         let resource_provider = ResourceProvider::new();
 
+        // 远程协助的会话
+        let remote_session = RemoteSession::new();
+
         // 创建能力提供者
         let talent_provider = TalentProvider::new();
 
@@ -104,6 +120,7 @@ impl Context {
         let window_manager = GuiProvider::new();
 
         Self {
+            cli_args: cli_args.into(),
             commander: commander.into(),
             config_manager: config_manager.into(),
             msaa: msaa.into(),
@@ -113,6 +130,7 @@ impl Context {
             performer: performer.into(),
             #[cfg(target_arch = "x86_64")]
             proxy32process: proxy32process.into(),
+            remote_session: remote_session.into(),
             resource_provider: resource_provider.into(),
             talent_provider: talent_provider.into(),
             task_manager: task_manager.into(),
@@ -151,6 +169,7 @@ impl Context {
      * 清理环境。
      * */
     pub(crate) fn dispose(&self) {
+        self.remote_session.stop();
         self.event_core.shutdown();
         self.jab.remove_all_listeners();
         self.msaa.remove_all_listeners();
@@ -166,6 +185,7 @@ impl Context {
 impl Clone for Context {
     fn clone(&self) -> Self {
         Self {
+            cli_args: self.cli_args.clone(),
             commander: self.commander.clone(),
             config_manager: self.config_manager.clone(),
             msaa: self.msaa.clone(),
@@ -175,6 +195,7 @@ impl Clone for Context {
             performer: self.performer.clone(),
             #[cfg(target_arch = "x86_64")]
             proxy32process: self.proxy32process.clone(),
+            remote_session: self.remote_session.clone(),
             resource_provider: self.resource_provider.clone(),
             talent_provider: self.talent_provider.clone(),
             task_manager: self.task_manager.clone(),
@@ -190,6 +211,8 @@ impl Clone for Context {
 
 /// 用于访问Context中的私有字段
 pub(crate) trait ContextAccessor {
+    /// 获取命令行参数
+    fn get_cli_args(&self) -> &CliArgs;
     /// 获取指挥官对象
     fn get_commander(&self) -> &Commander;
     /// 获取配置管理器对象
@@ -211,6 +234,8 @@ pub(crate) trait ContextAccessor {
     /// 获取32位的代理进程对象
     #[cfg(target_arch = "x86_64")]
     fn get_proxy32process(&self) -> &Proxy32Process;
+    /// 获取远程协助的会话对象
+    fn get_remote_session(&self) -> &RemoteSession;
     /// 获取资源提供者对象
     fn get_resource_provider(&self) -> &ResourceProvider;
     /// 获取能力提供者对象
@@ -228,6 +253,10 @@ pub(crate) trait ContextAccessor {
 }
 
 impl ContextAccessor for Weak<Context> {
+    fn get_cli_args(&self) -> &CliArgs {
+        unsafe { &*self.as_ptr() }.cli_args.as_ref()
+    }
+
     fn get_commander(&self) -> &Commander {
         unsafe { &*self.as_ptr() }.commander.as_ref()
     }
@@ -269,6 +298,10 @@ impl ContextAccessor for Weak<Context> {
         unsafe { &*self.as_ptr() }.proxy32process.as_ref()
     }
 
+    fn get_remote_session(&self) -> &RemoteSession {
+        unsafe { &*self.as_ptr() }.remote_session.as_ref()
+    }
+
     fn get_resource_provider(&self) -> &ResourceProvider {
         unsafe { &*self.as_ptr() }.resource_provider.as_ref()
     }
@@ -299,6 +332,10 @@ impl ContextAccessor for Weak<Context> {
 }
 
 impl ContextAccessor for Arc<Context> {
+    fn get_cli_args(&self) -> &CliArgs {
+        self.cli_args.as_ref()
+    }
+
     fn get_commander(&self) -> &Commander {
         self.commander.as_ref()
     }
@@ -340,6 +377,10 @@ impl ContextAccessor for Arc<Context> {
         self.proxy32process.as_ref()
     }
 
+    fn get_remote_session(&self) -> &RemoteSession {
+        self.remote_session.as_ref()
+    }
+
     fn get_resource_provider(&self) -> &ResourceProvider {
         self.resource_provider.as_ref()
     }
@@ -12,12 +12,15 @@
  */
 
 mod dialog;
+pub(crate) mod display_model;
 pub(crate) mod editor;
 mod element;
 mod focus;
 mod ime;
 mod input;
+mod profile;
 mod progress;
+mod window_text;
 
 use std::{
     fmt::{Debug, Formatter},
@@ -27,13 +30,14 @@ use std::{
 
 use tokio::sync::Mutex;
 
-use crate::event_core::editor::Editor;
+use crate::event_core::{display_model::DisplayModel, editor::Editor};
 use crate::{
     context::Context,
     event_core::{
         dialog::subscribe_dialog_events, element::subscribe_element_events,
         focus::subscribe_focus_events, ime::subscribe_ime_events, input::subscribe_input_events,
-        progress::subscribe_progress_and_slider_events,
+        profile::subscribe_profile_events, progress::subscribe_progress_and_slider_events,
+        window_text::subscribe_window_text_events,
     },
 };
 
@@ -49,6 +53,7 @@ pub(crate) struct EventItem {
 pub(crate) struct EventCore {
     filter: Arc<Mutex<Vec<EventItem>>>,
     pub(crate) editor: Arc<Editor>,
+    pub(crate) display_model: Arc<DisplayModel>,
 }
 
 impl EventCore {
@@ -56,6 +61,7 @@ impl EventCore {
         Self {
             filter: Arc::new(vec![].into()),
             editor: Arc::new(Editor::new()),
+            display_model: Arc::new(DisplayModel::new()),
         }
     }
 
@@ -90,6 +96,9 @@ impl EventCore {
         // 订阅UIA的焦点元素改变事件
         subscribe_focus_events(context.clone()).await;
 
+        // 订阅前台窗口改变事件，用于切换配置方案
+        subscribe_profile_events(context.clone()).await;
+
         // 订阅对话框事件
         subscribe_dialog_events(context.clone()).await;
 
@@ -105,8 +114,14 @@ impl EventCore {
         // 订阅编辑框事件
         self.editor.subscribe_events(context.clone()).await;
 
+        // 订阅GDI文字输出事件，用于离屏显示模型
+        self.display_model.subscribe_events(context.clone()).await;
+
         // 订阅进度栏事件
         subscribe_progress_and_slider_events(context.clone()).await;
+
+        // 订阅传统窗口的文字变化事件
+        subscribe_window_text_events(context.clone()).await;
     }
 
     /**
@@ -0,0 +1,371 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    configs::items::navigation::NavigationConfig,
+    context::{Context, ContextAccessor},
+};
+use peeper::{
+    model::{GdiText, TextRect},
+    set_gdi_hooks,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+use win_wrap::{
+    common::{get_foreground_window, HWND},
+    threading::get_window_thread_process_id,
+};
+
+// 每个窗口最多保存的文字片段数量，超出后丢弃最早的片段
+const MAX_FRAGMENTS: usize = 2000;
+// 最多保存的窗口数量，超出后只保留正在更新的窗口
+const MAX_WINDOWS: usize = 32;
+
+/// 显示模型中的一段文字，对应程序的一次文字输出
+#[derive(Clone, Debug)]
+pub(crate) struct TextFragment {
+    pub(crate) text: String,
+    pub(crate) rect: TextRect,
+    pub(crate) font_name: String,
+    pub(crate) font_size: i32,
+    pub(crate) color: u32,
+}
+
+impl From<GdiText> for TextFragment {
+    fn from(value: GdiText) -> Self {
+        Self {
+            text: value.text,
+            rect: value.rect,
+            font_name: value.font_name,
+            font_size: value.font_size,
+            color: value.color,
+        }
+    }
+}
+
+/// 由文字片段按照位置重建出来的一行文字
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DisplayLine {
+    pub(crate) text: String,
+    pub(crate) rect: TextRect,
+    /// 行首文字的字体名称
+    pub(crate) font_name: String,
+    /// 行首文字的字体高度
+    pub(crate) font_size: i32,
+    /// 行首文字的颜色
+    pub(crate) color: u32,
+}
+
+/**
+ * 判断两个区域是否相交。
+ * */
+fn intersects(a: &TextRect, b: &TextRect) -> bool {
+    a.left < b.right && b.left < a.right && a.top < b.bottom && b.top < a.bottom
+}
+
+/**
+ * 判断一个点是否在区域中。
+ * */
+fn contains(rect: &TextRect, x: i32, y: i32) -> bool {
+    rect.left <= x && x < rect.right && rect.top <= y && y < rect.bottom
+}
+
+/**
+ * 一个顶层窗口中的所有文字片段，坐标都是屏幕坐标。
+ * */
+#[derive(Debug, Default)]
+pub(crate) struct WindowModel {
+    fragments: Vec<TextFragment>,
+}
+
+impl WindowModel {
+    /**
+     * 放入一段文字，新的文字会覆盖与他相交的旧文字，空白的文字表示擦除这个区域。
+     * `fragment` 文字片段。
+     * */
+    pub(crate) fn put(&mut self, fragment: TextFragment) {
+        self.fragments
+            .retain(|i| !intersects(&i.rect, &fragment.rect));
+        if fragment.text.trim().is_empty() {
+            return;
+        }
+        if self.fragments.len() >= MAX_FRAGMENTS {
+            self.fragments.remove(0);
+        }
+        self.fragments.push(fragment);
+    }
+
+    /**
+     * 获取指定位置的文字片段。
+     * `x` 屏幕横坐标。
+     * `y` 屏幕纵坐标。
+     * */
+    pub(crate) fn get_fragment_at(&self, x: i32, y: i32) -> Option<&TextFragment> {
+        self.fragments
+            .iter()
+            .rev()
+            .find(|i| contains(&i.rect, x, y))
+    }
+
+    /**
+     * 按照位置把文字片段重建成行，从上到下排列，每一行中的片段从左到右连接。
+     * 如果一个片段的垂直中心落在某一行的范围内，他就属于这一行；片段之间的空隙比较大时会插入空格。
+     * */
+    pub(crate) fn get_lines(&self) -> Vec<DisplayLine> {
+        let mut fragments: Vec<&TextFragment> = self.fragments.iter().collect();
+        fragments.sort_by_key(|i| (i.rect.top, i.rect.left));
+
+        let mut lines: Vec<(TextRect, Vec<&TextFragment>)> = vec![];
+        for fragment in fragments {
+            let center = (fragment.rect.top + fragment.rect.bottom) / 2;
+            match lines
+                .iter_mut()
+                .find(|(rect, _)| rect.top <= center && center < rect.bottom)
+            {
+                Some((rect, items)) => {
+                    rect.left = rect.left.min(fragment.rect.left);
+                    rect.top = rect.top.min(fragment.rect.top);
+                    rect.right = rect.right.max(fragment.rect.right);
+                    rect.bottom = rect.bottom.max(fragment.rect.bottom);
+                    items.push(fragment);
+                }
+                None => lines.push((fragment.rect, vec![fragment])),
+            }
+        }
+
+        lines.sort_by_key(|(rect, _)| (rect.top, rect.left));
+        lines
+            .into_iter()
+            .map(|(rect, mut items)| {
+                items.sort_by_key(|i| i.rect.left);
+                let first = items[0];
+                let (font_name, font_size, color) =
+                    (first.font_name.clone(), first.font_size, first.color);
+                let mut text = String::new();
+                let mut last_right = None;
+                for i in items {
+                    // 大约一个空格的宽度
+                    let space = ((i.rect.bottom - i.rect.top) / 4).max(1);
+                    if last_right.is_some_and(|r| i.rect.left - r >= space)
+                        && !text.ends_with(char::is_whitespace)
+                        && !i.text.starts_with(char::is_whitespace)
+                    {
+                        text.push(' ');
+                    }
+                    text.push_str(&i.text);
+                    last_right = Some(i.rect.right);
+                }
+                DisplayLine {
+                    text: text.trim().to_string(),
+                    rect,
+                    font_name,
+                    font_size,
+                    color,
+                }
+            })
+            .collect()
+    }
+}
+
+/**
+ * 离屏显示模型，通过peeper收集程序使用GDI输出的文字，按照顶层窗口组织。
+ * 这可以帮助我们浏览那些不支持MSAA和UIA的程序的界面。
+ * */
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DisplayModel {
+    windows: Arc<Mutex<HashMap<isize, WindowModel>>>,
+    // 平面浏览的位置，包括顶层窗口和行号
+    review: Arc<Mutex<(isize, usize)>>,
+    // 开启了GDI钩子的窗口和它的进程id
+    gdi_window: Arc<Mutex<Option<(isize, u32)>>>,
+}
+
+impl DisplayModel {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    订阅GDI文字输出事件，GDI钩子跟随前台窗口开启，关闭离屏显示模型的配置后不再开启。
+    `context` 读屏框架的上下文环境。
+    */
+    pub(crate) async fn subscribe_events(&self, context: Weak<Context>) {
+        let this = self.clone();
+        context
+            .get_peeper_server()
+            .add_on_gdi_text_listener(move |data| this.put(data))
+            .await;
+
+        let this = self.clone();
+        let ctx = context.clone();
+        context
+            .get_msaa()
+            .add_on_system_foreground_listener(move |src| {
+                let enabled = ctx
+                    .get_config_manager()
+                    .get_section::<NavigationConfig>()
+                    .use_display_model;
+                this.follow_foreground(enabled.then_some(src.h_wnd));
+            });
+
+        let this = self.clone();
+        context
+            .get_config_manager()
+            .subscribe::<NavigationConfig>(move |config| {
+                this.follow_foreground(config.use_display_model.then(get_foreground_window));
+            });
+    }
+
+    /**
+     * 把GDI钩子切换到前台窗口所在的进程，关闭之前的进程中的钩子，同一个进程中切换窗口时不需要重新开启。
+     * `h_wnd` 前台窗口，None表示关闭GDI钩子。
+     * */
+    fn follow_foreground(&self, h_wnd: Option<HWND>) {
+        let target = h_wnd.map(|h| (h, get_window_thread_process_id(h).1));
+        let old = std::mem::replace(
+            &mut *self.gdi_window.lock().unwrap(),
+            target.map(|(h, pid)| (h.0 as isize, pid)),
+        );
+        match (old, target) {
+            (Some((_, old_pid)), Some((_, pid))) if old_pid == pid => {}
+            (old, target) => {
+                if let Some((h, _)) = old {
+                    set_gdi_hooks(HWND(h as _), false);
+                }
+                if let Some((h, _)) = target {
+                    set_gdi_hooks(h, true);
+                }
+            }
+        }
+    }
+
+    /**
+     * 放入一段GDI输出的文字。
+     * `data` peeper收集的文字。
+     * */
+    pub(crate) fn put(&self, data: GdiText) {
+        let mut windows = self.windows.lock().unwrap();
+        if !windows.contains_key(&data.root) && windows.len() >= MAX_WINDOWS {
+            windows.clear();
+        }
+        windows.entry(data.root).or_default().put(data.into());
+    }
+
+    /**
+     * 获取一个顶层窗口中的所有行。
+     * `root` 顶层窗口句柄的值。
+     * */
+    pub(crate) fn get_lines(&self, root: isize) -> Vec<DisplayLine> {
+        let windows = self.windows.lock().unwrap();
+        windows
+            .get(&root)
+            .map(|i| i.get_lines())
+            .unwrap_or_default()
+    }
+
+    /**
+     * 获取一个顶层窗口中指定位置的文字。
+     * `root` 顶层窗口句柄的值。
+     * `x` 屏幕横坐标。
+     * `y` 屏幕纵坐标。
+     * */
+    pub(crate) fn get_text_at(&self, root: isize, x: i32, y: i32) -> Option<String> {
+        let windows = self.windows.lock().unwrap();
+        windows
+            .get(&root)?
+            .get_fragment_at(x, y)
+            .map(|i| i.text.clone())
+    }
+
+    /**
+     * 移动平面浏览的位置并返回所在的行，切换了窗口之后从第一行开始。
+     * `root` 顶层窗口句柄的值。
+     * `diff` 移动的行数，0表示当前行。
+     * */
+    pub(crate) fn move_review_line(&self, root: isize, diff: i32) -> Option<DisplayLine> {
+        let lines = self.get_lines(root);
+        let mut review = self.review.lock().unwrap();
+        if review.0 != root {
+            *review = (root, 0);
+        }
+        let index = review.1.min(lines.len().saturating_sub(1)) as i32 + diff;
+        if index < 0 {
+            return None;
+        }
+        let line = lines.get(index as usize)?.clone();
+        review.1 = index as usize;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod test_display_model {
+    use crate::event_core::display_model::{DisplayLine, TextFragment, WindowModel};
+    use peeper::model::TextRect;
+
+    fn fragment(text: &str, left: i32, top: i32, right: i32, bottom: i32) -> TextFragment {
+        TextFragment {
+            text: text.to_string(),
+            rect: TextRect {
+                left,
+                top,
+                right,
+                bottom,
+            },
+            font_name: "SimSun".to_string(),
+            font_size: bottom - top,
+            color: 0,
+        }
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut model = WindowModel::default();
+        // 故意打乱输出的顺序
+        model.put(fragment("World", 60, 10, 100, 26));
+        model.put(fragment("Second", 0, 30, 50, 46));
+        model.put(fragment("Hello", 0, 10, 40, 26));
+        // 稍微偏下一点的片段仍然属于第一行
+        model.put(fragment("!", 100, 12, 104, 28));
+        let lines: Vec<String> = model.get_lines().into_iter().map(|i| i.text).collect();
+        assert_eq!(vec!["Hello World!", "Second"], lines);
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut model = WindowModel::default();
+        model.put(fragment("Loading", 0, 0, 60, 16));
+        model.put(fragment("Ready", 0, 0, 50, 16));
+        assert_eq!(
+            vec![DisplayLine {
+                text: "Ready".to_string(),
+                rect: TextRect {
+                    left: 0,
+                    top: 0,
+                    right: 50,
+                    bottom: 16
+                },
+                font_name: "SimSun".to_string(),
+                font_size: 16,
+                color: 0,
+            }],
+            model.get_lines()
+        );
+        assert_eq!("Ready", model.get_fragment_at(10, 5).unwrap().text);
+        // 擦除整个区域
+        model.put(fragment("", 0, 0, 100, 100));
+        assert!(model.get_lines().is_empty());
+    }
+}
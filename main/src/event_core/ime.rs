@@ -12,17 +12,28 @@
  */
 
 use peeper::model::CandidateList;
-use std::sync::Weak;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Weak,
+};
 use win_wrap::input::{IME_CMODE_ALPHANUMERIC, IME_CMODE_FULLSHAPE, IME_CMODE_NATIVE};
 
 use crate::{
     cancel_edge_handle,
+    configs::items::ime::{CandidateReadMode, ImeConfig},
     context::{Context, ContextAccessor},
+    ext::candidate::CandidateListExt,
     performer::sound::SoundArgument::Single,
 };
 
 pub(crate) const MS_IME_CLASS_NAME: &str = "Windows.UI.Core.CoreWindow";
 
+// 表示候选窗口刚刚打开，还没有显示过任何一页
+const NO_PAGE: u32 = u32::MAX;
+
+// 上一次显示的候选页的起始位置，用于判断是否翻页
+static LAST_PAGE_START: AtomicU32 = AtomicU32::new(NO_PAGE);
+
 /**
 订阅输入法通知事件。
 `context` 读屏框架的上下文环境。
@@ -37,6 +48,24 @@ pub(crate) async fn subscribe_ime_events(context: Weak<Context>) {
         })
         .await;
 
+    // 订阅输入法组字事件
+    let ctx = context.clone();
+    context
+        .get_peeper_server()
+        .add_on_ime_composition_listener(move |composition| {
+            handle_ime_composition(ctx.clone(), composition);
+        })
+        .await;
+
+    // 订阅输入法候选窗口的打开和关闭事件
+    let ctx = context.clone();
+    context
+        .get_peeper_server()
+        .add_on_ime_candidate_window_listener(move |opened| {
+            handle_ime_candidate_window(ctx.clone(), opened);
+        })
+        .await;
+
     // 订阅输入法模式转换事件
     let ctx = context.clone();
     context
@@ -74,6 +103,7 @@ pub(crate) async fn subscribe_ime_events(context: Weak<Context>) {
             let candidate_list = CandidateList {
                 selection: 0,
                 page_start: 0,
+                page_size: 0,
                 list: vec![obj.get_name(child)],
             };
             handle_ime_candidate(ctx.clone(), candidate_list);
@@ -84,14 +114,33 @@ fn handle_ime_candidate(context: Weak<Context>, candidate_list: CandidateList) {
     // 关闭编辑框键盘事件朗读
     cancel_edge_handle!(context);
 
+    let config = context.get_config_manager().get_section::<ImeConfig>();
+    let last_page_start = LAST_PAGE_START.swap(candidate_list.page_start, Ordering::SeqCst);
+    let page_changed = last_page_start != candidate_list.page_start;
+    // 候选窗口刚打开时显示的是第一页，这不算翻页
+    let paged = page_changed && last_page_start != NO_PAGE;
+
     let ctx = context.clone();
     context.get_task_manager().push(
         "ime",
         context.get_work_runtime().spawn(async move {
-            let candidate = candidate_list.list[candidate_list.selection as usize]
-                .clone()
-                .trim_end()
-                .to_string();
+            if paged && config.announce_page {
+                let page = t!("ime.page", page = candidate_list.get_page_number());
+                if !ctx.get_performer().speak(&page).await {
+                    return;
+                }
+            }
+            if config.candidate_mode == CandidateReadMode::Page && page_changed {
+                // 新的一页，朗读整页的候选
+                ctx.get_performer()
+                    .speak(&candidate_list.get_page_content())
+                    .await;
+                return;
+            }
+            let Some(candidate) = candidate_list.list.get(candidate_list.selection as usize) else {
+                return;
+            };
+            let candidate = candidate.trim_end().to_string();
             if candidate.is_empty() {
                 return;
             }
@@ -110,3 +159,47 @@ fn handle_ime_candidate(context: Weak<Context>, candidate_list: CandidateList) {
         }),
     );
 }
+
+fn handle_ime_composition(context: Weak<Context>, composition: String) {
+    if composition.is_empty() {
+        // 组字结束，候选窗口也会随之关闭
+        LAST_PAGE_START.store(NO_PAGE, Ordering::SeqCst);
+        return;
+    }
+    if !context
+        .get_config_manager()
+        .get_section::<ImeConfig>()
+        .read_composition
+    {
+        return;
+    }
+    cancel_edge_handle!(context);
+
+    // 使用单独的任务名称，避免组字串的朗读被候选的朗读放弃
+    let ctx = context.clone();
+    context.get_task_manager().push(
+        "ime_composition",
+        context.get_work_runtime().spawn(async move {
+            ctx.get_performer().speak(&composition).await;
+        }),
+    );
+}
+
+fn handle_ime_candidate_window(context: Weak<Context>, opened: bool) {
+    LAST_PAGE_START.store(NO_PAGE, Ordering::SeqCst);
+    if !context
+        .get_config_manager()
+        .get_section::<ImeConfig>()
+        .announce_candidate_window
+    {
+        return;
+    }
+    let msg = match opened {
+        true => t!("ime.candidate_window_opened"),
+        false => t!("ime.candidate_window_closed"),
+    };
+    let ctx = context.clone();
+    context
+        .get_work_runtime()
+        .spawn(async move { ctx.get_performer().speak(&msg).await });
+}
@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::context::{Context, ContextAccessor};
+use std::{path::Path, sync::Weak};
+use win_wrap::{
+    common::close_handle,
+    threading::{get_window_thread_process_id, open_process, PROCESS_QUERY_LIMITED_INFORMATION},
+};
+use windows::{
+    core::PWSTR,
+    Win32::System::Threading::{QueryFullProcessImageNameW, PROCESS_NAME_WIN32},
+};
+
+/**
+ * 获取进程的可执行文件名称，例如`code.exe`，无法获取时返回空字符串。
+ * `pid` 进程id。
+ * */
+fn get_process_name(pid: u32) -> String {
+    let Ok(handle) = open_process(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+        return String::new();
+    };
+    let mut buf = [0u16; 1024];
+    let mut size = buf.len() as u32;
+    let res = unsafe {
+        QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR(buf.as_mut_ptr()),
+            &mut size,
+        )
+    };
+    close_handle(handle);
+    if res.is_err() {
+        return String::new();
+    }
+    let path = String::from_utf16_lossy(&buf[..size as usize]);
+    Path::new(&path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+//noinspection SpellCheckingInspection
+/**
+订阅前台窗口改变事件，根据前台程序切换配置方案。
+`context` 读屏框架的上下文环境。
+*/
+pub(crate) async fn subscribe_profile_events(context: Weak<Context>) {
+    let ctx = context.clone();
+    context
+        .get_msaa()
+        .add_on_system_foreground_listener(move |src| {
+            let (_, pid) = get_window_thread_process_id(src.h_wnd);
+            let process_name = get_process_name(pid);
+            // 配置方案中覆盖的配置项会通过配置订阅通知给对应的组件，例如语音的参数
+            ctx.get_config_manager()
+                .switch_profile(&process_name, &src.get_class_name());
+        });
+}
@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use peeper::model::WindowText;
+use std::{sync::Weak, time::Duration};
+
+use crate::{
+    configs::items::window_text::WindowTextConfig,
+    context::{Context, ContextAccessor},
+};
+
+// 等待文字稳定的时间，很多程序会在短时间内连续修改同一个状态栏，只朗读最后一次的文字
+const SETTLE_DURATION: Duration = Duration::from_millis(200);
+// 相同的文字在这个时间内只朗读一次
+const REPEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/**
+ * 传统窗口的种类，通过窗口类名判断。
+ * */
+enum WindowKind {
+    StatusBar,
+    Label,
+    Other,
+}
+
+impl WindowKind {
+    fn from_class_name(class_name: &str) -> Self {
+        // WinForms等框架会在类名中加入前缀和后缀，例如“WindowsForms10.STATIC.app.0.xxx”
+        let class_name = class_name.to_lowercase();
+        if class_name.contains("statusbar") {
+            Self::StatusBar
+        } else if class_name.contains("static") {
+            Self::Label
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/**
+订阅传统窗口的文字变化事件，这些窗口修改文字时不会发出无障碍事件，只能通过peeper得知。
+`context` 读屏框架的上下文环境。
+*/
+pub(crate) async fn subscribe_window_text_events(context: Weak<Context>) {
+    // 订阅WM_SETTEXT事件，只关心状态栏和标签
+    let ctx = context.clone();
+    context
+        .get_peeper_server()
+        .add_on_window_text_changed_listener(move |data| {
+            let config = ctx.get_config_manager().get_section::<WindowTextConfig>();
+            let enabled = match WindowKind::from_class_name(&data.class_name) {
+                WindowKind::StatusBar => config.announce_status_bar,
+                WindowKind::Label => config.announce_label,
+                WindowKind::Other => false,
+            };
+            if enabled {
+                handle_window_text(ctx.clone(), data);
+            }
+        })
+        .await;
+
+    // 订阅编辑框的内容改变事件
+    let ctx = context.clone();
+    context
+        .get_peeper_server()
+        .add_on_edit_changed_listener(move |data| {
+            if ctx
+                .get_config_manager()
+                .get_section::<WindowTextConfig>()
+                .announce_edit
+            {
+                handle_window_text(ctx.clone(), data);
+            }
+        })
+        .await;
+}
+
+fn handle_window_text(context: Weak<Context>, data: WindowText) {
+    let text = data.text.trim().to_string();
+    if text.is_empty() {
+        return;
+    }
+
+    // 使用同一个任务名称，新的文字会放弃还没来得及朗读的旧文字
+    let ctx = context.clone();
+    context.get_task_manager().push(
+        "window_text",
+        context.get_work_runtime().spawn(async move {
+            tokio::time::sleep(SETTLE_DURATION).await;
+            if ctx
+                .get_event_core()
+                .should_ignore(text.clone(), REPEAT_INTERVAL)
+                .await
+            {
+                return;
+            }
+            ctx.get_performer().speak(&text).await;
+        }),
+    );
+}
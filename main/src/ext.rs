@@ -11,6 +11,7 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
+pub(crate) mod candidate;
 pub(crate) mod dialog;
 pub(crate) mod element;
 pub(crate) mod role;
@@ -0,0 +1,98 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use peeper::model::CandidateList;
+
+impl CandidateListExt for CandidateList {
+    fn get_page_number(&self) -> u32 {
+        if self.page_size == 0 {
+            return 1;
+        }
+        self.page_start / self.page_size + 1
+    }
+
+    fn get_page_content(&self) -> String {
+        let start = (self.page_start as usize).min(self.list.len());
+        let end = match self.page_size {
+            0 => self.list.len(),
+            x => (start + x as usize).min(self.list.len()),
+        };
+        self.list[start..end]
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} {}", i + 1, c.trim_end()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub(crate) trait CandidateListExt {
+    /**
+     * 获取当前页的页码，从1开始。
+     * */
+    fn get_page_number(&self) -> u32;
+
+    /**
+     * 获取当前页的所有候选，每个候选前面带有序号，例如“1 是, 2 事, 3 市”。
+     * */
+    fn get_page_content(&self) -> String;
+}
+
+#[cfg(test)]
+mod test_candidate {
+    use crate::ext::candidate::CandidateListExt;
+    use peeper::model::CandidateList;
+
+    fn candidates(count: usize, page_start: u32, page_size: u32) -> CandidateList {
+        CandidateList {
+            selection: page_start,
+            page_start,
+            page_size,
+            list: (1..=count).map(|i| format!("候选{} ", i)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_get_page_number() {
+        assert_eq!(1, candidates(9, 0, 5).get_page_number());
+        assert_eq!(2, candidates(9, 5, 5).get_page_number());
+        // 页的起始位置不在页的边界上时，按所在的页计算
+        assert_eq!(2, candidates(9, 8, 5).get_page_number());
+        // 输入法没有提供每页的数量
+        assert_eq!(1, candidates(9, 5, 0).get_page_number());
+        assert_eq!(1, candidates(0, 0, 0).get_page_number());
+    }
+
+    #[test]
+    fn test_get_page_content() {
+        assert_eq!(
+            "1 候选1, 2 候选2, 3 候选3",
+            candidates(7, 0, 3).get_page_content()
+        );
+        assert_eq!(
+            "1 候选4, 2 候选5, 3 候选6",
+            candidates(7, 3, 3).get_page_content()
+        );
+        // 最后一页不满
+        assert_eq!("1 候选7", candidates(7, 6, 3).get_page_content());
+        // 没有每页的数量时朗读剩下的所有候选
+        assert_eq!("1 候选2, 2 候选3", candidates(3, 1, 0).get_page_content());
+    }
+
+    #[test]
+    fn test_empty_page() {
+        assert_eq!("", candidates(0, 0, 5).get_page_content());
+        // 页的起始位置超出了候选列表
+        assert_eq!("", candidates(3, 5, 5).get_page_content());
+    }
+}
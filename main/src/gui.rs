@@ -18,8 +18,8 @@ mod utils;
 use crate::{
     context::Context,
     gui::forms::{
-        about::AboutForm, popup_menu::PopupMenuForm, settings_form::SettingsForm,
-        system_tray::SystemTray, welcome::WelcomeForm,
+        about::AboutForm, log_viewer::LogViewerForm, popup_menu::PopupMenuForm,
+        settings_form::SettingsForm, system_tray::SystemTray, welcome::WelcomeForm,
     },
 };
 use log::error;
@@ -53,6 +53,7 @@ pub(crate) struct GuiProvider {
     popup_menu: OnceLock<(NoticeSender, NoticeSender)>,
     settings: OnceLock<(NoticeSender, NoticeSender)>,
     about: OnceLock<(NoticeSender, NoticeSender)>,
+    log_viewer: OnceLock<(NoticeSender, NoticeSender)>,
     hotkeys: OnceLock<NoticeSender>,
 }
 
@@ -102,6 +103,7 @@ impl GuiProvider {
             build_form!(popup_menu, PopupMenuForm, context, tx);
             build_form!(settings, SettingsForm, context, tx);
             build_form!(about, AboutForm, context, tx);
+            build_form!(log_viewer, LogViewerForm, context, tx);
 
             let s = settings.show_hotkeys_notice.sender().clone();
             tx.send((s.clone(), s.clone())).unwrap();
@@ -114,6 +116,7 @@ impl GuiProvider {
         let _ = self.popup_menu.set(rx.recv().unwrap());
         let _ = self.settings.set(rx.recv().unwrap());
         let _ = self.about.set(rx.recv().unwrap());
+        let _ = self.log_viewer.set(rx.recv().unwrap());
         let _ = self.hotkeys.set(rx.recv().unwrap().0);
 
         self.welcome.get().unwrap().0.notice();
@@ -148,6 +151,10 @@ impl GuiProvider {
     pub(crate) fn show_about_form(&self) {
         self.about.get().unwrap().0.notice();
     }
+
+    pub(crate) fn show_log_viewer_form(&self) {
+        self.log_viewer.get().unwrap().0.notice();
+    }
 }
 
 impl Debug for GuiProvider {
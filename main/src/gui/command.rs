@@ -14,14 +14,22 @@
 use crate::{
     commander::keyboard::keys::Keys,
     configs::{
-        items::{general::Lang, tts::TtsConfig},
-        operations::{apply_mouse_config, save_auto_check_update, save_lang, save_run_on_startup},
+        bundle::{export_bundle, ImportMode, SettingsBundle},
+        items::{
+            general::{KeyboardLayout, Lang},
+            log::LogLevel,
+            tts::TtsConfig,
+        },
+        operations::{
+            apply_mouse_config, save_auto_check_update, save_keyboard_layout, save_lang,
+            save_log_level, save_run_on_startup,
+        },
         ConfigRoot,
     },
     context::{Context, ContextAccessor},
     gui::utils::{
-        backup_data, check_update, confirm_update_exists, create_shortcut_link, restore_data,
-        set_startup_registry, UpdateState, HELP_DIR,
+        check_update, confirm_update_exists, create_shortcut_link, set_startup_registry,
+        UpdateState, HELP_DIR,
     },
     talent::Talented,
 };
@@ -141,6 +149,19 @@ pub(crate) fn about_form_cmd(context: Weak<Context>) {
     context.get_gui_provider().show_about_form();
 }
 
+/// 打开日志查看器
+pub(crate) fn log_viewer_cmd(context: Weak<Context>) {
+    context.get_gui_provider().show_log_viewer_form();
+}
+
+/// 设置日志级别
+pub(crate) fn set_log_level_cmd(context: Weak<Context>, index: usize) {
+    let Some(level) = LogLevel::ALL.get(index) else {
+        return;
+    };
+    save_log_level(context, *level);
+}
+
 /// 访问开源官网
 pub(crate) fn visit_host_website_cmd(_context: Weak<Context>) {
     const URL: &str = "https://github.com/mzdk100/rigela";
@@ -208,6 +229,14 @@ pub(crate) fn set_lang_cmd(context: Weak<Context>, index: usize) {
     });
 }
 
+/// 设置键盘布局
+pub(crate) fn set_keyboard_layout_cmd(context: Weak<Context>, index: usize) {
+    let Some(layout) = KeyboardLayout::ALL.get(index) else {
+        return;
+    };
+    save_keyboard_layout(context, *layout);
+}
+
 /// 设置语音角色
 pub(crate) fn set_voice_cmd(context: Weak<Context>, engine: String, name: String) {
     let ctx = context.clone();
@@ -222,13 +251,11 @@ pub(crate) fn set_voice_cmd(context: Weak<Context>, engine: String, name: String
             .clone();
 
         let mut root = ctx.get_config_manager().get_config();
-        let config = TtsConfig {
+        root.tts_config = TtsConfig {
             voice: (info.engine, info.id),
             ..root.tts_config
         };
-        root.tts_config = config.clone();
         ctx.get_config_manager().set_config(&root);
-        tts.apply_config(&config.clone()).await;
         ctx.get_performer()
             .speak(&t!("command.tts_role", value = info.name))
             .await;
@@ -240,17 +267,14 @@ pub(crate) fn set_speed_cmd(context: Weak<Context>, index: usize) {
     let speed = 100 - index as i32;
 
     let mut root = context.get_config_manager().get_config();
-    let config = TtsConfig {
+    root.tts_config = TtsConfig {
         speed,
         ..root.tts_config
     };
-    root.tts_config = config.clone();
     context.get_config_manager().set_config(&root);
 
-    let tts = context.get_performer().get_tts();
     let ctx = context.clone();
     context.get_work_runtime().spawn(async move {
-        tts.apply_config(&config.clone()).await;
         ctx.get_performer()
             .speak(&t!("command.tts_speed", value = speed))
             .await;
@@ -262,17 +286,14 @@ pub(crate) fn set_pitch_cmd(context: Weak<Context>, index: usize) {
     let pitch = 100 - index as i32;
 
     let mut root = context.get_config_manager().get_config();
-    let config = TtsConfig {
+    root.tts_config = TtsConfig {
         pitch,
         ..root.tts_config
     };
-    root.tts_config = config.clone();
     context.get_config_manager().set_config(&root);
 
-    let tts = context.get_performer().get_tts();
     let ctx = context.clone();
     context.get_work_runtime().spawn(async move {
-        tts.apply_config(&config.clone()).await;
         ctx.get_performer()
             .speak(&t!("command.tts_pitch", value = pitch))
             .await;
@@ -284,17 +305,14 @@ pub(crate) fn set_volume_cmd(context: Weak<Context>, index: usize) {
     let volume = 100 - index as i32;
 
     let mut root = context.get_config_manager().get_config();
-    let config = TtsConfig {
+    root.tts_config = TtsConfig {
         volume,
         ..root.tts_config
     };
-    root.tts_config = config.clone();
     context.get_config_manager().set_config(&root);
 
-    let tts = context.get_performer().get_tts();
     let ctx = context.clone();
     context.get_work_runtime().spawn(async move {
-        tts.apply_config(&config.clone()).await;
         ctx.get_performer()
             .speak(&t!("command.tts_volume", value = volume))
             .await;
@@ -315,27 +333,83 @@ pub(crate) fn set_mouse_read_cmd(context: Weak<Context>, toggle: bool) {
     });
 }
 
-/// 导出配置
-pub(crate) fn export_config_cmd(_context: Weak<Context>, path: PathBuf) {
-    if backup_data(&path).is_err() {
-        error!("备份数据失败");
-    }
+/// 导出设置包
+pub(crate) fn export_config_cmd(context: Weak<Context>, path: PathBuf) {
+    let path = match path.extension() {
+        Some(_) => path,
+        None => path.with_extension("zip"),
+    };
+    let config = context.get_config_manager().get_global_config();
+    let msg = match export_bundle(&path, &config, &get_rigela_program_directory()) {
+        Ok(_) => t!("command.msg_export_success"),
+        Err(e) => {
+            error!("Can't export the settings bundle. {}", e);
+            t!("command.msg_export_failed", error = e)
+        }
+    };
 
-    message_box(
-        HWND::default(),
-        &t!("command.msg_export_success"),
-        &t!("command.msg_mind_title"),
-        MB_OK,
-    );
+    message_box(HWND::default(), &msg, &t!("command.msg_mind_title"), MB_OK);
 }
 
-/// 导入配置
+/// 导入设置包，先校验并预览修改的内容，再由用户选择合并还是替换
 pub(crate) fn import_config_cmd(context: Weak<Context>, path: PathBuf) {
-    if restore_data(&path).is_err() {
-        error!("Can't restore the data.");
-    }
+    let bundle = match SettingsBundle::open(&path) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Can't open the settings bundle. {}", e);
+            message_box(
+                HWND::default(),
+                &t!("command.msg_import_invalid", error = e),
+                &t!("command.msg_mind_title"),
+                MB_OK,
+            );
+            return;
+        }
+    };
 
-    context.get_config_manager().apply();
+    let current = context.get_config_manager().get_global_config();
+    let describe = |mode| {
+        let preview = bundle.preview(&current, mode);
+        match preview.sections.is_empty() {
+            true => t!("command.msg_import_no_change").to_string(),
+            false => preview
+                .sections
+                .iter()
+                .map(|s| t!(format!("command.section_{}", s)).to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    };
+    let version = bundle
+        .get_app_version()
+        .map_or(t!("command.msg_import_unknown_version").to_string(), |v| {
+            v.to_string()
+        });
+    let content = t!(
+        "command.msg_import_preview",
+        version = version,
+        merge = describe(ImportMode::Merge),
+        replace = describe(ImportMode::Replace),
+        sounds = bundle.preview(&current, ImportMode::Merge).sounds
+    );
+    let msg_params = MessageParams {
+        title: &t!("command.msg_confirm_title"),
+        content: &content,
+        buttons: MessageButtons::YesNoCancel,
+        icons: MessageIcons::Question,
+    };
+    let mode = match message(&msg_params) {
+        MessageChoice::Yes => ImportMode::Merge,
+        MessageChoice::No => ImportMode::Replace,
+        _ => return,
+    };
+
+    if let Err(e) = bundle.install_sounds(&get_rigela_program_directory()) {
+        error!("Can't install the sounds of the settings bundle. {}", e);
+    }
+    context
+        .get_config_manager()
+        .replace_config(bundle.resolve(&current, mode));
     reapply_config(context.clone());
 
     message_box(
@@ -373,13 +447,9 @@ fn reapply_config(context: Weak<Context>) {
         error!("关闭开机自动启动失败");
     }
 
+    // 语音的配置通过配置订阅应用到TTS
     let ctx = context.clone();
-    let tts = context.get_performer().get_tts();
-    let tts_cfg = config.tts_config.clone();
     context.get_work_runtime().spawn(async move {
-        // 应用配置到TTS
-        tts.apply_config(&tts_cfg.clone()).await;
-
         // 重新显示设置界面，更新界面上的状态值
         ctx.get_gui_provider().show_settings_form();
 
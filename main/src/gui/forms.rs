@@ -13,7 +13,9 @@
 
 pub(crate) mod about;
 pub(crate) mod hotkeys;
+pub(crate) mod log_viewer;
 pub(crate) mod popup_menu;
+pub(crate) mod profiles;
 pub(crate) mod settings_form;
 pub(crate) mod system_tray;
 pub(crate) mod welcome;
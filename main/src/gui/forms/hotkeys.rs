@@ -127,8 +127,9 @@ impl SettingsForm {
             let (keys_str, col) = match custom_talent {
                 Some(combo_key) => (combo_key.to_string(), 2),
                 None => (
-                    talent
-                        .get_combo_key()
+                    context
+                        .get_talent_provider()
+                        .get_default_combo_key(id)
                         .unwrap_or(ComboKey::default())
                         .to_string(),
                     1,
@@ -219,12 +220,9 @@ impl SettingsForm {
                 return;
             }
 
+            // 热键配置的改变会通过配置订阅更新到能力访问器中
             self.custom_combo_keys.borrow_mut().remove(id);
             save_hotkeys(context.clone(), self.custom_combo_keys.borrow().clone());
-
-            context
-                .get_talent_provider()
-                .update_custom_combo_key_map(context.clone());
         }
 
         self.init_data();
@@ -270,10 +268,6 @@ impl SettingsForm {
                 .borrow_mut()
                 .insert(id.clone(), combo_key);
             save_hotkeys(context.clone(), self.custom_combo_keys.borrow().clone());
-
-            context
-                .get_talent_provider()
-                .update_custom_combo_key_map(context.clone());
         }
 
         self.init_data();
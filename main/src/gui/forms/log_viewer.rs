@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{bring_window_front, context::Context};
+use log::{error, Level};
+use native_windows_derive::NwgUi;
+use native_windows_gui::{
+    stop_thread_dispatch, Button, ComboBox, GridLayout, Label, Notice, NoticeSender, TextBox,
+    TextInput, Window,
+};
+use rigela_macros::GuiFormImpl;
+use rigela_utils::{clip::set_clipboard_text, logger::get_log_files};
+use std::{
+    cell::RefCell,
+    fs::read,
+    process::Command,
+    str::FromStr,
+    sync::{OnceLock, Weak},
+};
+
+const SIZE: (u32, u32) = (800, 600);
+
+// 最多显示的日志条数，日志太多时编辑框会很卡，只显示最新的部分
+const MAX_ENTRIES: usize = 5000;
+
+// 可以选择的最低级别，和cb_level中的选项顺序相同
+const LEVELS: [Level; 5] = [
+    Level::Error,
+    Level::Warn,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+/// 一条日志，可能包含多行
+struct LogEntry {
+    level: Option<Level>,
+    text: String,
+}
+
+/**
+ * 把日志文件的内容拆分成日志条目。
+ * 日志文件中每一条日志的格式是`时间 级别 模块 - 内容`，不是这种格式开头的行属于上一条日志。
+ * `content` 日志文件的内容。
+ * */
+fn parse_entries(content: &str, entries: &mut Vec<LogEntry>) {
+    for line in content.lines() {
+        let level = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|l| Level::from_str(l).ok());
+        match entries.last_mut() {
+            Some(last) if level.is_none() => {
+                last.text.push_str("\r\n");
+                last.text.push_str(line);
+            }
+            _ => entries.push(LogEntry {
+                level,
+                text: line.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Default, NwgUi, GuiFormImpl)]
+pub struct LogViewerForm {
+    context: OnceLock<Weak<Context>>,
+    entries: RefCell<Vec<LogEntry>>,
+
+    #[nwg_control(title: & t ! ("log_viewer.title"), size: (0, 0), position: (250, 250), flags: "WINDOW|VISIBLE")]
+    #[nwg_events(OnWindowClose: [LogViewerForm::on_exit], OnInit: [LogViewerForm::on_init])]
+    window: Window,
+
+    #[nwg_layout(parent: window, spacing: 5, max_column: Some(6), max_row: Some(10))]
+    layout: GridLayout,
+
+    #[nwg_control(text: & t ! ("log_viewer.lb_keyword"))]
+    #[nwg_layout_item(layout: layout, col: 0, row: 0)]
+    lb_keyword: Label,
+
+    #[nwg_control(focus: true)]
+    #[nwg_layout_item(layout: layout, col: 1, col_span: 2, row: 0)]
+    #[nwg_events(OnTextInput: [LogViewerForm::update_content])]
+    tb_keyword: TextInput,
+
+    #[nwg_control(text: & t ! ("log_viewer.lb_level"))]
+    #[nwg_layout_item(layout: layout, col: 3, row: 0)]
+    lb_level: Label,
+
+    #[nwg_control(collection: vec ! [
+    t ! ("configs.log_level_error").to_string(),
+    t ! ("configs.log_level_warn").to_string(),
+    t ! ("configs.log_level_info").to_string(),
+    t ! ("configs.log_level_debug").to_string(),
+    t ! ("configs.log_level_trace").to_string(),
+    ], selected_index: Some(4))]
+    #[nwg_layout_item(layout: layout, col: 4, col_span: 2, row: 0)]
+    #[nwg_events(OnComboxBoxSelection: [LogViewerForm::update_content])]
+    cb_level: ComboBox<String>,
+
+    #[nwg_control(readonly: true, flags: "VISIBLE|TAB_STOP|VSCROLL|HSCROLL|AUTOVSCROLL")]
+    #[nwg_layout_item(layout: layout, col: 0, col_span: 6, row: 1, row_span: 8)]
+    tb_content: TextBox,
+
+    #[nwg_control(text: & t ! ("log_viewer.btn_refresh"))]
+    #[nwg_layout_item(layout: layout, col: 0, row: 9)]
+    #[nwg_events(OnButtonClick: [LogViewerForm::on_refresh])]
+    btn_refresh: Button,
+
+    #[nwg_control(text: & t ! ("log_viewer.btn_copy"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 9)]
+    #[nwg_events(OnButtonClick: [LogViewerForm::on_copy])]
+    btn_copy: Button,
+
+    #[nwg_control(text: & t ! ("log_viewer.btn_open_folder"))]
+    #[nwg_layout_item(layout: layout, col: 2, row: 9)]
+    #[nwg_events(OnButtonClick: [LogViewerForm::on_open_folder])]
+    btn_open_folder: Button,
+
+    #[nwg_control(text: & t ! ("log_viewer.btn_close"))]
+    #[nwg_layout_item(layout: layout, col: 5, row: 9)]
+    #[nwg_events(OnButtonClick: [LogViewerForm::on_exit])]
+    btn_close: Button,
+
+    #[nwg_control()]
+    #[nwg_events(OnNotice: [LogViewerForm::on_show_notice])]
+    show_notice: Notice,
+
+    #[nwg_control()]
+    #[nwg_events(OnNotice: [LogViewerForm::on_exit_notice])]
+    exit_notice: Notice,
+}
+
+impl LogViewerForm {
+    fn on_init(&self) {
+        self.window.set_visible(false);
+    }
+
+    fn on_exit(&self) {
+        self.window.set_visible(false);
+        // 日志可能很大，关闭后不再占用内存
+        self.entries.borrow_mut().clear();
+        self.tb_content.clear();
+    }
+
+    // 重新读取所有的日志文件，包括滚动的归档
+    fn on_refresh(&self) {
+        let mut entries = vec![];
+        for path in get_log_files() {
+            match read(&path) {
+                Ok(data) => parse_entries(&String::from_utf8_lossy(&data), &mut entries),
+                Err(e) => error!("Can't read the log file {}. {}", path.display(), e),
+            }
+        }
+        *self.entries.borrow_mut() = entries;
+        self.update_content();
+    }
+
+    // 按照关键字和最低级别筛选日志，显示最新的部分
+    fn update_content(&self) {
+        let keyword = self.tb_keyword.text().to_lowercase();
+        let level = LEVELS[self.cb_level.selection().unwrap_or(LEVELS.len() - 1)];
+        let entries = self.entries.borrow();
+        let mut lines: Vec<&str> = entries
+            .iter()
+            .rev()
+            .filter(|e| e.level.map_or(true, |l| l <= level))
+            .filter(|e| keyword.is_empty() || e.text.to_lowercase().contains(&keyword))
+            .take(MAX_ENTRIES)
+            .map(|e| e.text.as_str())
+            .collect();
+        lines.reverse();
+        self.tb_content.set_text(&lines.join("\r\n"));
+        self.tb_content.scroll_lastline();
+    }
+
+    // 复制筛选后的日志，方便在反馈问题时粘贴
+    fn on_copy(&self) {
+        set_clipboard_text(self.tb_content.text());
+    }
+
+    fn on_open_folder(&self) {
+        let Some(dir) = get_log_files()
+            .pop()
+            .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+        else {
+            return;
+        };
+        if let Err(e) = Command::new("explorer").arg(dir).spawn() {
+            error!("Can't open the log folder. {}", e);
+        }
+    }
+
+    fn on_show_notice(&self) {
+        bring_window_front!(&self.window);
+        self.window.set_size(SIZE.0, SIZE.1);
+        self.window.set_visible(true);
+        self.on_refresh();
+        self.tb_keyword.set_focus();
+    }
+
+    fn on_exit_notice(&self) {
+        stop_thread_dispatch()
+    }
+}
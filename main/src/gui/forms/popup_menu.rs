@@ -16,7 +16,7 @@ use crate::{
     context::Context,
     gui::command::{
         about_form_cmd, check_update_cmd, custom_hotkeys_cmd, donate_cmd, exit_cmd, help_cmd,
-        settings_cmd, visit_host_website_cmd, welcome_form_cmd,
+        log_viewer_cmd, settings_cmd, visit_host_website_cmd, welcome_form_cmd,
     },
 };
 use native_windows_derive::NwgUi;
@@ -67,6 +67,10 @@ pub struct PopupMenuForm {
     #[nwg_events(OnMenuItemSelected: [PopupMenuForm::on_check_update])]
     check_update_item: MenuItem,
 
+    #[nwg_control(parent: out_help_item, text: & t ! ("popupmenu.log_viewer_item"))]
+    #[nwg_events(OnMenuItemSelected: [PopupMenuForm::on_log_viewer])]
+    log_viewer_item: MenuItem,
+
     #[nwg_control(parent: out_help_item, text: & t ! ("popupmenu.about_item"))]
     #[nwg_events(OnMenuItemSelected: [PopupMenuForm::on_about])]
     about_item: MenuItem,
@@ -109,6 +113,10 @@ impl PopupMenuForm {
         about_form_cmd(self.context.get().unwrap().clone());
     }
 
+    fn on_log_viewer(&self) {
+        log_viewer_cmd(self.context.get().unwrap().clone());
+    }
+
     fn on_visit_host(&self) {
         visit_host_website_cmd(self.context.get().unwrap().clone());
     }
@@ -0,0 +1,226 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    configs::{
+        items::profile::ProfileConfig,
+        operations::{get_profiles, save_profiles},
+        overlay::merge_config,
+    },
+    context::ContextAccessor,
+    gui::forms::settings_form::SettingsForm,
+};
+use native_windows_derive::NwgPartial;
+use native_windows_gui::{
+    modal_error_message, modal_message, Button, GridLayout, Label, ListBox, MessageButtons,
+    MessageChoice, MessageIcons, MessageParams, TextBox, TextInput,
+};
+use toml::Table;
+
+#[derive(Default, NwgPartial)]
+pub struct ProfilesUi {
+    #[nwg_layout(max_size: [1200, 800], min_size: [650, 480], spacing: 10, max_column: Some(6), max_row: Some(10))]
+    layout: GridLayout,
+
+    #[nwg_layout(min_size: [600, 480], max_column: Some(4), max_row: Some(10))]
+    layout2: GridLayout,
+
+    #[nwg_control(collection: vec ! [])]
+    #[nwg_layout_item(layout: layout, col: 0, col_span: 2, row: 0, row_span: 8)]
+    pub(crate) lb_profiles: ListBox<String>,
+
+    #[nwg_control(text: & t ! ("profiles.lb_name"))]
+    #[nwg_layout_item(layout: layout, col: 2, row: 0)]
+    lb_name: Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: layout, col: 3, col_span: 3, row: 0)]
+    tb_name: TextInput,
+
+    #[nwg_control(text: & t ! ("profiles.lb_process_name"))]
+    #[nwg_layout_item(layout: layout, col: 2, row: 1)]
+    lb_process_name: Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: layout, col: 3, col_span: 3, row: 1)]
+    tb_process_name: TextInput,
+
+    #[nwg_control(text: & t ! ("profiles.lb_class_name"))]
+    #[nwg_layout_item(layout: layout, col: 2, row: 2)]
+    lb_class_name: Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: layout, col: 3, col_span: 3, row: 2)]
+    tb_class_name: TextInput,
+
+    #[nwg_control(text: & t ! ("profiles.lb_overlay"))]
+    #[nwg_layout_item(layout: layout, col: 2, row: 3)]
+    lb_overlay: Label,
+
+    #[nwg_control(flags: "VISIBLE|TAB_STOP|VSCROLL|AUTOVSCROLL")]
+    #[nwg_layout_item(layout: layout, col: 3, col_span: 3, row: 3, row_span: 5)]
+    tb_overlay: TextBox,
+
+    #[nwg_control(text: & t ! ("profiles.btn_new"))]
+    #[nwg_layout_item(layout: layout, col: 3, row: 8)]
+    pub(crate) btn_new: Button,
+
+    #[nwg_control(text: & t ! ("profiles.btn_save"))]
+    #[nwg_layout_item(layout: layout, col: 4, row: 8)]
+    pub(crate) btn_save: Button,
+
+    #[nwg_control(text: & t ! ("profiles.btn_delete"))]
+    #[nwg_layout_item(layout: layout, col: 5, row: 8)]
+    pub(crate) btn_delete: Button,
+
+    #[nwg_control(text: & t ! ("settings.btn_close"))]
+    #[nwg_layout_item(layout: layout2, col: 3, row: 9)]
+    pub(crate) btn_close: Button,
+}
+
+impl SettingsForm {
+    // 更新配置方案列表，并清空编辑框
+    pub(crate) fn update_profiles(&self) {
+        let Some(context) = self.context.get() else {
+            return;
+        };
+
+        let names = get_profiles(context.clone())
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        self.profiles_ui.lb_profiles.set_collection(names);
+        self.on_profile_new();
+    }
+
+    // 列表框选择变动，显示选中的配置方案
+    pub(crate) fn on_profile_selected(&self) {
+        let Some(context) = self.context.get() else {
+            return;
+        };
+        let Some(index) = self.profiles_ui.lb_profiles.selection() else {
+            return;
+        };
+        let Some(profile) = get_profiles(context.clone()).into_iter().nth(index) else {
+            return;
+        };
+
+        let ui = &self.profiles_ui;
+        ui.tb_name.set_text(&profile.name);
+        ui.tb_process_name.set_text(&profile.process_name);
+        ui.tb_class_name.set_text(&profile.class_name);
+        ui.tb_overlay
+            .set_text_unix2dos(&toml::to_string(&profile.overlay).unwrap_or_default());
+        ui.btn_delete.set_enabled(true);
+    }
+
+    // 新建按钮事件，清空编辑框，保存时会添加新的配置方案
+    pub(crate) fn on_profile_new(&self) {
+        let ui = &self.profiles_ui;
+        ui.lb_profiles.set_selection(None);
+        ui.tb_name.set_text("");
+        ui.tb_process_name.set_text("");
+        ui.tb_class_name.set_text("");
+        ui.tb_overlay.set_text("");
+        ui.btn_delete.set_enabled(false);
+    }
+
+    // 保存按钮事件，修改选中的配置方案，没有选中时添加新的配置方案
+    pub(crate) fn on_profile_save(&self) {
+        let Some(context) = self.context.get() else {
+            return;
+        };
+
+        let ui = &self.profiles_ui;
+        let name = ui.tb_name.text().trim().to_string();
+        if name.is_empty() {
+            modal_error_message(&self.window, &t!("profiles.title"), &t!("profiles.no_name"));
+            return;
+        }
+        // 覆盖的配置项必须能合并到当前的配置中，避免方案生效时出错
+        let config = context.get_config_manager().get_config();
+        let overlay = match ui
+            .tb_overlay
+            .text()
+            .parse::<Table>()
+            .map_err(|e| e.to_string())
+            .and_then(|t| merge_config(&config, &t).map(|_| t))
+        {
+            Ok(t) => t,
+            Err(e) => {
+                let info = t!("profiles.invalid_overlay", error = e).to_string();
+                modal_error_message(&self.window, &t!("profiles.title"), &info);
+                return;
+            }
+        };
+
+        let mut profiles = get_profiles(context.clone());
+        let index = ui.lb_profiles.selection();
+        if profiles
+            .iter()
+            .enumerate()
+            .any(|(i, p)| p.name == name && Some(i) != index)
+        {
+            modal_error_message(
+                &self.window,
+                &t!("profiles.title"),
+                &t!("profiles.duplicated"),
+            );
+            return;
+        }
+
+        let profile = ProfileConfig {
+            name,
+            process_name: ui.tb_process_name.text().trim().to_string(),
+            class_name: ui.tb_class_name.text().trim().to_string(),
+            overlay,
+        };
+        match index.and_then(|i| profiles.get_mut(i)) {
+            Some(p) => *p = profile,
+            None => profiles.push(profile),
+        }
+        save_profiles(context.clone(), profiles);
+
+        self.update_profiles();
+    }
+
+    // 删除按钮事件
+    pub(crate) fn on_profile_delete(&self) {
+        let Some(context) = self.context.get() else {
+            return;
+        };
+        let Some(index) = self.profiles_ui.lb_profiles.selection() else {
+            return;
+        };
+
+        let mut profiles = get_profiles(context.clone());
+        if index >= profiles.len() {
+            return;
+        }
+        let info = t!("profiles.confirm_delete", name = profiles[index].name).to_string();
+        let msg_params = MessageParams {
+            title: &t!("profiles.title"),
+            content: &info,
+            buttons: MessageButtons::OkCancel,
+            icons: MessageIcons::Question,
+        };
+        if modal_message(&self.window, &msg_params) == MessageChoice::Cancel {
+            return;
+        }
+
+        profiles.remove(index);
+        save_profiles(context.clone(), profiles);
+
+        self.update_profiles();
+    }
+}
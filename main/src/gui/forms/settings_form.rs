@@ -15,18 +15,26 @@ use crate::{
     bring_window_front,
     commander::keyboard::{combo_keys::ComboKey, keys::Keys},
     configs::{
-        items::{general::Lang, tts::TtsPropertyItem},
-        operations::{get_auto_check_update, get_lang, get_mouse_read_state, get_run_on_startup},
+        items::{
+            general::{KeyboardLayout, Lang},
+            log::LogLevel,
+            tts::TtsPropertyItem,
+        },
+        operations::{
+            get_auto_check_update, get_keyboard_layout, get_lang, get_log_level,
+            get_mouse_read_state, get_run_on_startup,
+        },
     },
     context::{Context, ContextAccessor},
     gui::{
         command::{
             add_desktop_shortcut_cmd, check_update_cmd, export_config_cmd,
-            get_desktop_shortcut_path, import_config_cmd, reset_config_cmd,
-            set_auto_check_update_cmd, set_auto_start_cmd, set_lang_cmd, set_mouse_read_cmd,
-            set_pitch_cmd, set_speed_cmd, set_voice_cmd, set_volume_cmd,
+            get_desktop_shortcut_path, import_config_cmd, log_viewer_cmd, reset_config_cmd,
+            set_auto_check_update_cmd, set_auto_start_cmd, set_keyboard_layout_cmd, set_lang_cmd,
+            set_log_level_cmd, set_mouse_read_cmd, set_pitch_cmd, set_speed_cmd, set_voice_cmd,
+            set_volume_cmd,
         },
-        forms::hotkeys::HotKeysUi,
+        forms::{hotkeys::HotKeysUi, profiles::ProfilesUi},
         utils::set_hook_simple,
     },
     performer::tts::{TtsProperty, VoiceInfo},
@@ -89,6 +97,7 @@ pub struct SettingsForm {
     t ! ("settings.menu_voice_item").to_string(),
     t ! ("settings.menu_hotkeys_item").to_string(),
     t ! ("settings.menu_mouse_item").to_string(),
+    t ! ("settings.menu_profiles_item").to_string(),
     t ! ("settings.menu_advanced_item").to_string(),
     ])]
     #[nwg_layout_item(layout: layout, size: Size{width: D::Points(150.0), height: D::Auto})]
@@ -108,6 +117,9 @@ pub struct SettingsForm {
     #[nwg_control(flags: "BORDER")]
     mouse_frame: Frame,
 
+    #[nwg_control(flags: "BORDER")]
+    profiles_frame: Frame,
+
     #[nwg_control(flags: "BORDER")]
     advanced_frame: Frame,
 
@@ -118,6 +130,7 @@ pub struct SettingsForm {
     (ck_auot_update, OnButtonClick): [SettingsForm::on_auto_check_update(SELF, CTRL)],
     (btn_check_update, OnButtonClick): [SettingsForm::on_check_update],
     (cb_lang, OnComboxBoxSelection): [SettingsForm::on_lang_changed(SELF, CTRL)],
+    (cb_keyboard_layout, OnComboxBoxSelection): [SettingsForm::on_keyboard_layout_changed(SELF, CTRL)],
     (btn_close, OnButtonClick): [SettingsForm::on_save],
     (finish_program_hotkeys_notice, OnNotice): [SettingsForm::on_finish_program_hotkeys_hook],
     (cancel_program_hotkeys_notice, OnNotice): [SettingsForm::on_cancel_program_hotkeys_hook],
@@ -156,11 +169,23 @@ pub struct SettingsForm {
     )]
     mouse_ui: MouseUi,
 
+    #[nwg_partial(parent: profiles_frame)]
+    #[nwg_events(
+    (lb_profiles, OnListBoxSelect): [SettingsForm::on_profile_selected],
+    (btn_new, OnButtonClick): [SettingsForm::on_profile_new],
+    (btn_save, OnButtonClick): [SettingsForm::on_profile_save],
+    (btn_delete, OnButtonClick): [SettingsForm::on_profile_delete],
+    (btn_close, OnButtonClick): [SettingsForm::on_save],
+    )]
+    pub(crate) profiles_ui: ProfilesUi,
+
     #[nwg_partial(parent: advanced_frame)]
     #[nwg_events(
     (btn_import, OnButtonClick): [SettingsForm::on_import],
     (btn_export, OnButtonClick): [SettingsForm::on_export],
     (btn_reset, OnButtonClick): [SettingsForm::on_reset],
+    (cb_log_level, OnComboxBoxSelection): [SettingsForm::on_log_level_changed(SELF, CTRL)],
+    (btn_log_viewer, OnButtonClick): [SettingsForm::on_log_viewer],
     (btn_close, OnButtonClick): [SettingsForm::on_save],
     )]
     advanced_ui: AdvancedUi,
@@ -185,6 +210,7 @@ impl SettingsForm {
             &self.voice_frame,
             &self.hotkeys_frame,
             &self.mouse_frame,
+            &self.profiles_frame,
             &self.advanced_frame,
         ];
 
@@ -307,6 +333,11 @@ impl SettingsForm {
         set_lang_cmd(self.context.get().unwrap().clone(), lang);
     }
 
+    fn on_keyboard_layout_changed(&self, ctrl: &GeneralUi) {
+        let index = ctrl.cb_keyboard_layout.selection().unwrap();
+        set_keyboard_layout_cmd(self.context.get().unwrap().clone(), index);
+    }
+
     fn on_role_changed(&self, ctrl: &VoiceUi) {
         let index = ctrl.cb_role.selection().unwrap();
         let all_voice = self.all_voices.load();
@@ -362,6 +393,15 @@ impl SettingsForm {
         reset_config_cmd(self.context.get().unwrap().clone());
     }
 
+    fn on_log_level_changed(&self, ctrl: &AdvancedUi) {
+        let index = ctrl.cb_log_level.selection().unwrap();
+        set_log_level_cmd(self.context.get().unwrap().clone(), index);
+    }
+
+    fn on_log_viewer(&self) {
+        log_viewer_cmd(self.context.get().unwrap().clone());
+    }
+
     fn on_show_notice(&self) {
         // 更新桌面快捷显示
         let state = match get_desktop_shortcut_path().exists() {
@@ -395,6 +435,11 @@ impl SettingsForm {
         };
         self.general_ui.cb_lang.set_selection(Some(index));
 
+        // 更新键盘布局显示
+        let layout = get_keyboard_layout(self.context.get().unwrap().clone());
+        let index = KeyboardLayout::ALL.iter().position(|l| *l == layout);
+        self.general_ui.cb_keyboard_layout.set_selection(index);
+
         // 更新语音角色框显示
         let format_voice_info = |v: &VoiceInfo| format!("{}_{}", v.engine, v.name);
 
@@ -442,6 +487,14 @@ impl SettingsForm {
         };
         self.mouse_ui.ck_mouse_read.set_check_state(state);
 
+        // 更新配置方案显示
+        self.update_profiles();
+
+        // 更新日志级别显示
+        let level = get_log_level(self.context.get().unwrap().clone());
+        let index = LogLevel::ALL.iter().position(|l| *l == level);
+        self.advanced_ui.cb_log_level.set_selection(index);
+
         bring_window_front!(&self.window);
         self.window.set_size(FORM_SIZE.0, FORM_SIZE.1);
         self.window.set_visible(true);
@@ -520,6 +573,14 @@ pub struct GeneralUi {
     #[nwg_layout_item(layout: layout, col: 2, row: 6)]
     cb_lang: ComboBox<String>,
 
+    #[nwg_control(text: & t ! ("settings.lb_keyboard_layout"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 7)]
+    lb_keyboard_layout: Label,
+
+    #[nwg_control(collection: KeyboardLayout::ALL.iter().map(| l | l.get_name()).collect())]
+    #[nwg_layout_item(layout: layout, col: 2, row: 7)]
+    cb_keyboard_layout: ComboBox<String>,
+
     #[nwg_control(text: & t ! ("settings.btn_close"))]
     #[nwg_layout_item(layout: layout2, col: 3, row: 9)]
     btn_close: Button,
@@ -622,6 +683,18 @@ pub struct AdvancedUi {
     #[nwg_layout_item(layout: layout, col: 1, row: 3)]
     btn_reset: Button,
 
+    #[nwg_control(text: & t ! ("settings.lb_log_level"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 5)]
+    lb_log_level: Label,
+
+    #[nwg_control(collection: LogLevel::ALL.iter().map(| l | l.get_name()).collect())]
+    #[nwg_layout_item(layout: layout, col: 2, row: 5)]
+    cb_log_level: ComboBox<String>,
+
+    #[nwg_control(text: & t ! ("settings.btn_log_viewer"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 6)]
+    btn_log_viewer: Button,
+
     #[nwg_control(text: & t ! ("settings.btn_close"))]
     #[nwg_layout_item(layout: layout2, col: 3, row: 9)]
     btn_close: Button,
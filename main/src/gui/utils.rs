@@ -25,9 +25,6 @@ use std::{
     collections::HashMap,
     env::{args, current_exe},
     error::Error,
-    fs::File,
-    io::{copy, Read, Write},
-    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex, Weak,
@@ -46,7 +43,6 @@ use win_wrap::{
     },
     shell::ShellLink,
 };
-use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
 
 const HELP_URL: &str =
     "https://gitcode.net/mzdk100/rigela/-/blob/dev/main/docs/user/help.md?format=json&viewer=simple";
@@ -203,65 +199,6 @@ pub(crate) async fn confirm_update_exists() -> Result<(), Box<dyn Error>> {
     }
 }
 
-//noinspection RsUnresolvedPath
-/// 备份数据
-pub(crate) fn backup_data(target: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let zip_file_path = Path::new(target.to_str().unwrap());
-    let zip_file = File::create(&zip_file_path)?;
-
-    let mut zip = ZipWriter::new(zip_file);
-    let options = SimpleFileOptions::default().compression_method(CompressionMethod::DEFLATE);
-
-    let mut files_to_compress: Vec<PathBuf> = vec![];
-    let resources_path = get_rigela_program_directory().join("resources");
-    for entry in resources_path.read_dir()? {
-        let path = entry?.path();
-        if path.is_file() {
-            files_to_compress.push(path);
-        }
-    }
-    files_to_compress.push(get_rigela_program_directory().join("config.toml"));
-
-    for file_path in &files_to_compress {
-        let file = File::open(file_path)?;
-        let file_name = file_path.file_name().unwrap().to_str().unwrap();
-
-        zip.start_file(file_name, options)?;
-
-        let mut buffer = Vec::new();
-        copy(&mut file.take(u64::MAX), &mut buffer)?;
-
-        zip.write_all(&buffer)?;
-    }
-
-    zip.finish()?;
-    Ok(())
-}
-
-/// 还原备份数据
-pub(crate) fn restore_data(source: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let zip_file_path = Path::new(source.as_os_str());
-    let zip_file = File::open(zip_file_path)?;
-
-    let mut archive = ZipArchive::new(zip_file)?;
-    let extraction_dir = get_rigela_program_directory();
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let file_name = file.name().to_owned();
-
-        let target_path = match file_name.as_str() {
-            "config.toml" => extraction_dir.join(file_name),
-            _ => extraction_dir.join("resources").join(file_name),
-        };
-
-        let mut output_file = File::create(&target_path)?;
-        copy(&mut file, &mut output_file)?;
-    }
-
-    Ok(())
-}
-
 /**
  * 创建读屏程序的快捷方式。
  * `link_path` 快捷方式的路径（扩展名是.lnk）。
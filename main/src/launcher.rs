@@ -12,6 +12,12 @@
  */
 
 use crate::{
+    automation::serve,
+    cli::CliArgs,
+    configs::{
+        items::{general::GeneralConfig, hotkeys::HotKeysConfig, log::LogConfig},
+        watcher::watch,
+    },
     context::{Context, ContextAccessor},
     ext::window::AccessibleWindowExt,
     performer::sound::SoundArgument::Single,
@@ -23,6 +29,7 @@ use log::{error, info};
 use rigela_utils::{
     killer::wait_until_killed,
     library::{get_rigela_library_path, setup_library},
+    logger::set_log_options,
 };
 use std::sync::{Arc, Weak};
 use tokio::{process::Command, runtime::Runtime};
@@ -36,8 +43,13 @@ pub(crate) struct Launcher {
 impl Launcher {
     /**
     创建一个发射台，通常一个进程只有一个实例。
+    `args` 命令行参数。
     */
-    pub(crate) fn new(work_runtime: Weak<Runtime>, terminator: Weak<Terminator>) -> Self {
+    pub(crate) fn new(
+        work_runtime: Weak<Runtime>,
+        terminator: Weak<Terminator>,
+        args: CliArgs,
+    ) -> Self {
         // 初始化COM线程模型。
         let res = co_initialize_multi_thread();
         if res.is_err() {
@@ -50,11 +62,25 @@ impl Launcher {
         let context = Context::new(
             unsafe { &*work_runtime.as_ptr() },
             terminator.upgrade().unwrap(),
+            args,
         );
         let context = Arc::new(context);
         // 调用上下文对象的应用到每一个组件的方法
         context.apply();
 
+        // 按配置设置日志，命令行指定的日志级别只在启动时覆盖配置，之后修改的配置照常生效
+        let mut options = context
+            .get_config_manager()
+            .get_section::<LogConfig>()
+            .to_options();
+        if let Some(level) = context.get_cli_args().log_level {
+            options.level = level;
+        }
+        set_log_options(&options);
+        context
+            .get_config_manager()
+            .subscribe::<LogConfig>(|c| set_log_options(&c.to_options()));
+
         Self { context }
     }
 
@@ -81,20 +107,46 @@ impl Launcher {
             register_service((&get_ia2_lib_path()).to_str().unwrap()).await;
         });
 
-        // peeper 可以监控远进程中的信息
-        put_peeper();
-        peeper::mount();
-        let ctx = self.context.clone();
-        self.context.get_work_runtime().spawn(async move {
-            ctx.get_peeper_server().run().await;
-        });
+        // peeper 可以监控远进程中的信息，最小模式下不注入
+        let minimal = self.context.get_cli_args().minimal;
+        if !minimal {
+            put_peeper();
+            peeper::mount();
+            let ctx = self.context.clone();
+            self.context.get_work_runtime().spawn(async move {
+                ctx.get_peeper_server().run().await;
+            });
+        }
+
+        // 自动化接口，外部程序可以通过他控制读屏
+        let ctx = Arc::downgrade(&self.context);
+        self.context
+            .get_work_runtime()
+            .spawn(async move { serve(ctx).await });
+
+        // 配置文件被外部修改后自动重新加载
+        let ctx = Arc::downgrade(&self.context);
+        self.context
+            .get_work_runtime()
+            .spawn(async move { watch(ctx).await });
 
         #[cfg(target_arch = "x86_64")]
-        {
-            // 加载32位的主程序代理模块（为了启动速度，此模块可以延迟加载）
+        if !minimal {
+            // proxy32意外退出或卡死时播放错误音效，提示用户vvtts暂时不可用，稍后会自动重启
+            let ctx = Arc::downgrade(&self.context);
+            self.context
+                .get_proxy32process()
+                .add_on_outage_listener(move || {
+                    let ctx2 = ctx.clone();
+                    ctx.get_work_runtime().spawn(async move {
+                        ctx2.get_performer().play_sound(Single("error.wav")).await;
+                    });
+                });
+
+            // 加载32位的主程序代理模块（为了启动速度，此模块可以延迟加载），然后持续监督它的运行状态
             let ctx = self.context.clone();
             self.context.get_work_runtime().spawn(async move {
-                ctx.get_proxy32process().spawn().await;
+                ctx.get_proxy32process().spawn().await.supervise().await;
             });
         }
 
@@ -115,10 +167,39 @@ impl Launcher {
             .run(Arc::downgrade(&self.context))
             .await;
 
-        // 更新自定义热键, 这个调用放在apply里面不会生效
+        // 按照键盘布局和自定义热键更新热键表, 这个调用放在apply里面不会生效
         self.context
             .get_talent_provider()
-            .update_custom_combo_key_map(Arc::downgrade(&self.context));
+            .update_combo_key_map(Arc::downgrade(&self.context));
+
+        let timings = self
+            .context
+            .get_config_manager()
+            .get_section::<HotKeysConfig>()
+            .get_press_timings();
+        self.context
+            .get_commander()
+            .get_keyboard_manager()
+            .set_press_timings(timings);
+
+        // 热键配置改变时重新更新自定义热键和按键的时间阈值
+        let ctx = Arc::downgrade(&self.context);
+        self.context
+            .get_config_manager()
+            .subscribe::<HotKeysConfig>(move |c| {
+                ctx.get_talent_provider().update_combo_key_map(ctx.clone());
+                ctx.get_commander()
+                    .get_keyboard_manager()
+                    .set_press_timings(c.get_press_timings());
+            });
+
+        // 键盘布局改变时重新更新热键表
+        let ctx = Arc::downgrade(&self.context);
+        self.context
+            .get_config_manager()
+            .subscribe::<GeneralConfig>(move |_| {
+                ctx.get_talent_provider().update_combo_key_map(ctx.clone());
+            });
     }
 
     //noinspection RsUnresolvedPath
@@ -34,8 +34,13 @@ i18n!("locale");
 10. talent 能力模块，定义所有能力，这些能力通常绑定到输入设备上，例如（键盘、鼠标、触摸屏等）；
 11. tasks 任务模块，针对一些异步的任务进行管理；
 12. terminator 终结者，用于控制和等待程序结束；
-13. context 上下文环境，可以贯穿整个框架的环境，让每一个模块之间可以互相访问。
+13. context 上下文环境，可以贯穿整个框架的环境，让每一个模块之间可以互相访问；
+14. automation 自动化接口，外部程序可以通过本机的JSON-RPC服务控制读屏；
+15. remote 远程协助，两个读屏之间通过加密的TCP连接共享语音和键盘；
+16. cli 命令行参数的解析。
 */
+mod automation;
+mod cli;
 mod commander;
 mod configs;
 mod context;
@@ -46,27 +51,54 @@ mod impls;
 mod launcher;
 mod navigator;
 mod performer;
+mod remote;
 mod resources;
 mod talent;
 mod tasks;
 mod terminator;
 
-use crate::terminator::Terminator;
+use crate::{
+    cli::{attach_console, CliArgs, USAGE},
+    terminator::Terminator,
+};
 use launcher::Launcher;
 use log::info;
-use rigela_utils::{killer::kill, logger::init_logger};
-use std::sync::{Arc, Weak};
+use rigela_utils::{
+    fs::set_rigela_program_directory,
+    killer::{is_running, kill},
+    logger::init_logger,
+};
+use std::{
+    process::exit,
+    sync::{Arc, Weak},
+};
 use tokio::runtime::Builder;
 use win_wrap::threading::get_current_thread_id;
 
 fn main() {
+    // 解析命令行参数
+    let args = match CliArgs::from_env() {
+        Ok(a) => a,
+        Err(e) => {
+            attach_console();
+            eprintln!("{}\n\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    if args.help {
+        attach_console();
+        println!("{}", USAGE);
+        return;
+    }
+
+    // 命令行指定的数据目录，必须在初始化日志之前设置，因为日志文件也保存在数据目录中
+    if let Some(path) = &args.data_dir {
+        set_rigela_program_directory(path);
+    }
+
     // 初始化日志库
     init_logger(None);
 
-    // 创建一个终结者对象，main方法将使用他异步等待程序退出
-    let terminator = Terminator::new(get_current_thread_id());
-    let terminator = Arc::new(terminator);
-
     // 获取一个工作线程携程运行时，可以把任何耗时的操作任务调度到子线程中
     let work_runtime = Builder::new_multi_thread()
         .worker_threads(2)
@@ -75,8 +107,29 @@ fn main() {
         .unwrap();
     let work_runtime = Arc::new(work_runtime);
 
+    // 通知正在运行的读屏退出，然后结束
+    if args.quit {
+        work_runtime.block_on(kill());
+        return;
+    }
+    // 防止多开，只有指定了替换时才让正在运行的读屏退出
+    if args.replace {
+        work_runtime.block_on(kill());
+    } else if work_runtime.block_on(is_running()) {
+        info!("RigelA is already running, use --replace to restart it.");
+        return;
+    }
+
+    // 创建一个终结者对象，main方法将使用他异步等待程序退出
+    let terminator = Terminator::new(get_current_thread_id());
+    let terminator = Arc::new(terminator);
+
     // 创建发射台
-    let launcher = Launcher::new(Arc::downgrade(&work_runtime), Arc::downgrade(&terminator));
+    let launcher = Launcher::new(
+        Arc::downgrade(&work_runtime),
+        Arc::downgrade(&terminator),
+        args,
+    );
     let launcher = Arc::new(launcher);
     work_runtime.spawn(entry(Arc::downgrade(&launcher)));
 
@@ -88,9 +141,6 @@ fn main() {
 }
 
 async fn entry(launcher: Weak<Launcher>) {
-    // 通知其他的读屏进程退出，防止多开
-    kill().await;
-
     // 使用发射台启动主程序
     info!("Launching RigelA...");
     unsafe { &*launcher.as_ptr() }.launch().await;
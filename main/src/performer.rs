@@ -17,30 +17,46 @@ mod text_processing;
 pub(crate) mod tts;
 
 use crate::{
-    context::Context,
+    configs::items::tts::TtsConfig,
+    context::{Context, ContextAccessor},
     performer::{
         cache::Cache,
         sound::{Sound, SoundArgument},
-        tts::{sapi5::Sapi5Engine, vvtts::VvttsEngine, Tts},
+        tts::{sapi5::Sapi5Engine, Tts},
     },
 };
 use std::{
     sync::{Arc, Weak},
     time::Duration,
 };
-use tokio::{sync::OnceCell, time::sleep};
+use tokio::{
+    sync::{broadcast, OnceCell},
+    time::sleep,
+};
 
 /// 表演者语音信息收集接口。 实现此接口的对象可以调用表演者的speak方法进行输出。
 pub(crate) trait Speakable {
     fn get_sentence(&self) -> String;
 }
 
+/// 表演者的输出，可以被订阅，例如远程协助时转发给领导者。
+#[derive(Clone, Debug)]
+pub(crate) enum PerformerOutput {
+    /// 朗读的文字
+    Speech(String),
+    /// 播放的音效，内容是资源名称
+    Sound(String),
+    /// 停止朗读
+    Stop,
+}
+
 /// 表演者对象结构。 可以进行语音输出或音效提示。
 #[derive(Debug)]
 pub(crate) struct Performer {
     tts: OnceCell<Arc<Tts>>,
     cache: OnceCell<Arc<Cache>>,
     sound: Arc<Sound>,
+    output: broadcast::Sender<PerformerOutput>,
 }
 
 impl Performer {
@@ -50,6 +66,7 @@ impl Performer {
             tts: OnceCell::new().into(),
             cache: OnceCell::new().into(),
             sound: Sound::new().into(),
+            output: broadcast::channel(64).0,
         }
     }
 
@@ -63,13 +80,34 @@ impl Performer {
 
         let tts = Arc::new(Tts::new(context.clone()));
         self.tts.set(tts.clone()).unwrap_or(());
-        tts.put_default_engine(Sapi5Engine::new())
-            .await
-            .add_engine(VvttsEngine::new(context.clone()).await)
-            .await;
+        // 语音的配置改变时（例如在设置中调节语速或者切换了配置方案），在下一次朗读前应用到语音引擎
+        let tts2 = tts.clone();
+        context
+            .get_config_manager()
+            .subscribe::<TtsConfig>(move |c| tts2.set_pending_config(c.clone()));
+        tts.put_default_engine(Sapi5Engine::new()).await;
+        #[cfg(target_arch = "x86")]
+        {
+            use crate::performer::tts::vvtts::VvttsEngine;
+            tts.add_engine(VvttsEngine::new(context.clone()).await)
+                .await;
+        }
         self.cache
             .set(Arc::new(Cache::build(context.clone()).await))
             .unwrap_or(());
+        // 64位进程无法直接加载32位的语音引擎，这些引擎由proxy32托管，最小模式下不启动proxy32。
+        // proxy32可能启动得很慢或者启动失败，所以在后台注册，注册完成之前使用默认的引擎朗读
+        #[cfg(target_arch = "x86_64")]
+        if !context.get_cli_args().minimal {
+            use crate::performer::tts::proxy32::Proxy32Engine;
+            let ctx = context.clone();
+            context.get_work_runtime().spawn(async move {
+                let engines = ctx.get_proxy32process().await.get_engines().await;
+                for info in engines {
+                    tts.add_engine(Proxy32Engine::new(ctx.clone(), info)).await;
+                }
+            });
+        }
     }
 
     /// 获取表演者的TTS对象
@@ -114,6 +152,19 @@ impl Performer {
         return tts.speak(text).await;
     }
 
+    /// 订阅表演者的输出，订阅者处理得太慢时会丢失最早的输出。
+    pub(crate) fn subscribe_output(&self) -> broadcast::Receiver<PerformerOutput> {
+        self.output.subscribe()
+    }
+
+    /**
+     通知订阅者表演者产生了输出，没有订阅者时什么都不做。
+     `output` 输出的内容。
+     */
+    pub(crate) fn emit_output(&self, output: PerformerOutput) {
+        self.output.send(output).unwrap_or(0);
+    }
+
     /// 播放音效
     pub(crate) async fn play_sound(&self, arg: SoundArgument) {
         self.sound.stop_all().await;
@@ -11,7 +11,10 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
-use crate::context::{Context, ContextAccessor};
+use crate::{
+    context::{Context, ContextAccessor},
+    performer::PerformerOutput,
+};
 use rigela_utils::bass::BassChannelOutputStream;
 use std::{
     collections::HashMap,
@@ -57,10 +60,17 @@ impl Sound {
             }
             sleep(Duration::from_millis(100)).await;
         };
+        // 命令行指定了不播放音效
+        if context.get_cli_args().no_sound {
+            return;
+        }
         let res_name = match arg {
             SoundArgument::Single(n) => n,
             SoundArgument::WithFreq(n, _) => n,
         };
+        context
+            .get_performer()
+            .emit_output(PerformerOutput::Sound(res_name.to_string()));
         let lock = self.sound_streams.lock().await;
         let stream = match lock.get(res_name) {
             None => {
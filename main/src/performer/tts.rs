@@ -11,17 +11,20 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod proxy32;
 pub(crate) mod sapi5;
 //noinspection SpellCheckingInspection
+#[cfg(target_arch = "x86")]
 pub(crate) mod vvtts;
 
 use crate::{
     configs::items::tts::{TtsConfig, TtsPropertyItem},
     context::{Context, ContextAccessor},
-    performer::text_processing::transform_single_char,
+    performer::{text_processing::transform_single_char, PerformerOutput},
 };
 use arc_swap::ArcSwapAny;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::{
     collections::HashMap,
     fmt::{Debug, Formatter},
@@ -83,6 +86,8 @@ pub(crate) struct Tts {
     is_cancelled: AtomicBool,
     all_engines: RwLock<HashMap<String, Arc<dyn TtsEngine + Sync + Send>>>,
     all_voices: ArcSwapAny<Arc<Vec<VoiceInfo>>>,
+    // 改变后还没有应用到语音引擎的配置
+    pending_config: Mutex<Option<TtsConfig>>,
     context: Weak<Context>,
 }
 
@@ -94,6 +99,7 @@ impl Tts {
             is_cancelled: false.into(),
             all_engines: HashMap::new().into(),
             all_voices: ArcSwapAny::from(Arc::new(Vec::new())),
+            pending_config: None.into(),
             context,
         }
     }
@@ -106,6 +112,10 @@ impl Tts {
     */
     pub(crate) async fn speak(&self, text: String) -> bool {
         assert!(text.len() > 0);
+        self.context
+            .get_performer()
+            .emit_output(PerformerOutput::Speech(text.clone()));
+        self.sync_config().await;
 
         if let Some(engine) = self.get_engine().await.upgrade() {
             // 这里Chars是迭代器，没有计算，不损耗性能，确保text不为空,unwrap不会失败
@@ -131,7 +141,7 @@ impl Tts {
     }
 
     async fn get_engine(&self) -> Weak<dyn TtsEngine + Sync + Send> {
-        let ttc_cfg = self.context.get_config_manager().get_config().tts_config;
+        let ttc_cfg = self.context.get_config_manager().get_section::<TtsConfig>();
         let engine_name = ttc_cfg.voice.0.clone();
 
         match { self.all_engines.read().get(&engine_name) } {
@@ -155,11 +165,13 @@ impl Tts {
     */
     pub(crate) async fn stop(&self) {
         self.is_cancelled.store(true, Ordering::Release);
+        self.context
+            .get_performer()
+            .emit_output(PerformerOutput::Stop);
         let engine = self
             .context
             .get_config_manager()
-            .get_config()
-            .tts_config
+            .get_section::<TtsConfig>()
             .voice
             .0
             .clone();
@@ -181,6 +193,9 @@ impl Tts {
     */
     pub(crate) async fn stop_all(&self) {
         self.is_cancelled.store(true, Ordering::Release);
+        self.context
+            .get_performer()
+            .emit_output(PerformerOutput::Stop);
         self.all_engines.read().iter().for_each(|(_, engine)| {
             engine.stop();
         });
@@ -195,38 +210,20 @@ impl Tts {
             ValueChange::Decrement => 1,
         };
 
-        let TtsConfig {
-            speed,
-            pitch,
-            volume,
-            voice: (engine, id),
-            item,
-        } = self
-            .context
-            .get_config_manager()
-            .get_config()
-            .tts_config
-            .clone();
-
-        let mut cfg = self
-            .context
-            .get_config_manager()
-            .get_config()
-            .tts_config
-            .clone();
-        match item {
-            TtsPropertyItem::Speed => cfg.speed = set_val(speed),
-            TtsPropertyItem::Pitch => cfg.pitch = set_val(pitch),
-            TtsPropertyItem::Volume => cfg.volume = set_val(volume),
+        let mut cfg = self.context.get_config_manager().get_section::<TtsConfig>();
+        match cfg.item {
+            TtsPropertyItem::Speed => cfg.speed = set_val(cfg.speed),
+            TtsPropertyItem::Pitch => cfg.pitch = set_val(cfg.pitch),
+            TtsPropertyItem::Volume => cfg.volume = set_val(cfg.volume),
             TtsPropertyItem::Voice => {
                 self.stop_all().await;
+                let (engine, id) = cfg.voice.clone();
                 let voice = self.switch_voice(engine, id, value_change).await;
                 cfg.voice = (voice.engine, voice.id)
             }
         };
 
-        self.apply_config(&cfg).await;
-
+        // 新的配置会通过配置订阅在下一次朗读前应用到语音引擎
         let mut root = self.context.get_config_manager().get_config();
         root.tts_config = cfg;
         self.context.get_config_manager().set_config(&root);
@@ -234,12 +231,7 @@ impl Tts {
 
     /// 获取当前TTS属性值
     pub(crate) async fn get_tts_prop_value(&self, item: Option<TtsPropertyItem>) -> TtsProperty {
-        let config = self
-            .context
-            .get_config_manager()
-            .get_config()
-            .tts_config
-            .clone();
+        let config = self.context.get_config_manager().get_section::<TtsConfig>();
 
         match item.map(|x| x).unwrap_or(config.item) {
             TtsPropertyItem::Speed => TtsProperty::Speed(config.speed),
@@ -295,19 +287,30 @@ impl Tts {
                 .write()
                 .insert(engine.get_name(), Arc::new(engine));
         }
-        let cfg = self
-            .context
-            .get_config_manager()
-            .get_config()
-            .tts_config
-            .clone();
+        let cfg = self.context.get_config_manager().get_section::<TtsConfig>();
         self.apply_config(&cfg).await;
 
         self
     }
 
+    /**
+    记录改变后的配置，在下一次朗读前应用到语音引擎，这样紧接着的朗读一定会使用新的配置。
+    `config` 改变后的语音配置。
+    */
+    pub(crate) fn set_pending_config(&self, config: TtsConfig) {
+        *self.pending_config.lock() = Some(config);
+    }
+
+    // 应用还没有应用的配置
+    async fn sync_config(&self) {
+        let config = self.pending_config.lock().take();
+        if let Some(config) = config {
+            self.apply_config(&config).await;
+        }
+    }
+
     // 应用配置到TTS
-    pub(crate) async fn apply_config(&self, config: &TtsConfig) {
+    async fn apply_config(&self, config: &TtsConfig) {
         for (_, engine) in { self.all_engines.read().clone() }.iter() {
             let (engine_name, id) = config.voice.clone();
             if engine.get_name() == engine_name {
@@ -321,12 +324,7 @@ impl Tts {
     }
 
     pub(crate) async fn move_tts_prop(&self, direction: Direction) {
-        let mut cfg = self
-            .context
-            .get_config_manager()
-            .get_config()
-            .tts_config
-            .clone();
+        let mut cfg = self.context.get_config_manager().get_section::<TtsConfig>();
         cfg.item = match direction {
             Direction::Next => match cfg.item {
                 TtsPropertyItem::Speed => TtsPropertyItem::Pitch,
@@ -342,8 +340,6 @@ impl Tts {
             },
         };
 
-        self.apply_config(&cfg).await;
-
         let mut root = self.context.get_config_manager().get_config();
         root.tts_config = cfg;
         self.context.get_config_manager().set_config(&root);
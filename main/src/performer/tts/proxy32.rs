@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    context::{Context, ContextAccessor},
+    performer::tts::TtsEngine,
+};
+use rigela_proxy32::model::{EngineInfo, EngineParams};
+use rigela_utils::bass::BassChannelOutputStream;
+use std::sync::{
+    atomic::{AtomicU32, Ordering::SeqCst},
+    Weak,
+};
+
+/// 托管在proxy32中的32位语音引擎，每个引擎对应一个实例
+pub(crate) struct Proxy32Engine {
+    context: Weak<Context>,
+    info: EngineInfo,
+    // 正在进行的流式合成的id，0表示当前没有合成
+    synth_id: AtomicU32,
+    output_stream: BassChannelOutputStream,
+}
+
+impl Proxy32Engine {
+    /**
+     * 创建一个proxy32引擎的代理。
+     * `context` 读屏框架的上下文环境。
+     * `info` proxy32返回的引擎信息。
+     * */
+    pub(crate) fn new(context: Weak<Context>, info: EngineInfo) -> Self {
+        let output_stream = BassChannelOutputStream::new(info.sample_rate, info.channels);
+        Self {
+            context,
+            info,
+            synth_id: AtomicU32::new(0),
+            output_stream,
+        }
+    }
+
+    async fn set_params(&self, params: EngineParams) {
+        self.context
+            .get_proxy32process()
+            .await
+            .set_params(&self.info.name, &params)
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl TtsEngine for Proxy32Engine {
+    async fn speak(&self, text: &str) {
+        self.output_stream.start();
+        let proxy32 = self.context.get_proxy32process().await;
+        let Some(mut stream) = proxy32.synth_stream(&self.info.name, text).await else {
+            return;
+        };
+        let id = stream.get_id();
+        self.synth_id.store(id, SeqCst);
+        // 边合成边播放，不必等待整段文字合成完毕
+        while let Some(data) = stream.next().await {
+            if self.output_stream.is_stopped() {
+                // 播放已经被停止，通知proxy32不必再合成剩余的部分
+                if self
+                    .synth_id
+                    .compare_exchange(id, 0, SeqCst, SeqCst)
+                    .is_ok()
+                {
+                    proxy32.synth_cancel(id).await;
+                }
+                break;
+            }
+            self.output_stream.put_data(&data);
+        }
+        self.synth_id
+            .compare_exchange(id, 0, SeqCst, SeqCst)
+            .unwrap_or_default();
+    }
+
+    async fn wait(&self) {
+        self.output_stream.wait_until_stopped_or_stalled().await;
+    }
+
+    fn stop(&self) {
+        self.output_stream.stop();
+        let id = self.synth_id.swap(0, SeqCst);
+        if id == 0 {
+            return;
+        }
+        let context = self.context.clone();
+        self.context.get_work_runtime().spawn(async move {
+            context.get_proxy32process().await.synth_cancel(id).await;
+        });
+    }
+
+    fn get_name(&self) -> String {
+        self.info.name.clone()
+    }
+
+    async fn get_all_voices(&self) -> Vec<(String, String)> {
+        self.context
+            .get_proxy32process()
+            .await
+            .get_voices(&self.info.name)
+            .await
+    }
+
+    async fn set_speed(&self, value: i32) {
+        self.set_params(EngineParams {
+            speed: Some(value),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn set_volume(&self, value: i32) {
+        self.set_params(EngineParams {
+            volume: Some(value),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn set_pitch(&self, value: i32) {
+        self.set_params(EngineParams {
+            pitch: Some(value),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn set_voice(&self, id: String) {
+        self.context
+            .get_proxy32process()
+            .await
+            .set_voice(&self.info.name, &id)
+            .await
+    }
+}
+
+unsafe impl Send for Proxy32Engine {}
+
+unsafe impl Sync for Proxy32Engine {}
@@ -0,0 +1,493 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    context::{Context, ContextAccessor},
+    performer::{sound::SoundArgument::Single, PerformerOutput},
+};
+use log::{info, warn};
+use rigela_utils::remote::{
+    handshake, RemoteError, RemoteMessage, RemoteReader, RemoteRole, RemoteWriter,
+};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    mem::size_of,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        LazyLock, Mutex, Weak,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    select,
+    sync::{broadcast::error::RecvError, mpsc},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+    KEYEVENTF_EXTENDEDKEY, KEYEVENTF_KEYUP, VIRTUAL_KEY,
+};
+
+// 领导者连接跟随者的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// 握手失败后跟随者暂停接受连接的初始时间，连续失败时加倍
+const HANDSHAKE_BACKOFF: Duration = Duration::from_secs(1);
+// 握手失败后暂停接受连接的最长时间
+const MAX_HANDSHAKE_BACKOFF: Duration = Duration::from_secs(30);
+// 领导者最多接受的不同音效名称数量
+const MAX_SOUND_NAMES: usize = 128;
+
+/// 启动远程会话的错误
+#[derive(Debug)]
+pub(crate) enum SessionError {
+    /// 没有设置会话密码
+    NoPassword,
+    /// 没有设置跟随者的地址
+    NoAddress,
+    /// 无法监听或者连接
+    Io(String),
+    /// 握手失败
+    Remote(RemoteError),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPassword => write!(f, "The session password is not set."),
+            Self::NoAddress => write!(f, "The follower address is not set."),
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Remote(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SessionState {
+    Idle,
+    /// 作为跟随者等待领导者连接，或者正在被领导者协助
+    Following(JoinHandle<()>),
+    /// 作为领导者协助跟随者，按键通过通道发送给跟随者
+    Leading {
+        task: JoinHandle<()>,
+        keys: mpsc::UnboundedSender<RemoteMessage>,
+    },
+}
+
+/**
+ * 远程协助的会话。
+ * 跟随者（被协助者）监听TCP端口，把自己的语音和音效转发给领导者；领导者（协助者）直接连接跟随者，收听这些输出，并且可以把自己的按键发送给跟随者。
+ * 同一时间只能有一个会话。
+ * */
+#[derive(Debug)]
+pub(crate) struct RemoteSession {
+    state: Mutex<SessionState>,
+    controlling: AtomicBool,
+    // 已经发送给跟随者但还没有松开的按键，虚拟键码对应扫描码和是否是扩展键
+    forwarded: Mutex<HashMap<u32, (u32, bool)>>,
+}
+
+impl RemoteSession {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: SessionState::Idle.into(),
+            controlling: false.into(),
+            forwarded: HashMap::new().into(),
+        }
+    }
+
+    /**
+     * 判断当前是否有会话。
+     * */
+    pub(crate) fn is_active(&self) -> bool {
+        !matches!(*self.state.lock().unwrap(), SessionState::Idle)
+    }
+
+    /**
+     * 结束当前的会话，如果没有会话则返回false。
+     * */
+    pub(crate) fn stop(&self) -> bool {
+        self.controlling.store(false, Ordering::Release);
+        self.forwarded.lock().unwrap().clear();
+        match std::mem::replace(&mut *self.state.lock().unwrap(), SessionState::Idle) {
+            SessionState::Idle => false,
+            SessionState::Following(task) | SessionState::Leading { task, .. } => {
+                task.abort();
+                true
+            }
+        }
+    }
+
+    /**
+     * 作为跟随者开始会话，在配置的端口上等待领导者连接，返回监听的端口。
+     * `context` 读屏框架的上下文环境。
+     * */
+    pub(crate) async fn follow(&self, context: Weak<Context>) -> Result<u16, SessionError> {
+        let config = context.get_config_manager().get_config().remote_config;
+        let password_hash = config.password_hash().ok_or(SessionError::NoPassword)?;
+        self.stop();
+        let listener = TcpListener::bind(("0.0.0.0", config.port))
+            .await
+            .map_err(|e| SessionError::Io(e.to_string()))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| SessionError::Io(e.to_string()))?
+            .port();
+        info!("Waiting for the remote leader on port {}.", port);
+        let ctx = context.clone();
+        let task = context
+            .get_work_runtime()
+            .spawn(async move { run_follower(ctx, listener, password_hash).await });
+        *self.state.lock().unwrap() = SessionState::Following(task);
+        Ok(port)
+    }
+
+    /**
+     * 作为领导者开始会话，连接配置中的跟随者地址。
+     * `context` 读屏框架的上下文环境。
+     * */
+    pub(crate) async fn lead(&self, context: Weak<Context>) -> Result<(), SessionError> {
+        let config = context.get_config_manager().get_config().remote_config;
+        let password_hash = config.password_hash().ok_or(SessionError::NoPassword)?;
+        if config.address.is_empty() {
+            return Err(SessionError::NoAddress);
+        }
+        self.stop();
+        // 地址中没有端口时使用配置的端口
+        let address = match config.address.parse::<SocketAddr>() {
+            Ok(_) => config.address.clone(),
+            Err(_) => format!("{}:{}", config.address, config.port),
+        };
+        let stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(&address)).await {
+            Ok(Ok(s)) => s,
+            Ok(Err(e)) => return Err(SessionError::Io(e.to_string())),
+            Err(e) => return Err(SessionError::Io(e.to_string())),
+        };
+        stream.set_nodelay(true).unwrap_or(());
+        let (reader, writer) = handshake(stream, RemoteRole::Leader, &password_hash)
+            .await
+            .map_err(SessionError::Remote)?;
+        info!("Connected to the remote follower {}.", address);
+
+        let (keys, rx) = mpsc::unbounded_channel();
+        let ctx = context.clone();
+        let task = context
+            .get_work_runtime()
+            .spawn(async move { run_leader(ctx, reader, writer, rx).await });
+        *self.state.lock().unwrap() = SessionState::Leading { task, keys };
+        Ok(())
+    }
+
+    /**
+     * 判断领导者是否正在控制跟随者。
+     * */
+    pub(crate) fn is_controlling(&self) -> bool {
+        self.controlling.load(Ordering::Acquire)
+    }
+
+    /**
+     * 领导者切换是否把按键发送给跟随者，返回切换后的状态；如果当前不是领导者，返回None。
+     * */
+    pub(crate) fn toggle_control(&self) -> Option<bool> {
+        let state = self.state.lock().unwrap();
+        let SessionState::Leading { keys, .. } = &*state else {
+            return None;
+        };
+        let controlling = !self.controlling.load(Ordering::Acquire);
+        self.controlling.store(controlling, Ordering::Release);
+        if !controlling {
+            // 停止控制时松开跟随者上还按着的键，例如切换控制的热键中的修饰键
+            for (vk_code, (scan_code, extended)) in self.forwarded.lock().unwrap().drain() {
+                keys.send(RemoteMessage::Key {
+                    vk_code,
+                    scan_code,
+                    extended,
+                    pressed: false,
+                })
+                .unwrap_or(());
+            }
+        }
+        Some(controlling)
+    }
+
+    /**
+     * 如果领导者正在控制跟随者，则把按键发送给跟随者并返回true，这时本机应该拦截这个按键。
+     * 开始控制之前就按下的键，松开时不发送给跟随者。
+     * `vk_code` 虚拟键码。
+     * `scan_code` 扫描码。
+     * `extended` 是否是扩展键。
+     * `pressed` 是否按下。
+     * */
+    pub(crate) fn forward_key(
+        &self,
+        vk_code: u32,
+        scan_code: u32,
+        extended: bool,
+        pressed: bool,
+    ) -> bool {
+        if !self.is_controlling() {
+            return false;
+        }
+        let state = self.state.lock().unwrap();
+        let SessionState::Leading { keys, .. } = &*state else {
+            return false;
+        };
+        let mut forwarded = self.forwarded.lock().unwrap();
+        if pressed {
+            forwarded.insert(vk_code, (scan_code, extended));
+        } else if forwarded.remove(&vk_code).is_none() {
+            return false;
+        }
+        keys.send(RemoteMessage::Key {
+            vk_code,
+            scan_code,
+            extended,
+            pressed,
+        })
+        .is_ok()
+    }
+
+    // 领导者的连接断开后清理状态，不能中止正在执行清理的任务本身
+    fn on_leader_closed(&self) {
+        self.controlling.store(false, Ordering::Release);
+        self.forwarded.lock().unwrap().clear();
+        *self.state.lock().unwrap() = SessionState::Idle;
+    }
+}
+
+/**
+ * 跟随者的主循环，依次接受领导者的连接，同一时间只服务一个领导者。
+ * 握手失败后暂停一段时间再接受新的连接，连续失败时暂停的时间加倍，减慢在线猜测会话密码的速度。
+ * */
+async fn run_follower(context: Weak<Context>, listener: TcpListener, password_hash: String) {
+    let mut backoff = HANDSHAKE_BACKOFF;
+    loop {
+        let Ok((stream, addr)) = listener.accept().await else {
+            continue;
+        };
+        stream.set_nodelay(true).unwrap_or(());
+        let (reader, writer) = match handshake(stream, RemoteRole::Follower, &password_hash).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("The remote handshake with {} failed. {}", addr, e);
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_HANDSHAKE_BACKOFF);
+                continue;
+            }
+        };
+        backoff = HANDSHAKE_BACKOFF;
+        info!("The remote leader {} is connected.", addr);
+        context
+            .get_performer()
+            .speak(&t!("remote.leader_connected"))
+            .await;
+        serve_leader(context.clone(), reader, writer).await;
+        info!("The remote leader {} is disconnected.", addr);
+        context
+            .get_performer()
+            .speak(&t!("remote.leader_disconnected"))
+            .await;
+    }
+}
+
+/**
+ * 跟随者把表演者的输出转发给领导者，同时执行领导者发来的按键，直到连接断开。
+ * 连接断开或者会话被中止时，松开领导者按下但还没有松开的键，避免修饰键卡在按下的状态。
+ * */
+async fn serve_leader(
+    context: Weak<Context>,
+    mut reader: RemoteReader<TcpStream>,
+    mut writer: RemoteWriter<TcpStream>,
+) {
+    let mut output = context.get_performer().subscribe_output();
+    let forward = async move {
+        loop {
+            let message = match output.recv().await {
+                Ok(PerformerOutput::Speech(text)) => RemoteMessage::Speech(text),
+                Ok(PerformerOutput::Sound(name)) => RemoteMessage::Sound(name),
+                Ok(PerformerOutput::Stop) => RemoteMessage::StopSpeech,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            if writer.send(&message).await.is_err() {
+                break;
+            }
+        }
+    };
+    // 会话任务被中止时不会执行到循环之后，所以在析构时松开按键
+    let mut held = HeldKeys::default();
+    let inject = async {
+        while let Ok(message) = reader.recv().await {
+            if let RemoteMessage::Key {
+                vk_code,
+                scan_code,
+                extended,
+                pressed,
+            } = message
+            {
+                held.inject(vk_code, scan_code, extended, pressed);
+            }
+        }
+    };
+    select! {
+        _ = forward => {},
+        _ = inject => {},
+    }
+}
+
+/**
+ * 跟随者模拟按下但还没有松开的键，虚拟键码对应扫描码和是否是扩展键，析构时全部松开。
+ * */
+#[derive(Default)]
+struct HeldKeys(HashMap<u32, (u32, bool)>);
+
+impl HeldKeys {
+    /**
+     * 模拟一个按键并记录按下的状态。
+     * */
+    fn inject(&mut self, vk_code: u32, scan_code: u32, extended: bool, pressed: bool) {
+        if pressed {
+            self.0.insert(vk_code, (scan_code, extended));
+        } else {
+            self.0.remove(&vk_code);
+        }
+        inject_key(vk_code, scan_code, extended, pressed);
+    }
+}
+
+impl Drop for HeldKeys {
+    fn drop(&mut self) {
+        for (vk_code, (scan_code, extended)) in self.0.drain() {
+            inject_key(vk_code, scan_code, extended, false);
+        }
+    }
+}
+
+/**
+ * 领导者的主循环，播放跟随者的输出，同时把本机的按键发送给跟随者，直到连接断开或者会话结束。
+ * */
+async fn run_leader(
+    context: Weak<Context>,
+    mut reader: RemoteReader<TcpStream>,
+    mut writer: RemoteWriter<TcpStream>,
+    mut keys: mpsc::UnboundedReceiver<RemoteMessage>,
+) {
+    // 跟随者的朗读按收到的顺序排队，由同一个任务依次朗读，朗读会等待完毕，所以不能在接收消息的循环中进行，否则无法及时处理停止消息。
+    // 每段朗读带有收到时的停止次数，停止朗读以后，之前收到但还没有开始的朗读会被丢弃。
+    let stops = AtomicU64::new(0);
+    let (speech_tx, mut speech_rx) = mpsc::unbounded_channel();
+    let ctx = context.clone();
+    let speak = async {
+        while let Some((text, generation)) = speech_rx.recv().await {
+            if generation == stops.load(Ordering::Acquire) {
+                ctx.get_performer().get_tts().speak(text).await;
+            }
+        }
+    };
+    let play = async {
+        while let Ok(message) = reader.recv().await {
+            match message {
+                RemoteMessage::Speech(text) if !text.is_empty() => {
+                    speech_tx
+                        .send((text, stops.load(Ordering::Acquire)))
+                        .unwrap_or(());
+                }
+                RemoteMessage::Sound(name) => {
+                    let Some(name) = intern_sound_name(name) else {
+                        continue;
+                    };
+                    let ctx2 = ctx.clone();
+                    ctx.get_work_runtime().spawn(async move {
+                        ctx2.get_performer().play_sound(Single(name)).await;
+                    });
+                }
+                RemoteMessage::StopSpeech => {
+                    stops.fetch_add(1, Ordering::AcqRel);
+                    ctx.get_performer().get_tts().stop().await;
+                }
+                _ => {}
+            }
+        }
+    };
+    let send = async move {
+        while let Some(message) = keys.recv().await {
+            if writer.send(&message).await.is_err() {
+                break;
+            }
+        }
+    };
+    select! {
+        _ = play => {},
+        _ = speak => {},
+        _ = send => {},
+    }
+    context.get_remote_session().on_leader_closed();
+    context
+        .get_performer()
+        .speak(&t!("remote.follower_disconnected"))
+        .await;
+}
+
+/**
+ * 把跟随者发来的音效名称转换成静态字符串，音效播放器使用静态字符串作为资源名称。
+ * 只接受.wav文件名，并且限制数量，避免对方发来大量不同的名称。
+ * */
+fn intern_sound_name(name: String) -> Option<&'static str> {
+    static NAMES: LazyLock<Mutex<HashMap<String, &'static str>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    if !name.ends_with(".wav") || name.contains(['/', '\\']) || name.contains("..") {
+        return None;
+    }
+    let mut names = NAMES.lock().unwrap();
+    if let Some(n) = names.get(&name) {
+        return Some(n);
+    }
+    if names.len() >= MAX_SOUND_NAMES {
+        return None;
+    }
+    let leaked: &'static str = Box::leak(name.clone().into_boxed_str());
+    names.insert(name, leaked);
+    Some(leaked)
+}
+
+/**
+ * 模拟领导者的按键，模拟的按键同样会经过本机的键盘钩子，所以领导者也可以使用读屏的热键。
+ * */
+fn inject_key(vk_code: u32, scan_code: u32, extended: bool, pressed: bool) {
+    let mut flags = KEYBD_EVENT_FLAGS(0);
+    if extended {
+        flags |= KEYEVENTF_EXTENDEDKEY;
+    }
+    if !pressed {
+        flags |= KEYEVENTF_KEYUP;
+    }
+    let input = INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(vk_code as u16),
+                wScan: scan_code as u16,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    };
+    unsafe {
+        SendInput(&[input], size_of::<INPUT>() as i32);
+    }
+}
@@ -11,26 +11,35 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
+mod layout;
 pub(crate) mod mouse;
 mod navigator;
 mod program;
+mod remote;
 mod tts;
 
 use crate::{
     commander::{keyboard::combo_keys::ComboKey, CommandType},
-    configs::operations::get_hotkeys,
-    context::Context,
+    configs::{
+        items::general::{GeneralConfig, KeyboardLayout},
+        operations::get_hotkeys,
+    },
+    context::{Context, ContextAccessor},
     talent::{
+        layout::get_layout_combo_key,
         mouse::{ClickTalent, ReadMouseTalent, RightClickTalent},
         navigator::{
             ElementColorSetTalent, ElementCurrentLineTalent, ElementCurrentTalent,
-            ElementNextLineTalent, ElementNextTalent, ElementPrevLineTalent, ElementPrevTalent,
-            ModeNextTalent, ModePrevTalent,
+            ElementLineFontTalent, ElementNextLineTalent, ElementNextTalent, ElementPrevLineTalent,
+            ElementPrevTalent, ModeNextTalent, ModePrevTalent,
         },
         program::{
-            CurrentCpuUsageTalent, CurrentDateTalent, CurrentTimeTalent, ExitTalent, HotkeysTalent,
-            PopupMenuTalent, StopTtsOutputTalent, ViewFocusTalent, ViewWindowTitleTalent,
+            CurrentCpuUsageTalent, CurrentDateTalent, CurrentProfileTalent, CurrentTimeTalent,
+            ExitTalent, HotkeysTalent, ImeCandidateModeTalent, InputHelpTalent, NextLogLevelTalent,
+            PeeperDiagnosticsTalent, PopupMenuTalent, StopTtsOutputTalent, ViewFocusTalent,
+            ViewWindowTitleTalent,
         },
+        remote::{RemoteFollowTalent, RemoteLeadTalent, RemoteToggleControlTalent},
         tts::{
             CacheToClipboardTalent, IncreaseTalent, MakeWordCacheCharTalent, NextCacheCharTalent,
             NextPropTalent, PrevCacheCharTalent, PrevPropTalent, ReduceTalent,
@@ -84,6 +93,8 @@ pub(crate) struct TalentProvider {
     talent_ids: Vec<String>,
     // 热键能力映射，加速热键能力获取
     combo_key_map: Mutex<HashMap<ComboKey, String>>,
+    // 当前的键盘布局
+    layout: Mutex<KeyboardLayout>,
 }
 
 macro_rules! make_talents {
@@ -127,6 +138,11 @@ impl TalentProvider {
             ViewFocusTalent,
             ViewWindowTitleTalent,
             StopTtsOutputTalent,
+            PeeperDiagnosticsTalent,
+            ImeCandidateModeTalent,
+            CurrentProfileTalent,
+            NextLogLevelTalent,
+            InputHelpTalent,
             // 导航器能力
             ModePrevTalent,
             ModeNextTalent,
@@ -136,6 +152,7 @@ impl TalentProvider {
             ElementPrevLineTalent,
             ElementNextLineTalent,
             ElementCurrentLineTalent,
+            ElementLineFontTalent,
             ElementColorSetTalent,
             // 语音调节能力
             IncreaseTalent,
@@ -151,13 +168,18 @@ impl TalentProvider {
             // 鼠标能力
             ClickTalent,
             RightClickTalent,
-            ReadMouseTalent
+            ReadMouseTalent,
+            // 远程协助能力
+            RemoteFollowTalent,
+            RemoteLeadTalent,
+            RemoteToggleControlTalent
         );
 
         Self {
             talents,
             talent_ids,
             combo_key_map: Mutex::new(combo_key_map),
+            layout: Mutex::new(KeyboardLayout::Desktop),
         }
     }
 
@@ -177,27 +199,49 @@ impl TalentProvider {
         self.talents.get(id).map(|t| t.clone())
     }
 
-    /// 更新自定义热键的能力映射
-    pub(crate) fn update_custom_combo_key_map(&self, context: Weak<Context>) {
-        let map = get_hotkeys(context);
-        let map2: HashMap<ComboKey, String>;
-        {
-            map2 = self
-                .combo_key_map
-                .lock()
-                .unwrap()
-                .iter()
-                .map(|(k, v)| {
-                    if map.contains_key(v) {
-                        (map.get(v).unwrap().clone(), v.clone())
-                    } else {
-                        (k.clone(), v.clone())
-                    }
-                })
-                .collect();
-        }
+    /**
+     * 获取能力在当前键盘布局中的默认热键，不包括用户自定义的热键。
+     * `id` 能力的ID。
+     * */
+    pub(crate) fn get_default_combo_key(&self, id: &str) -> Option<ComboKey> {
+        let layout = *self.layout.lock().unwrap();
+        get_layout_combo_key(layout, id)
+            .or_else(|| self.talents.get(id).and_then(|t| t.get_combo_key()))
+    }
+
+    /**
+     * 获取能力当前生效的热键，用户自定义的热键优先，其次是键盘布局中的默认热键。
+     * `id` 能力的ID。
+     * */
+    pub(crate) fn get_combo_key_by_id(&self, id: &str) -> Option<ComboKey> {
+        self.combo_key_map
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, v)| v.as_str() == id)
+            .map(|(k, _)| *k)
+    }
+
+    /// 根据键盘布局和自定义热键重新生成热键能力映射
+    pub(crate) fn update_combo_key_map(&self, context: Weak<Context>) {
+        *self.layout.lock().unwrap() = context
+            .get_config_manager()
+            .get_section::<GeneralConfig>()
+            .keyboard_layout;
+        let custom = get_hotkeys(context);
+        let map: HashMap<ComboKey, String> = self
+            .talent_ids
+            .iter()
+            .filter_map(|id| {
+                let key = custom
+                    .get(id)
+                    .cloned()
+                    .or_else(|| self.get_default_combo_key(id))?;
+                Some((key, id.clone()))
+            })
+            .collect();
 
-        *self.combo_key_map.lock().unwrap() = map2;
+        *self.combo_key_map.lock().unwrap() = map;
     }
 
     /// 通过热键获取能力
@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) 2023. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    combo_key,
+    commander::keyboard::{
+        combo_keys::{ComboKey, State},
+        keys::Keys::*,
+        modify_keys::ModifierKeys,
+    },
+    configs::items::general::KeyboardLayout,
+};
+
+/**
+ * 获取能力在键盘布局中的默认热键，布局没有单独定义这个能力时返回None，使用能力自己的默认热键。
+ * 笔记本布局按照小键盘的位置使用RigelA+Shift加字母键，U、I、O对应小键盘的7、8、9，J、K、L对应4、5、6。
+ * `layout` 键盘布局。
+ * `id` 能力的ID。
+ * */
+pub(crate) fn get_layout_combo_key(layout: KeyboardLayout, id: &str) -> Option<ComboKey> {
+    if layout != KeyboardLayout::Laptop {
+        return None;
+    }
+    let key = match id {
+        "element_prev" => combo_key!("RigelA_Shift", VkU),
+        "element_current" => combo_key!("RigelA_Shift", VkI),
+        "element_color_set" => combo_key!("RigelA_Shift", VkI, double),
+        "element_next" => combo_key!("RigelA_Shift", VkO),
+        "element_prev_line" => combo_key!("RigelA_Shift", VkJ),
+        "element_current_line" => combo_key!("RigelA_Shift", VkK),
+        "element_line_font" => combo_key!("RigelA_Shift", VkK, double),
+        "element_next_line" => combo_key!("RigelA_Shift", VkL),
+        "mode_prev" => combo_key!("RigelA_Shift", VkY),
+        "mode_next" => combo_key!("RigelA_Shift", VkP),
+        "click" => combo_key!("RigelA_Shift", VkM),
+        "right_click" => combo_key!("RigelA_Shift", VkN),
+        _ => return None,
+    };
+    Some(key)
+}
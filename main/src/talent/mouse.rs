@@ -18,12 +18,15 @@ use crate::{
         keys::Keys::*,
         modify_keys::ModifierKeys,
     },
-    configs::operations::apply_mouse_config,
+    configs::operations::{apply_mouse_config, get_mouse_read_state},
     context::{Context, ContextAccessor},
 };
 use rigela_macros::talent;
 use std::sync::Weak;
-use win_wrap::input::{click, get_cur_mouse_point, right_click};
+use win_wrap::{
+    common::get_foreground_window,
+    input::{click, get_cur_mouse_point, right_click},
+};
 
 #[talent(doc = String::from("鼠标单击"), key = combo_key!(VkNumPadDiv))]
 async fn click(context: Weak<Context>) {
@@ -44,11 +47,7 @@ async fn right_click(context: Weak<Context>) {
 
 #[talent(doc = String::from("鼠标朗读"), key = combo_key!("RigelA", VkM))]
 async fn read_mouse(context: Weak<Context>) {
-    let is_read = !context
-        .get_config_manager()
-        .get_config()
-        .mouse_config
-        .is_read;
+    let is_read = !get_mouse_read_state(context.clone());
     apply_mouse_config(context.clone(), is_read);
     let state = match is_read {
         true => t!("mouse.state_on"),
@@ -81,7 +80,15 @@ pub(crate) fn mouse_read(context: Weak<Context>, x: i32, y: i32) {
         .element_from_point(x, y)
         .unwrap();
     let ctx = context.clone();
-    context
-        .get_work_runtime()
-        .spawn(async move { ctx.get_performer().speak(&ele).await });
+    context.get_work_runtime().spawn(async move {
+        if ele.get_name().is_empty() {
+            // 没有无障碍名称的元素（例如自绘的控件），尝试从离屏显示模型中获取鼠标下方的文字
+            let root = get_foreground_window().0 as isize;
+            let text = ctx.get_event_core().display_model.get_text_at(root, x, y);
+            if let Some(text) = text {
+                return ctx.get_performer().speak(&text).await;
+            }
+        }
+        ctx.get_performer().speak(&ele).await
+    });
 }
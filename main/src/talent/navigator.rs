@@ -24,7 +24,9 @@ use crate::{
     performer::sound::SoundArgument::Single,
 };
 use rigela_macros::talent;
+use rigela_utils::color::get_nearest_color_name;
 use std::sync::Weak;
+use win_wrap::common::get_foreground_window;
 
 const WAVE: &str = "boundary.wav";
 
@@ -64,17 +66,65 @@ async fn element_current(context: Weak<Context>) {
 
 #[talent(doc = t ! ("navigator.element_prev_line_doc").to_string(), key = combo_key ! (VkNumPad4))]
 async fn element_prev_line(context: Weak<Context>) {
-    context.get_performer().play_sound(Single(WAVE)).await;
+    review_line(context, -1).await;
 }
 
 #[talent(doc = t ! ("navigator.element_next_line_doc").to_string(), key = combo_key ! (VkNumPad6))]
 async fn element_next_line(context: Weak<Context>) {
-    context.get_performer().play_sound(Single(WAVE)).await;
+    review_line(context, 1).await;
 }
 
 #[talent(doc = t ! ("navigator.element_current_line_doc").to_string(), key = combo_key ! (VkNumPad5))]
 async fn element_current_line(context: Weak<Context>) {
-    context.get_performer().play_sound(Single(WAVE)).await;
+    review_line(context, 0).await;
+}
+
+#[talent(doc = t ! ("navigator.element_line_font_doc").to_string(), key = combo_key ! (VkNumPad5, double))]
+async fn element_line_font(context: Weak<Context>) {
+    let root = get_foreground_window().0 as isize;
+    let Some(line) = context
+        .get_event_core()
+        .display_model
+        .move_review_line(root, 0)
+    else {
+        context.get_performer().play_sound(Single(WAVE)).await;
+        return;
+    };
+    let color = get_nearest_color_name(
+        (line.color & 0xff) as u8,
+        (line.color >> 8 & 0xff) as u8,
+        (line.color >> 16 & 0xff) as u8,
+    );
+    context
+        .get_performer()
+        .speak(&t!(
+            "navigator.element_line_font",
+            name = line.font_name,
+            size = line.font_size.abs(),
+            color = color
+        ))
+        .await;
+}
+
+/**
+ * 在离屏显示模型中按行浏览前台窗口，这适用于那些不支持MSAA和UIA，但使用GDI绘制文字的程序。
+ * `context` 读屏框架的上下文环境。
+ * `diff` 移动的行数，0表示朗读当前行。
+ * */
+async fn review_line(context: Weak<Context>, diff: i32) {
+    let root = get_foreground_window().0 as isize;
+    match context
+        .get_event_core()
+        .display_model
+        .move_review_line(root, diff)
+    {
+        Some(line) => {
+            context.get_performer().speak(&line.text).await;
+        }
+        None => {
+            context.get_performer().play_sound(Single(WAVE)).await;
+        }
+    }
 }
 
 #[talent(doc = t ! ("navigator.mode_next_doc").to_string(), key = combo_key ! (VkAdd))]
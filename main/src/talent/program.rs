@@ -16,6 +16,10 @@ use crate::{
     commander::keyboard::{
         combo_keys::ComboKey, combo_keys::State, keys::Keys::*, modify_keys::ModifierKeys,
     },
+    configs::{
+        items::ime::CandidateReadMode,
+        operations::{get_log_level, save_log_level},
+    },
     context::{Context, ContextAccessor},
     ext::window::AccessibleWindowExt,
     performer::{sound::SoundArgument::Single, Speakable},
@@ -120,3 +124,65 @@ async fn view_focus(context: Weak<Context>) {
 async fn stop_tts_output(context: Weak<Context>) {
     context.get_performer().get_tts().stop_all().await;
 }
+
+#[talent(doc = t ! ("program.peeper_diagnostics_doc").to_string(), key = combo_key ! ("RigelA", VkF11))]
+async fn peeper_diagnostics(context: Weak<Context>) {
+    let peeper = context.get_peeper_server();
+    // 详细的信息输出到日志中，朗读时只报告摘要
+    peeper.log_clients().await;
+    let clients = peeper.get_clients().await;
+    let events: u64 = clients.iter().map(|i| i.get_total_events()).sum();
+    let dropped: u64 = clients.iter().map(|i| i.dropped_events).sum();
+    let msg = t!(
+        "program.peeper_diagnostics",
+        count = clients.len(),
+        events = events,
+        dropped = dropped,
+        incompatible = peeper.get_incompatible_client_count()
+    );
+    context.get_performer().speak(&msg).await;
+}
+
+#[talent(doc = t ! ("program.ime_candidate_mode_doc").to_string(), key = combo_key ! ("RigelA", VkI))]
+async fn ime_candidate_mode(context: Weak<Context>) {
+    let mut config = context.get_config_manager().get_config();
+    config.ime_config.candidate_mode = match config.ime_config.candidate_mode {
+        CandidateReadMode::Selected => CandidateReadMode::Page,
+        CandidateReadMode::Page => CandidateReadMode::Selected,
+    };
+    context.get_config_manager().set_config(&config);
+    let text = match config.ime_config.candidate_mode {
+        CandidateReadMode::Selected => t!("program.ime_candidate_selected"),
+        CandidateReadMode::Page => t!("program.ime_candidate_page"),
+    };
+    context.get_performer().speak(&text).await;
+}
+
+#[talent(doc = t ! ("program.current_profile_doc").to_string(), key = combo_key ! ("RigelA", VkP))]
+async fn current_profile(context: Weak<Context>) {
+    let text = match context.get_config_manager().get_profile_name() {
+        Some(name) => t!("program.current_profile", name = name),
+        None => t!("program.no_profile"),
+    };
+    context.get_performer().speak(&text).await;
+}
+
+#[talent(doc = t ! ("program.next_log_level_doc").to_string(), key = combo_key ! ("RigelA", VkL))]
+async fn next_log_level(context: Weak<Context>) {
+    let level = get_log_level(context.clone()).next();
+    save_log_level(context.clone(), level);
+    let text = t!("program.log_level", value = level.get_name());
+    context.get_performer().speak(&text).await;
+}
+
+#[talent(doc = t ! ("program.input_help_doc").to_string(), key = combo_key ! ("RigelA", Vk1))]
+async fn input_help(context: Weak<Context>) {
+    let mng = context.get_commander().get_keyboard_manager();
+    let enabled = !mng.is_help_mode();
+    mng.set_help_mode(enabled);
+    let text = match enabled {
+        true => t!("program.input_help_on"),
+        false => t!("program.input_help_off"),
+    };
+    context.get_performer().speak(&text).await;
+}
@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    combo_key,
+    commander::keyboard::{
+        combo_keys::ComboKey, combo_keys::State, keys::Keys::*, modify_keys::ModifierKeys,
+    },
+    context::{Context, ContextAccessor},
+    remote::SessionError,
+};
+use log::error;
+use rigela_macros::talent;
+use rigela_utils::remote::RemoteError;
+use rust_i18n;
+use std::sync::Weak;
+
+/**
+ * 把启动会话的错误转换成给用户的提示。
+ * */
+fn describe_error(e: &SessionError) -> String {
+    match e {
+        SessionError::NoPassword => t!("remote.no_password").to_string(),
+        SessionError::NoAddress => t!("remote.no_address").to_string(),
+        SessionError::Remote(RemoteError::AuthFailed | RemoteError::Incompatible(_)) => {
+            t!("remote.auth_failed").to_string()
+        }
+        _ => t!("remote.connect_failed").to_string(),
+    }
+}
+
+#[talent(doc = t ! ("remote.follow_doc").to_string(), key = combo_key ! ("RigelA", VkF8))]
+async fn remote_follow(context: Weak<Context>) {
+    let session = context.get_remote_session();
+    if session.stop() {
+        context.get_performer().speak(&t!("remote.stopped")).await;
+        return;
+    }
+    let msg = match session.follow(context.clone()).await {
+        Ok(port) => t!("remote.following", port = port).to_string(),
+        Err(e) => {
+            error!("Can't start the remote session. {}", e);
+            describe_error(&e)
+        }
+    };
+    context.get_performer().speak(&msg).await;
+}
+
+#[talent(doc = t ! ("remote.lead_doc").to_string(), key = combo_key ! ("RigelA", VkF9))]
+async fn remote_lead(context: Weak<Context>) {
+    let session = context.get_remote_session();
+    if session.stop() {
+        context.get_performer().speak(&t!("remote.stopped")).await;
+        return;
+    }
+    let msg = match session.lead(context.clone()).await {
+        Ok(_) => t!("remote.connected").to_string(),
+        Err(e) => {
+            error!("Can't connect to the remote follower. {}", e);
+            describe_error(&e)
+        }
+    };
+    context.get_performer().speak(&msg).await;
+}
+
+#[talent(doc = t ! ("remote.toggle_control_doc").to_string(), key = combo_key ! ("RigelA", VkF10))]
+async fn remote_toggle_control(context: Weak<Context>) {
+    let msg = match context.get_remote_session().toggle_control() {
+        Some(true) => t!("remote.control_on"),
+        Some(false) => t!("remote.control_off"),
+        None => t!("remote.not_leading"),
+    };
+    context.get_performer().speak(&msg).await;
+}
@@ -12,27 +12,89 @@
  */
 
 use crate::{
-    model::{PeeperData, PeeperPacket},
+    model::{PeeperData, PeeperHandshake, PeeperPacket, PEEPER_PROTOCOL_VERSION},
     utils::get_pipe_name,
 };
-use log::{error, trace};
-use parking_lot::Mutex;
-use rigela_utils::pipe::PipeStream;
+use log::{error, trace, warn};
+use rigela_utils::pipe::{connect, PipeFormat, PipeStream, PipeStreamError, Transport};
 use std::{
+    process,
     sync::{Arc, OnceLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
-    net::windows::named_pipe::{ClientOptions, NamedPipeClient},
     runtime::{Builder, Runtime},
+    sync::Mutex,
 };
 
+// 连接失败后第一次重试前的等待时间
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(100);
+// 连接失败后重试的最长等待时间，每次失败等待时间加倍，但不会超过这个值
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(5);
+
+/**
+ * 重新连接的退避策略，连续失败时等待时间成倍增长，但有上限。
+ * */
+#[derive(Debug)]
+struct Backoff {
+    delay: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self {
+            delay: RECONNECT_DELAY_MIN,
+            retry_at: None,
+        }
+    }
+
+    /**
+     * 判断现在是否可以尝试连接。
+     * `now` 当前时间。
+     * */
+    fn is_ready(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|t| now >= t)
+    }
+
+    /**
+     * 记录一次连接失败，下一次尝试要等待的时间会加倍。
+     * `now` 当前时间。
+     * */
+    fn fail(&mut self, now: Instant) {
+        self.retry_at = Some(now + self.delay);
+        self.delay = (self.delay * 2).min(RECONNECT_DELAY_MAX);
+    }
+
+    /**
+     * 记录一次连接成功，重置等待时间。
+     * */
+    fn reset(&mut self) {
+        self.delay = RECONNECT_DELAY_MIN;
+        self.retry_at = None;
+    }
+}
+
+enum ConnectError {
+    // 服务端的协议版本与本客户端不同，参数是服务端的版本
+    Rejected(u32),
+    Failed(String),
+}
+
+// 到服务端的连接状态
+struct Connection {
+    stream: Option<PipeStream<PeeperPacket, Transport>>,
+    backoff: Backoff,
+    // 被服务端拒绝后不再尝试连接，通常是因为遗留了旧版本的dll
+    rejected: bool,
+}
+
 /**
 peeper的client运行在远进程中。
 */
 pub(crate) struct PeeperClient {
     module: String,
-    sender: Arc<OnceLock<Mutex<PipeStream<PeeperPacket, NamedPipeClient>>>>,
+    connection: Arc<Mutex<Connection>>,
     rt: OnceLock<Runtime>,
 }
 
@@ -44,7 +106,11 @@ impl PeeperClient {
         trace!("New pipe connection.");
         let self_ = Self {
             module,
-            sender: OnceLock::new().into(),
+            connection: Arc::new(Mutex::new(Connection {
+                stream: None,
+                backoff: Backoff::new(),
+                rejected: false,
+            })),
             rt: OnceLock::new(),
         };
         let rt = Builder::new_multi_thread()
@@ -61,14 +127,84 @@ impl PeeperClient {
         self_
     }
 
-    fn get_stream() -> PipeStream<PeeperPacket, NamedPipeClient> {
-        match ClientOptions::new().open(get_pipe_name()) {
-            Ok(p) => PipeStream::new(p),
-            Err(e) => {
-                error!("{}", e);
-                return Self::get_stream();
+    /**
+     * 连接到服务端并完成握手，只尝试一次。
+     * `module` 当前进程的模块名称。
+     * */
+    async fn connect_server(
+        module: &str,
+    ) -> Result<PipeStream<PeeperPacket, Transport>, ConnectError> {
+        let p = connect(&get_pipe_name())
+            .await
+            .map_err(|e| ConnectError::Failed(e.to_string()))?;
+        let mut stream = PipeStream::<PeeperHandshake, _>::new(p);
+        match stream.handshake(PipeFormat::preferred()).await {
+            Ok(_) => {}
+            Err(PipeStreamError::Incompatible(v)) => return Err(ConnectError::Rejected(v)),
+            Err(e) => return Err(ConnectError::Failed(e.to_string())),
+        }
+        let hello = PeeperHandshake::Hello {
+            version: PEEPER_PROTOCOL_VERSION,
+            pid: process::id(),
+            module: module.to_string(),
+        };
+        if let Err(e) = stream.send(&hello).await {
+            return Err(ConnectError::Failed(e.to_string()));
+        }
+        match stream.recv().await {
+            Ok(PeeperHandshake::Accepted) => Ok(stream.cast()),
+            Ok(PeeperHandshake::Rejected(v)) => Err(ConnectError::Rejected(v)),
+            Ok(x) => Err(ConnectError::Failed(format!(
+                "Unexpected handshake message {:?}.",
+                x
+            ))),
+            Err(e) => Err(ConnectError::Failed(e.to_string())),
+        }
+    }
+
+    /**
+     * 把数据包发送到服务端，如果还没有连接则先连接。
+     * 连接失败后会按照退避策略等待，等待期间的数据包会被丢弃，而不会阻塞后续的数据包。
+     * */
+    async fn send(module: &str, connection: &Mutex<Connection>, packet: &PeeperPacket) {
+        let mut connection = connection.lock().await;
+        if connection.rejected {
+            return;
+        }
+        if connection.stream.is_none() {
+            let now = Instant::now();
+            if !connection.backoff.is_ready(now) {
+                trace!("The peeper server is unavailable, the packet is dropped.");
+                return;
+            }
+            match Self::connect_server(module).await {
+                Ok(s) => {
+                    connection.backoff.reset();
+                    connection.stream = Some(s);
+                }
+                Err(ConnectError::Rejected(v)) => {
+                    warn!(
+                        "The peeper server rejected this client (protocol version {}, expected {}), please restart the program to update the dll.",
+                        PEEPER_PROTOCOL_VERSION, v
+                    );
+                    connection.rejected = true;
+                    return;
+                }
+                Err(ConnectError::Failed(e)) => {
+                    error!("Can't connect to the peeper server. {}", e);
+                    connection.backoff.fail(now);
+                    return;
+                }
             }
         }
+        let Some(stream) = connection.stream.as_mut() else {
+            return;
+        };
+        if let Err(e) = stream.send(packet).await {
+            // 服务端可能已经重启，丢弃当前连接，下一个数据包会重新连接
+            error!("{}", e);
+            connection.stream = None;
+        }
     }
 
     /**
@@ -80,14 +216,11 @@ impl PeeperClient {
             name: self.module.clone(),
             data,
         };
-        let sender = self.sender.clone();
+        let module = self.module.clone();
+        let connection = self.connection.clone();
         if let Some(rt) = self.rt.get() {
             rt.spawn(async move {
-                let sender = sender.get_or_init(|| Self::get_stream().into());
-                let mut sender = sender.lock();
-                if let Err(e) = sender.send(&packet).await {
-                    error!("{}", e);
-                }
+                Self::send(&module, &connection, &packet).await;
             });
         }
     }
@@ -116,3 +249,27 @@ impl PeeperClient {
         }
     }
 }
+
+#[cfg(test)]
+mod test_client {
+    use crate::client::{Backoff, RECONNECT_DELAY_MAX, RECONNECT_DELAY_MIN};
+    use std::time::Instant;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+        assert!(backoff.is_ready(now));
+        backoff.fail(now);
+        assert!(!backoff.is_ready(now));
+        assert!(backoff.is_ready(now + RECONNECT_DELAY_MIN));
+        // 等待时间成倍增长，但不会超过上限
+        for _ in 0..20 {
+            backoff.fail(now);
+        }
+        assert!(!backoff.is_ready(now + RECONNECT_DELAY_MAX / 2));
+        assert!(backoff.is_ready(now + RECONNECT_DELAY_MAX));
+        backoff.reset();
+        assert!(backoff.is_ready(now));
+    }
+}
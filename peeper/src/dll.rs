@@ -13,8 +13,12 @@
 
 use crate::{
     client::PeeperClient,
-    handler::{on_ime, on_input_char},
-    wm, HOOK_INIT, HOOK_UNINIT,
+    gdi,
+    handler::{
+        on_command, on_ime, on_ime_composition, on_ime_end_composition, on_input_char, on_set_text,
+    },
+    model::{GdiText, PeeperData},
+    wm, HOOK_GDI, HOOK_INIT, HOOK_UNINIT,
 };
 use log::debug;
 use std::{ffi::c_void, sync::RwLock};
@@ -25,12 +29,17 @@ use win_wrap::{
     },
     ext::LParamExt,
     hook::CwpStruct,
-    input::{WM_CHAR, WM_IME_NOTIFY},
+    input::{WM_CHAR, WM_IME_COMPOSITION, WM_IME_ENDCOMPOSITION, WM_IME_NOTIFY},
     message::{register_window_message, MSG},
 };
+use windows::Win32::{
+    Foundation::HWND,
+    Graphics::Gdi::{RedrawWindow, HRGN, RDW_ALLCHILDREN, RDW_INVALIDATE},
+    UI::WindowsAndMessaging::{WM_COMMAND, WM_SETTEXT},
+};
 
 macro_rules! handle_event {
-    ($name:ident, $($arg:expr),*) => {{
+    ($name:ident $(, $arg:expr)*) => {{
         activate_transaction();
         let lock = CLIENT.read().unwrap();
         $name(lock.as_ref().unwrap(), $($arg),*);
@@ -51,7 +60,41 @@ fn activate_transaction() {
     };
 }
 
+/**
+ * 开启或关闭GDI文字输出钩子，钩子只在主进程要求时开启。
+ * 开启后让窗口重绘，这样离屏显示模型可以马上得到窗口中已经显示的文字。
+ * `h_wnd` 收到消息的窗口。
+ * `enabled` 是否开启。
+ * */
+fn set_gdi_hooks(h_wnd: HWND, enabled: bool) {
+    if !enabled {
+        gdi::uninstall();
+        return;
+    }
+    gdi::install(on_gdi_text);
+    unsafe {
+        let _ = RedrawWindow(
+            h_wnd,
+            None,
+            HRGN::default(),
+            RDW_INVALIDATE | RDW_ALLCHILDREN,
+        );
+    }
+}
+
+/**
+ * 处理GDI输出的文字，此函数在GDI钩子的发送线程中被调用，不会阻塞程序的绘图。
+ * `data` 输出的文字。
+ * */
+fn on_gdi_text(data: GdiText) {
+    let lock = CLIENT.read().unwrap();
+    if let Some(client) = lock.as_ref() {
+        client.push(PeeperData::GdiText(data));
+    }
+}
+
 fn deactivate_transaction() {
+    gdi::uninstall();
     let mut lock = CLIENT.write().unwrap();
     if let Some(c) = lock.as_mut() {
         c.quit();
@@ -83,9 +126,19 @@ unsafe extern "system" fn hook_proc_call_wnd_proc(
     } else if wm!(HOOK_UNINIT) == msg.message {
         // 主进程发来的卸载命令
         deactivate_transaction();
+    } else if wm!(HOOK_GDI) == msg.message {
+        // 主进程要求开启或关闭GDI钩子，通常是因为窗口成为了前台窗口或者不再是前台窗口
+        activate_transaction();
+        set_gdi_hooks(msg.hwnd, msg.wParam.0 != 0);
     } else {
         match msg.message {
             WM_IME_NOTIFY => handle_event!(on_ime, msg.hwnd, msg.wParam, msg.lParam),
+            // 组字消息是输入法通过send_message发送给窗口的，所以只需要在窗口过程钩子中处理
+            WM_IME_COMPOSITION => handle_event!(on_ime_composition, msg.hwnd, msg.lParam),
+            WM_IME_ENDCOMPOSITION => handle_event!(on_ime_end_composition),
+            // 传统窗口修改文字时不会发出无障碍事件，只能通过这些消息得知
+            WM_SETTEXT => handle_event!(on_set_text, msg.hwnd, msg.lParam),
+            WM_COMMAND => handle_event!(on_command, msg.wParam, msg.lParam),
             _ => {}
         }
     }
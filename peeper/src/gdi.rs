@@ -0,0 +1,468 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::model::{GdiText, TextRect};
+use log::error;
+use retour::GenericDetour;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    mem::{size_of, transmute},
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Mutex, OnceLock, RwLock,
+    },
+    thread::{spawn, JoinHandle},
+};
+use win_wrap::common::{get_proc_address, load_library};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{CloseHandle, BOOL, HANDLE, POINT, RECT, SIZE},
+        Graphics::Gdi::{
+            ClientToScreen, GetCurrentPositionEx, GetTextAlign, GetTextColor,
+            GetTextExtentPoint32W, GetTextFaceW, GetTextMetricsW, LPtoDP, WindowFromDC,
+            DRAW_TEXT_FORMAT, DT_CALCRECT, ETO_GLYPH_INDEX, ETO_OPAQUE, ETO_OPTIONS, HDC,
+            TA_BASELINE, TA_BOTTOM, TA_CENTER, TA_RIGHT, TA_UPDATECP, TEXTMETRICW,
+        },
+        System::{
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD,
+                THREADENTRY32,
+            },
+            Threading::{
+                GetCurrentProcessId, GetCurrentThreadId, OpenThread, ResumeThread, SuspendThread,
+                THREAD_SUSPEND_RESUME,
+            },
+        },
+        UI::WindowsAndMessaging::{GetAncestor, IsWindowVisible, GA_ROOT},
+    },
+};
+
+type TextOutWFn = unsafe extern "system" fn(HDC, i32, i32, PCWSTR, i32) -> BOOL;
+type ExtTextOutWFn = unsafe extern "system" fn(
+    HDC,
+    i32,
+    i32,
+    ETO_OPTIONS,
+    *const RECT,
+    PCWSTR,
+    u32,
+    *const i32,
+) -> BOOL;
+type DrawTextWFn = unsafe extern "system" fn(HDC, PCWSTR, i32, *mut RECT, DRAW_TEXT_FORMAT) -> i32;
+
+// 字体名称的最大长度（LF_FACESIZE）
+const FACE_NAME_SIZE: usize = 32;
+// 最多记录的文字区域数量，超出后清空重新记录
+const MAX_LAST_TEXTS: usize = 4096;
+// 等待发送的文字输出的最大数量，队列满时丢弃新的输出，不能让程序的绘图等待
+const QUEUE_SIZE: usize = 1024;
+
+/// GDI文字输出函数的钩子
+struct GdiHooks {
+    text_out: GenericDetour<TextOutWFn>,
+    ext_text_out: GenericDetour<ExtTextOutWFn>,
+    draw_text: GenericDetour<DrawTextWFn>,
+}
+
+/// 钩子中收集到的文字输出，由发送线程转换成GdiText
+struct RawText {
+    h_wnd: isize,
+    root: isize,
+    rect: TextRect,
+    text: Vec<u16>,
+    face: [u16; FACE_NAME_SIZE],
+    face_len: usize,
+    font_size: i32,
+    color: u32,
+}
+
+/// 把文字输出从钩子转交给发送线程的队列
+struct TextQueue {
+    sender: SyncSender<RawText>,
+    worker: JoinHandle<()>,
+}
+
+// 钩子只创建一次，卸载时只是禁用，因为目标函数的代码已经被修改，重复创建会出错
+static HOOKS: OnceLock<Option<GdiHooks>> = OnceLock::new();
+// 钩子开启期间的文字输出队列
+static QUEUE: RwLock<Option<TextQueue>> = RwLock::new(None);
+// 每个区域最后一次发送的文字，程序重绘时会反复输出相同的文字，这些不需要再次发送
+static LAST_TEXTS: Mutex<Option<HashMap<(isize, TextRect), String>>> = Mutex::new(None);
+
+/// 当前线程处理钩子的状态
+#[derive(Clone, Copy, PartialEq)]
+enum HookState {
+    /// 没有在处理钩子
+    Idle,
+    /// 正在处理钩子，内部再次调用的文字输出函数不需要重复报告
+    Busy,
+    /// 正在执行DrawTextW，`reported`表示内部调用的ExtTextOutW是否已经报告了文字，他的位置比DrawTextW的矩形更准确
+    DrawText { reported: bool },
+}
+
+thread_local! {
+    static STATE: Cell<HookState> = const { Cell::new(HookState::Idle) };
+}
+
+/**
+ * 创建GDI钩子。
+ * */
+fn create_hooks() -> Option<GdiHooks> {
+    let gdi32 = load_library("gdi32.dll").ok()?;
+    let user32 = load_library("user32.dll").ok()?;
+    unsafe {
+        let text_out: TextOutWFn = transmute(get_proc_address(gdi32, "TextOutW")?);
+        let ext_text_out: ExtTextOutWFn = transmute(get_proc_address(gdi32, "ExtTextOutW")?);
+        let draw_text: DrawTextWFn = transmute(get_proc_address(user32, "DrawTextW")?);
+        let hooks = GdiHooks {
+            text_out: GenericDetour::new(text_out, hook_text_out).ok()?,
+            ext_text_out: GenericDetour::new(ext_text_out, hook_ext_text_out).ok()?,
+            draw_text: GenericDetour::new(draw_text, hook_draw_text).ok()?,
+        };
+        Some(hooks)
+    }
+}
+
+/**
+ * 当前进程中被暂停的其他线程，析构时恢复它们。
+ * 开启或关闭钩子需要修改目标函数开头的指令，其他线程可能正在执行这些函数，暂停它们以后再修改，才不会执行到修改了一半的指令。
+ * 被暂停的线程可能持有堆或者其他的锁，所以暂停期间只能修改指令，不能分配内存或者输出日志。
+ * */
+struct SuspendedThreads(Vec<HANDLE>);
+
+impl SuspendedThreads {
+    fn new() -> Self {
+        let mut threads = vec![];
+        unsafe {
+            let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) else {
+                return Self(threads);
+            };
+            let (pid, tid) = (GetCurrentProcessId(), GetCurrentThreadId());
+            let mut entry = THREADENTRY32 {
+                dwSize: size_of::<THREADENTRY32>() as u32,
+                ..Default::default()
+            };
+            let mut next = Thread32First(snapshot, &mut entry);
+            while next.is_ok() {
+                if entry.th32OwnerProcessID == pid && entry.th32ThreadID != tid {
+                    if let Ok(h) = OpenThread(THREAD_SUSPEND_RESUME, false, entry.th32ThreadID) {
+                        threads.push(h);
+                    }
+                }
+                next = Thread32Next(snapshot, &mut entry);
+            }
+            let _ = CloseHandle(snapshot);
+        }
+        // 先打开所有的线程再暂停，打开线程的时候需要分配内存
+        threads.retain(|h| unsafe {
+            if SuspendThread(*h) == u32::MAX {
+                // 线程可能已经退出
+                let _ = CloseHandle(*h);
+                return false;
+            }
+            true
+        });
+        Self(threads)
+    }
+}
+
+impl Drop for SuspendedThreads {
+    fn drop(&mut self) {
+        for h in self.0.drain(..) {
+            unsafe {
+                ResumeThread(h);
+                let _ = CloseHandle(h);
+            }
+        }
+    }
+}
+
+/**
+ * 安装GDI文字输出钩子。
+ * 钩子函数运行在程序的绘图路径上，只把文字放入有界的队列就返回，由单独的线程转换并交给`sink`。
+ * `sink` 接收文字输出的函数，他在发送线程中被调用。
+ * */
+pub(crate) fn install(sink: fn(GdiText)) {
+    let Some(hooks) = HOOKS.get_or_init(create_hooks) else {
+        error!("Can't create the gdi hooks.");
+        return;
+    };
+    {
+        let mut queue = QUEUE.write().unwrap();
+        if queue.is_none() {
+            let (sender, receiver) = sync_channel(QUEUE_SIZE);
+            let worker = spawn(move || send_texts(receiver, sink));
+            *queue = Some(TextQueue { sender, worker });
+        }
+    }
+    let res = unsafe {
+        let _suspended = SuspendedThreads::new();
+        hooks
+            .text_out
+            .enable()
+            .and_then(|_| hooks.ext_text_out.enable())
+            .and_then(|_| hooks.draw_text.enable())
+    };
+    if let Err(e) = res {
+        error!("Can't enable the gdi hooks. {}", e);
+    }
+}
+
+/**
+ * 卸载GDI文字输出钩子，等待发送线程处理完队列中的文字后退出。
+ * */
+pub(crate) fn uninstall() {
+    let Some(Some(hooks)) = HOOKS.get() else {
+        return;
+    };
+    unsafe {
+        let _suspended = SuspendedThreads::new();
+        hooks.text_out.disable().unwrap_or(());
+        hooks.ext_text_out.disable().unwrap_or(());
+        hooks.draw_text.disable().unwrap_or(());
+    }
+    // 丢弃发送端以后，发送线程处理完剩余的文字就会退出
+    if let Some(queue) = QUEUE.write().unwrap().take() {
+        drop(queue.sender);
+        queue.worker.join().unwrap_or(());
+    }
+    LAST_TEXTS.lock().unwrap().take();
+}
+
+/**
+ * 发送线程，把钩子收集到的文字转换成GdiText并交给`sink`，直到队列被关闭。
+ * `receiver` 队列的接收端。
+ * `sink` 接收文字输出的函数。
+ * */
+fn send_texts(receiver: Receiver<RawText>, sink: fn(GdiText)) {
+    while let Ok(raw) = receiver.recv() {
+        let text = String::from_utf16_lossy(&raw.text);
+        if !is_changed(raw.h_wnd, raw.rect, &text) {
+            continue;
+        }
+        sink(GdiText {
+            h_wnd: raw.h_wnd,
+            root: raw.root,
+            text,
+            rect: raw.rect,
+            font_name: String::from_utf16_lossy(&raw.face[..raw.face_len]),
+            font_size: raw.font_size,
+            color: raw.color,
+        });
+    }
+}
+
+fn get_hooks() -> &'static GdiHooks {
+    // 钩子函数只有在钩子创建成功之后才会被调用
+    HOOKS.get().unwrap().as_ref().unwrap()
+}
+
+/**
+ * 读取指定长度的字符串，长度为负数时表示以空字符结尾。
+ * */
+unsafe fn read_text(text: PCWSTR, len: i32) -> Vec<u16> {
+    if text.is_null() {
+        return vec![];
+    }
+    if len < 0 {
+        return text.as_wide().to_vec();
+    }
+    std::slice::from_raw_parts(text.0, len as usize).to_vec()
+}
+
+/**
+ * 计算TextOutW和ExtTextOutW输出的文字所占的区域（逻辑坐标），需要考虑文字的对齐方式。
+ * */
+unsafe fn get_text_out_rect(hdc: HDC, x: i32, y: i32, text: &[u16]) -> RECT {
+    let align = GetTextAlign(hdc);
+    let (mut x, mut y) = (x, y);
+    if align.0 & TA_UPDATECP.0 != 0 {
+        // 使用当前位置，忽略传入的坐标
+        let mut point = POINT::default();
+        GetCurrentPositionEx(hdc, &mut point);
+        (x, y) = (point.x, point.y);
+    }
+    let mut size = SIZE::default();
+    GetTextExtentPoint32W(hdc, text, &mut size);
+    let mut metric = TEXTMETRICW::default();
+    GetTextMetricsW(hdc, &mut metric);
+    let left = if align.0 & TA_CENTER.0 == TA_CENTER.0 {
+        x - size.cx / 2
+    } else if align.0 & TA_RIGHT.0 == TA_RIGHT.0 {
+        x - size.cx
+    } else {
+        x
+    };
+    let top = if align.0 & TA_BASELINE.0 == TA_BASELINE.0 {
+        y - metric.tmAscent
+    } else if align.0 & TA_BOTTOM.0 == TA_BOTTOM.0 {
+        y - size.cy
+    } else {
+        y
+    };
+    RECT {
+        left,
+        top,
+        right: left + size.cx,
+        bottom: top + size.cy,
+    }
+}
+
+/**
+ * 报告一段输出的文字，只报告可见窗口中的文字，内存设备上下文（例如双缓冲绘图）无法得知最终的位置，所以被忽略。
+ * 设备上下文只在钩子中有效，所以位置和字体在这里获取，之后放入队列立即返回，队列已满时丢弃这段文字。
+ * `hdc` 设备上下文。
+ * `rect` 文字所在的区域（逻辑坐标）。
+ * `text` 文字。
+ * */
+unsafe fn report(hdc: HDC, rect: RECT, text: Vec<u16>) {
+    let h_wnd = WindowFromDC(hdc);
+    if h_wnd.is_invalid() {
+        return;
+    }
+    let root = GetAncestor(h_wnd, GA_ROOT);
+    if !IsWindowVisible(root).as_bool() {
+        return;
+    }
+    let queue = QUEUE.read().unwrap();
+    let Some(queue) = queue.as_ref() else {
+        return;
+    };
+
+    let mut points = [
+        POINT {
+            x: rect.left,
+            y: rect.top,
+        },
+        POINT {
+            x: rect.right,
+            y: rect.bottom,
+        },
+    ];
+    LPtoDP(hdc, &mut points);
+    for p in points.iter_mut() {
+        ClientToScreen(h_wnd, p);
+    }
+    // 某些映射模式下y轴是向上的，需要重新排列
+    let rect = TextRect {
+        left: points[0].x.min(points[1].x),
+        top: points[0].y.min(points[1].y),
+        right: points[0].x.max(points[1].x),
+        bottom: points[0].y.max(points[1].y),
+    };
+
+    let mut face = [0u16; FACE_NAME_SIZE];
+    let len = GetTextFaceW(hdc, Some(&mut face));
+    let mut metric = TEXTMETRICW::default();
+    GetTextMetricsW(hdc, &mut metric);
+    let raw = RawText {
+        h_wnd: h_wnd.0 as isize,
+        root: root.0 as isize,
+        rect,
+        text,
+        face,
+        // 返回的长度包括结尾的空字符
+        face_len: (len.max(1) as usize - 1).min(FACE_NAME_SIZE),
+        font_size: metric.tmHeight,
+        color: GetTextColor(hdc).0,
+    };
+    queue.sender.try_send(raw).unwrap_or(());
+}
+
+/**
+ * 判断一个区域的文字是否和上次发送的不同。
+ * */
+fn is_changed(h_wnd: isize, rect: TextRect, text: &str) -> bool {
+    let mut lock = LAST_TEXTS.lock().unwrap();
+    let map = lock.get_or_insert_with(HashMap::new);
+    let key = (h_wnd, rect);
+    if map.get(&key).is_some_and(|i| i == text) {
+        return false;
+    }
+    if map.len() >= MAX_LAST_TEXTS {
+        map.clear();
+    }
+    map.insert(key, text.to_string());
+    true
+}
+
+unsafe extern "system" fn hook_text_out(hdc: HDC, x: i32, y: i32, text: PCWSTR, len: i32) -> BOOL {
+    if STATE.get() != HookState::Idle {
+        return get_hooks().text_out.call(hdc, x, y, text, len);
+    }
+    STATE.set(HookState::Busy);
+    let chars = read_text(text, len);
+    report(hdc, get_text_out_rect(hdc, x, y, &chars), chars);
+    let res = get_hooks().text_out.call(hdc, x, y, text, len);
+    STATE.set(HookState::Idle);
+    res
+}
+
+unsafe extern "system" fn hook_ext_text_out(
+    hdc: HDC,
+    x: i32,
+    y: i32,
+    options: ETO_OPTIONS,
+    rect: *const RECT,
+    text: PCWSTR,
+    len: u32,
+    dx: *const i32,
+) -> BOOL {
+    let state = STATE.get();
+    // 字形索引不是字符，无法还原成文字
+    if state == HookState::Busy || options.0 & ETO_GLYPH_INDEX.0 != 0 {
+        return get_hooks()
+            .ext_text_out
+            .call(hdc, x, y, options, rect, text, len, dx);
+    }
+    STATE.set(HookState::Busy);
+    let chars = read_text(text, len as i32);
+    if !chars.is_empty() {
+        report(hdc, get_text_out_rect(hdc, x, y, &chars), chars);
+    } else if options.0 & ETO_OPAQUE.0 != 0 && !rect.is_null() {
+        // 没有文字，只是用背景色填充矩形，这通常是在擦除原来的文字
+        report(hdc, *rect, chars);
+    }
+    let res = get_hooks()
+        .ext_text_out
+        .call(hdc, x, y, options, rect, text, len, dx);
+    STATE.set(match state {
+        HookState::DrawText { .. } => HookState::DrawText { reported: true },
+        _ => HookState::Idle,
+    });
+    res
+}
+
+unsafe extern "system" fn hook_draw_text(
+    hdc: HDC,
+    text: PCWSTR,
+    len: i32,
+    rect: *mut RECT,
+    format: DRAW_TEXT_FORMAT,
+) -> i32 {
+    // DT_CALCRECT只是计算文字的大小，并不会输出
+    if STATE.get() != HookState::Idle || format.0 & DT_CALCRECT.0 != 0 || rect.is_null() {
+        return get_hooks().draw_text.call(hdc, text, len, rect, format);
+    }
+    STATE.set(HookState::DrawText { reported: false });
+    let area = *rect;
+    let res = get_hooks().draw_text.call(hdc, text, len, rect, format);
+    if STATE.get() == (HookState::DrawText { reported: false }) {
+        // 内部没有调用ExtTextOutW，只能使用整个矩形作为文字的区域
+        report(hdc, area, read_text(text, len));
+    }
+    STATE.set(HookState::Idle);
+    res
+}
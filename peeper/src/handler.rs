@@ -13,16 +13,36 @@
 
 use crate::{
     client::PeeperClient,
-    model::{CandidateList, PeeperData},
+    model::{CandidateList, PeeperData, WindowText},
+};
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CStr},
+    sync::Mutex,
 };
 use win_wrap::{
-    common::{HWND, LPARAM, WPARAM},
+    common::{get_class_name, get_foreground_window, get_window_text, HWND, LPARAM, WPARAM},
     input::{
-        imm_get_candidate_list, imm_get_context, imm_get_conversion_status, imm_release_context,
-        IMN_CHANGECANDIDATE, IMN_SETCONVERSIONMODE,
+        get_focus, imm_get_candidate_list, imm_get_context, imm_get_conversion_status,
+        imm_release_context, HIMC, IMN_CHANGECANDIDATE, IMN_CLOSECANDIDATE, IMN_OPENCANDIDATE,
+        IMN_SETCONVERSIONMODE,
+    },
+};
+use windows::Win32::{
+    Globalization::{MultiByteToWideChar, CP_ACP, MULTI_BYTE_TO_WIDE_CHAR_FLAGS},
+    UI::{
+        Input::Ime::{ImmGetCompositionStringW, GCS_COMPSTR},
+        WindowsAndMessaging::{GetAncestor, IsWindowUnicode, IsWindowVisible, EN_CHANGE, GA_ROOT},
     },
 };
 
+// 最后一次发送的组字串，用于避免重复发送相同的内容
+static LAST_COMPOSITION: Mutex<String> = Mutex::new(String::new());
+// 每个窗口最后一次发送的文字，用于避免重复发送相同的内容（很多程序会用定时器反复设置相同的状态文字）
+static LAST_WINDOW_TEXTS: Mutex<Option<HashMap<isize, String>>> = Mutex::new(None);
+// 最多记录的窗口数量，超出后清空重新记录，防止窗口不断创建和销毁时占用过多内存
+const MAX_WINDOW_TEXTS: usize = 64;
+
 //noinspection SpellCheckingInspection
 /**
  * 处理输入法通知。
@@ -51,6 +71,7 @@ pub(crate) fn on_ime(
             let cand = CandidateList {
                 selection: list.dwSelection,
                 page_start: list.dwPageStart,
+                page_size: list.dwPageSize,
                 list: text_list,
             };
             client.push(PeeperData::ImeCandidateList(cand));
@@ -71,10 +92,78 @@ pub(crate) fn on_ime(
             }
             imm_release_context(h_wnd, h_imc);
         }
+        IMN_OPENCANDIDATE => client.push(PeeperData::ImeCandidateWindow(true)),
+        IMN_CLOSECANDIDATE => client.push(PeeperData::ImeCandidateWindow(false)),
         _ => {}
     }
 }
 
+/**
+ * 获取输入上下文中的组字串。
+ * `h_imc` 输入上下文的句柄。
+ * */
+fn get_composition_string(h_imc: HIMC) -> String {
+    unsafe {
+        // 返回的是字节数
+        let len = ImmGetCompositionStringW(h_imc, GCS_COMPSTR, None, 0);
+        if len < 1 {
+            return String::new();
+        }
+        let mut buf = vec![0u16; len as usize / 2];
+        ImmGetCompositionStringW(
+            h_imc,
+            GCS_COMPSTR,
+            Some(buf.as_mut_ptr() as *mut _),
+            len as u32,
+        );
+        String::from_utf16_lossy(&buf)
+    }
+}
+
+/**
+ * 发送组字串，如果与上次发送的内容相同则忽略。
+ * `client` peeper客户端对象。
+ * `composition` 组字串。
+ * */
+fn push_composition(client: &PeeperClient, composition: String) {
+    let mut last = LAST_COMPOSITION.lock().unwrap();
+    if *last == composition {
+        return;
+    }
+    *last = composition.clone();
+    drop(last);
+    client.push(PeeperData::ImeComposition(composition));
+}
+
+//noinspection SpellCheckingInspection
+/**
+ * 处理输入法组字消息（WM_IME_COMPOSITION）。
+ * `client` peeper客户端对象。
+ * `h_wnd` 窗口句柄。
+ * `flags` 组字的变化标志，对应GCS_开头的常量。
+ * */
+pub(crate) fn on_ime_composition(client: &PeeperClient, h_wnd: HWND, flags: LPARAM) {
+    if flags.0 as u32 & GCS_COMPSTR.0 == 0 {
+        // 组字串没有改变，例如只是光标位置变化
+        return;
+    }
+    let h_imc = imm_get_context(h_wnd);
+    if h_imc.is_invalid() {
+        return;
+    }
+    let composition = get_composition_string(h_imc);
+    imm_release_context(h_wnd, h_imc);
+    push_composition(client, composition);
+}
+
+/**
+ * 处理输入法组字结束消息（WM_IME_ENDCOMPOSITION）。
+ * `client` peeper客户端对象。
+ * */
+pub(crate) fn on_ime_end_composition(client: &PeeperClient) {
+    push_composition(client, String::new());
+}
+
 /**
  * 处理输入字符。
  * `client` peeper客户端对象。
@@ -83,3 +172,104 @@ pub(crate) fn on_ime(
 pub(crate) fn on_input_char(client: &PeeperClient, character: WPARAM) {
     client.push(PeeperData::InputChar(character.0 as u16))
 }
+
+/**
+ * 判断窗口的文字变化是否值得关注。只关注前台窗口中可见的子窗口（例如状态栏和静态文本），顶层窗口的标题变化已经有无障碍事件可以处理。
+ * `h_wnd` 窗口句柄。
+ * */
+fn is_watched_window(h_wnd: HWND) -> bool {
+    unsafe {
+        let root = GetAncestor(h_wnd, GA_ROOT);
+        root != h_wnd && root == get_foreground_window() && IsWindowVisible(h_wnd).as_bool()
+    }
+}
+
+/**
+ * 读取WM_SETTEXT消息携带的文字，对于非unicode窗口，文字使用的是系统默认的ANSI代码页。
+ * `h_wnd` 窗口句柄。
+ * `text` 消息的lParam参数，指向以空字符结尾的字符串。
+ * */
+fn read_text_param(h_wnd: HWND, text: LPARAM) -> String {
+    if text.0 == 0 {
+        return String::new();
+    }
+    unsafe {
+        if IsWindowUnicode(h_wnd).as_bool() {
+            let mut len = 0;
+            let ptr = text.0 as *const u16;
+            while *ptr.add(len) != 0 {
+                len += 1;
+            }
+            return String::from_utf16_lossy(std::slice::from_raw_parts(ptr, len));
+        }
+        let bytes = CStr::from_ptr(text.0 as *const c_char).to_bytes();
+        let flags = MULTI_BYTE_TO_WIDE_CHAR_FLAGS(0);
+        let len = MultiByteToWideChar(CP_ACP, flags, bytes, None);
+        if len < 1 {
+            return String::new();
+        }
+        let mut buf = vec![0u16; len as usize];
+        MultiByteToWideChar(CP_ACP, flags, bytes, Some(&mut buf));
+        String::from_utf16_lossy(&buf)
+    }
+}
+
+/**
+ * 如果窗口的文字和上次发送的不同，则构造要发送的数据。
+ * `h_wnd` 窗口句柄。
+ * `text` 窗口新的文字。
+ * */
+fn make_window_text(h_wnd: HWND, text: String) -> Option<WindowText> {
+    let mut lock = LAST_WINDOW_TEXTS.lock().unwrap();
+    let map = lock.get_or_insert_with(HashMap::new);
+    let key = h_wnd.0 as isize;
+    if map.get(&key) == Some(&text) {
+        return None;
+    }
+    if map.len() >= MAX_WINDOW_TEXTS {
+        map.clear();
+    }
+    map.insert(key, text.clone());
+    drop(lock);
+    Some(WindowText {
+        h_wnd: key,
+        class_name: get_class_name(h_wnd),
+        text,
+    })
+}
+
+/**
+ * 处理设置窗口文字的消息（WM_SETTEXT）。
+ * `client` peeper客户端对象。
+ * `h_wnd` 窗口句柄。
+ * `text` 指向新文字的指针。
+ * */
+pub(crate) fn on_set_text(client: &PeeperClient, h_wnd: HWND, text: LPARAM) {
+    if !is_watched_window(h_wnd) {
+        return;
+    }
+    if let Some(data) = make_window_text(h_wnd, read_text_param(h_wnd, text)) {
+        client.push(PeeperData::WindowTextChanged(data));
+    }
+}
+
+//noinspection SpellCheckingInspection
+/**
+ * 处理命令消息（WM_COMMAND），目前只关心编辑框的内容改变通知（EN_CHANGE）。
+ * `client` peeper客户端对象。
+ * `command` 高16位是通知代码。
+ * `h_wnd` 发出通知的控件的窗口句柄。
+ * */
+pub(crate) fn on_command(client: &PeeperClient, command: WPARAM, h_wnd: LPARAM) {
+    if (command.0 >> 16) as u32 & 0xffff != EN_CHANGE || h_wnd.0 == 0 {
+        return;
+    }
+    let h_wnd = HWND(h_wnd.0 as _);
+    // 有键盘焦点的编辑框是用户自己在输入，输入的字符已经有其他事件处理
+    if h_wnd == get_focus() || !is_watched_window(h_wnd) {
+        return;
+    }
+    if let Some(data) = make_window_text(h_wnd, get_window_text(h_wnd)) {
+        client.push(PeeperData::EditChanged(data));
+    }
+}
@@ -15,11 +15,15 @@
 mod client;
 #[cfg(feature = "dll")]
 mod dll;
+#[cfg(feature = "dll")]
+mod gdi;
 #[cfg(feature = "handler")]
 mod handler;
 #[cfg(feature = "model")]
 pub mod model;
 #[cfg(feature = "server")]
+pub mod registry;
+#[cfg(feature = "server")]
 pub mod server;
 #[cfg(feature = "utils")]
 mod utils;
@@ -37,7 +41,8 @@ use std::{
 #[cfg(not(feature = "dll"))]
 use win_wrap::{
     common::{
-        get_proc_address, load_library, set_windows_hook_ex, unhook_windows_hook_ex, LPARAM, WPARAM,
+        get_proc_address, load_library, set_windows_hook_ex, unhook_windows_hook_ex, HWND, LPARAM,
+        WPARAM,
     },
     ext::FarProcExt,
     hook::{HOOK_TYPE_CALL_WND_PROC, HOOK_TYPE_GET_MESSAGE},
@@ -58,6 +63,9 @@ static HOOK_INIT: OnceLock<u32> = OnceLock::new();
 // 此字段保存一个自定义的窗口消息值并在所有进程中都需要使用，用于在主进程中通知所有远进程钩子将要被卸载，这能确保所有远进程收到通知并处理后主进程才能进行下一步操作
 static HOOK_UNINIT: OnceLock<u32> = OnceLock::new();
 
+// 此字段保存一个自定义的窗口消息值并在所有进程中都需要使用，用于在主进程中通知窗口所在的进程开启（wParam不为0）或关闭GDI钩子
+static HOOK_GDI: OnceLock<u32> = OnceLock::new();
+
 /**
  启动亏叹气，这会把当前模块作为dll注入到远进程中，这是通过set_windows_hook机制实现的。
  为什么选择使用windows hook的方法注入呢？这是因为很多安全防护软件会监控读屏的行为，如果使用create_remote_thread的方法，很容易被拦截，而windows hook机制是通过系统这一个媒介来完成dll注入，防护软件一般无能为力。
@@ -153,6 +161,29 @@ pub fn mount() {
     });
 }
 
+/**
+ 开启或关闭窗口所在进程的GDI文字输出钩子。
+ GDI钩子会拦截进程中所有的文字输出，开销较大，所以注入时不会安装，只在需要离屏显示模型的窗口（通常是前台窗口）所在的进程中开启。
+ 消息在单独的线程中发送，不会阻塞调用者。
+ `h_wnd` 目标窗口。
+ `enabled` 是否开启。
+ */
+#[cfg(not(feature = "dll"))]
+pub fn set_gdi_hooks(h_wnd: HWND, enabled: bool) {
+    // 窗口句柄不能在线程之间传递，只传递它的值
+    let h_wnd = h_wnd.0 as isize;
+    thread::spawn(move || {
+        send_message_timeout(
+            HWND(h_wnd as _),
+            wm!(HOOK_GDI),
+            WPARAM(enabled as usize),
+            LPARAM::default(),
+            SMTO_ABORTIFHUNG,
+            1000,
+        );
+    });
+}
+
 /** 停止亏叹气。 */
 #[cfg(not(feature = "dll"))]
 pub fn unmount() {
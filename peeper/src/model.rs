@@ -13,6 +13,27 @@
 
 use serde::{Deserialize, Serialize};
 
+/// peeper业务协议的版本号，每次修改PeeperData或PeeperPacket的布局时都必须增加
+pub(crate) const PEEPER_PROTOCOL_VERSION: u32 = 4;
+
+/**
+ * peeper业务协议的握手消息，在管道握手之后、发送任何PeeperPacket之前交换。
+ * 注意：此类型用于识别旧版本的dll，他的布局永远不能改变，只能在末尾增加新的变体。
+ * */
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) enum PeeperHandshake {
+    /// 客户端发起握手，包含客户端的协议版本、进程id和模块名称
+    Hello {
+        version: u32,
+        pid: u32,
+        module: String,
+    },
+    /// 服务端接受了客户端
+    Accepted,
+    /// 服务端拒绝了客户端，参数是服务端的协议版本
+    Rejected(u32),
+}
+
 #[derive(Deserialize, Serialize)]
 pub(crate) struct PeeperPacket {
     pub(crate) name: String,
@@ -23,9 +44,56 @@ pub(crate) struct PeeperPacket {
 pub struct CandidateList {
     pub selection: u32,
     pub page_start: u32,
+    /// 每一页的候选数量，0表示输入法没有提供
+    pub page_size: u32,
     pub list: Vec<String>,
 }
 
+/**
+ * 传统窗口（例如状态栏、静态文本和编辑框）的文字，这些窗口修改文字时通常不会发出任何无障碍事件。
+ * */
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WindowText {
+    /// 窗口句柄的值
+    pub h_wnd: isize,
+    /// 窗口的类名
+    pub class_name: String,
+    /// 窗口新的文字
+    pub text: String,
+}
+
+/**
+ * 屏幕上的矩形区域，使用屏幕坐标。
+ * */
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TextRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+/**
+ * 程序通过GDI接口（TextOutW/ExtTextOutW/DrawTextW）输出的一段文字。
+ * */
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GdiText {
+    /// 绘制文字的窗口句柄的值
+    pub h_wnd: isize,
+    /// 绘制文字的窗口所属的顶层窗口句柄的值
+    pub root: isize,
+    /// 输出的文字，空字符串表示这个区域被擦除了
+    pub text: String,
+    /// 文字所在的区域
+    pub rect: TextRect,
+    /// 字体名称
+    pub font_name: String,
+    /// 字体高度
+    pub font_size: i32,
+    /// 文字颜色，格式和COLORREF相同（0x00BBGGRR）
+    pub color: u32,
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub(crate) enum PeeperData {
     Log(String),
@@ -33,4 +101,34 @@ pub(crate) enum PeeperData {
     InputChar(u16),
     ImeCandidateList(CandidateList),
     ImeConversionMode(u32),
+    /// 输入法的组字串（例如拼音），空字符串表示组字已经结束
+    ImeComposition(String),
+    /// 输入法的候选窗口打开（true）或关闭（false）
+    ImeCandidateWindow(bool),
+    /// 窗口的文字被WM_SETTEXT修改
+    WindowTextChanged(WindowText),
+    /// 编辑框的内容发生了改变（EN_CHANGE通知）
+    EditChanged(WindowText),
+    /// 通过GDI接口输出了文字
+    GdiText(GdiText),
+}
+
+impl PeeperData {
+    /**
+     * 获取数据的类型名称，用于诊断统计。
+     * */
+    pub(crate) fn get_kind(&self) -> &'static str {
+        match self {
+            Self::Log(_) => "Log",
+            Self::Quit => "Quit",
+            Self::InputChar(_) => "InputChar",
+            Self::ImeCandidateList(_) => "ImeCandidateList",
+            Self::ImeConversionMode(_) => "ImeConversionMode",
+            Self::ImeComposition(_) => "ImeComposition",
+            Self::ImeCandidateWindow(_) => "ImeCandidateWindow",
+            Self::WindowTextChanged(_) => "WindowTextChanged",
+            Self::EditChanged(_) => "EditChanged",
+            Self::GdiText(_) => "GdiText",
+        }
+    }
 }
@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    time::Instant,
+};
+
+// 每个客户端每秒允许的事件数量
+const RATE_LIMIT_PER_SECOND: f64 = 200.0;
+// 每个客户端允许的突发事件数量，例如一次性粘贴大段文字时会短时间内产生大量输入事件
+const RATE_LIMIT_BURST: f64 = 400.0;
+
+/**
+ * 已连接的客户端（也就是注入了peeper的进程）的诊断信息，以模块名称区分。
+ * 同一个程序可能运行了多个进程，他们的统计信息会合并在一起。
+ * */
+#[derive(Clone, Debug)]
+pub struct ClientInfo {
+    /// 模块名称，也就是进程的可执行文件路径
+    pub module: String,
+    /// 当前连接着的进程id
+    pub pids: Vec<u32>,
+    /// 第一次连接的时间
+    pub connected_at: Instant,
+    /// 最后一次收到事件的时间
+    pub last_activity: Instant,
+    /// 按照事件类型统计的事件数量
+    pub event_counts: BTreeMap<&'static str, u64>,
+    /// 因为超出速率限制而被丢弃的事件数量
+    pub dropped_events: u64,
+}
+
+impl ClientInfo {
+    /**
+     * 获取收到的事件总数。
+     * */
+    pub fn get_total_events(&self) -> u64 {
+        self.event_counts.values().sum()
+    }
+}
+
+impl Display for ClientInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (pid: {:?}, connected {}s ago, last active {}s ago, dropped: {}, events: {:?})",
+            self.module,
+            self.pids,
+            self.connected_at.elapsed().as_secs(),
+            self.last_activity.elapsed().as_secs(),
+            self.dropped_events,
+            self.event_counts
+        )
+    }
+}
+
+/**
+ * 客户端注册表，记录所有已连接的客户端和他们的事件统计。
+ * */
+#[derive(Debug, Default)]
+pub(crate) struct ClientRegistry {
+    clients: HashMap<String, ClientInfo>,
+}
+
+impl ClientRegistry {
+    /**
+     * 登记一个新连接的客户端。
+     * `module` 模块名称。
+     * `pid` 进程id。
+     * */
+    pub(crate) fn connect(&mut self, module: &str, pid: u32) {
+        let now = Instant::now();
+        let info = self
+            .clients
+            .entry(module.to_string())
+            .or_insert_with(|| ClientInfo {
+                module: module.to_string(),
+                pids: vec![],
+                connected_at: now,
+                last_activity: now,
+                event_counts: Default::default(),
+                dropped_events: 0,
+            });
+        info.pids.push(pid);
+    }
+
+    /**
+     * 注销一个断开连接的客户端，同一个模块的所有进程都断开后，这个模块的记录会被移除。
+     * `module` 模块名称。
+     * `pid` 进程id。
+     * */
+    pub(crate) fn disconnect(&mut self, module: &str, pid: u32) {
+        let Some(info) = self.clients.get_mut(module) else {
+            return;
+        };
+        info.pids.retain(|i| *i != pid);
+        if info.pids.is_empty() {
+            self.clients.remove(module);
+        }
+    }
+
+    /**
+     * 记录客户端的一个事件。
+     * `module` 模块名称。
+     * `kind` 事件类型。
+     * `dropped` 事件是否因为超出速率限制而被丢弃。
+     * */
+    pub(crate) fn record(&mut self, module: &str, kind: &'static str, dropped: bool) {
+        let Some(info) = self.clients.get_mut(module) else {
+            return;
+        };
+        info.last_activity = Instant::now();
+        *info.event_counts.entry(kind).or_default() += 1;
+        if dropped {
+            info.dropped_events += 1;
+        }
+    }
+
+    /**
+     * 获取所有客户端的诊断信息，按照事件总数从多到少排序。
+     * */
+    pub(crate) fn get_clients(&self) -> Vec<ClientInfo> {
+        let mut clients: Vec<ClientInfo> = self.clients.values().cloned().collect();
+        clients.sort_by_key(|i| std::cmp::Reverse(i.get_total_events()));
+        clients
+    }
+}
+
+/**
+ * 每个客户端的速率限制器（令牌桶算法），防止某个出问题的进程发送大量事件而使监听器无法处理其他进程的事件。
+ * */
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            tokens: RATE_LIMIT_BURST,
+            last: now,
+        }
+    }
+
+    /**
+     * 尝试获取一个事件的许可，如果超出了速率限制则返回false，这时候应该丢弃此事件。
+     * `now` 当前时间。
+     * */
+    pub(crate) fn acquire(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * RATE_LIMIT_PER_SECOND).min(RATE_LIMIT_BURST);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_registry {
+    use crate::registry::{ClientRegistry, RateLimiter, RATE_LIMIT_BURST};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_registry() {
+        let mut registry = ClientRegistry::default();
+        registry.connect("a.exe", 1);
+        registry.connect("a.exe", 2);
+        registry.connect("b.exe", 3);
+        registry.record("a.exe", "InputChar", false);
+        registry.record("a.exe", "InputChar", true);
+        registry.record("b.exe", "Log", false);
+        let clients = registry.get_clients();
+        assert_eq!("a.exe", clients[0].module);
+        assert_eq!(2, clients[0].get_total_events());
+        assert_eq!(1, clients[0].dropped_events);
+        registry.disconnect("a.exe", 1);
+        assert_eq!(2, registry.get_clients().len());
+        registry.disconnect("a.exe", 2);
+        assert_eq!(1, registry.get_clients().len());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(now);
+        for _ in 0..RATE_LIMIT_BURST as usize {
+            assert!(limiter.acquire(now));
+        }
+        assert!(!limiter.acquire(now));
+        // 每秒恢复200个许可，10毫秒之后可以恢复两个
+        let later = now + Duration::from_millis(10);
+        assert!(limiter.acquire(later));
+        assert!(limiter.acquire(later));
+        assert!(!limiter.acquire(later));
+    }
+}
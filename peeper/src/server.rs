@@ -12,20 +12,24 @@
  */
 
 use crate::{
-    model::{CandidateList, PeeperData, PeeperPacket},
+    model::{
+        CandidateList, GdiText, PeeperData, PeeperHandshake, PeeperPacket, WindowText,
+        PEEPER_PROTOCOL_VERSION,
+    },
+    registry::{ClientInfo, ClientRegistry, RateLimiter},
     utils::get_pipe_name,
 };
-use log::{error, info};
-use rigela_utils::pipe::{PipeStream, PipeStreamError};
+use log::{error, info, warn};
+use rigela_utils::pipe::{PipeFormat, PipeStream, PipeStreamError, Transport, TransportListener};
 use std::{
     fmt::{Debug, Formatter},
-    sync::Arc,
-};
-use tokio::{
-    net::windows::named_pipe::{NamedPipeServer, ServerOptions},
-    runtime::Runtime,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
+use tokio::{runtime::Runtime, sync::Mutex};
 
 macro_rules! add_listener {
     ($self:expr,$listener_type:path,$listener:expr) => {{
@@ -38,11 +42,18 @@ enum ListenerType {
     OnInputChar(Box<dyn Fn(u16) + Send + Sync>),
     OnImeCandidateList(Box<dyn Fn(CandidateList) + Send + Sync>),
     OnImeConversionMode(Box<dyn Fn(u32) + Send + Sync>),
+    OnImeComposition(Box<dyn Fn(String) + Send + Sync>),
+    OnImeCandidateWindow(Box<dyn Fn(bool) + Send + Sync>),
+    OnWindowTextChanged(Box<dyn Fn(WindowText) + Send + Sync>),
+    OnEditChanged(Box<dyn Fn(WindowText) + Send + Sync>),
+    OnGdiText(Box<dyn Fn(GdiText) + Send + Sync>),
 }
 
 pub struct PeeperServer {
     listeners: Arc<Mutex<Vec<ListenerType>>>,
     work_runtime: &'static Runtime,
+    incompatible_clients: Arc<AtomicU32>,
+    registry: Arc<Mutex<ClientRegistry>>,
 }
 
 impl PeeperServer {
@@ -50,32 +61,138 @@ impl PeeperServer {
         Self {
             listeners: Arc::new(vec![].into()),
             work_runtime,
+            incompatible_clients: AtomicU32::new(0).into(),
+            registry: Default::default(),
         }
     }
 
     pub async fn run(&self) {
+        let mut listener = match TransportListener::bind(&get_pipe_name()).await {
+            Ok(l) => l,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
         loop {
-            match ServerOptions::new().create(get_pipe_name()) {
+            match listener.accept().await {
                 Ok(p) => {
-                    if let Err(e) = p.connect().await {
-                        error!("{}", e);
-                        continue;
-                    }
                     info!("New client has connected.");
-                    let stream = PipeStream::new(p);
-                    self.on_client(stream);
+                    self.on_client(PipeStream::new(p));
                 }
-                Err(e) => {
-                    error!("{}", e);
-                    break;
+                Err(e) => error!("{}", e),
+            }
+        }
+    }
+
+    /**
+     * 获取因为协议版本不匹配而被拒绝的客户端数量，通常是因为远进程中加载了旧版本的dll。
+     * */
+    pub fn get_incompatible_client_count(&self) -> u32 {
+        self.incompatible_clients.load(Ordering::Relaxed)
+    }
+
+    /**
+     * 获取所有已连接的客户端（注入了peeper的进程）的诊断信息，按照事件总数从多到少排序。
+     * */
+    pub async fn get_clients(&self) -> Vec<ClientInfo> {
+        self.registry.lock().await.get_clients()
+    }
+
+    /**
+     * 把所有已连接的客户端的诊断信息输出到日志。
+     * */
+    pub async fn log_clients(&self) {
+        let clients = self.get_clients().await;
+        info!(
+            "{} clients are connected, {} incompatible clients were rejected.",
+            clients.len(),
+            self.get_incompatible_client_count()
+        );
+        for i in clients.iter() {
+            info!("{}", i);
+        }
+    }
+
+    /**
+     * 与客户端握手，如果客户端的协议版本与本服务端不同，则拒绝客户端并计数。
+     * `stream` 客户端的连接。
+     * `incompatible_clients` 不兼容的客户端的计数。
+     * */
+    async fn accept_client(
+        mut stream: PipeStream<PeeperHandshake, Transport>,
+        incompatible_clients: &AtomicU32,
+    ) -> Option<(PipeStream<PeeperPacket, Transport>, String, u32)> {
+        let reject = |reason: String| {
+            let count = incompatible_clients.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(
+                "Rejected an incompatible client ({} incompatible clients so far). {}",
+                count, reason
+            );
+        };
+        match stream.accept_handshake(PipeFormat::preferred()).await {
+            Ok(_) => {}
+            Err(e @ PipeStreamError::Incompatible(_)) => {
+                reject(e.to_string());
+                return None;
+            }
+            // 旧版本的dll不会进行管道握手，而是直接发送业务数据，这里无法解析
+            Err(PipeStreamError::DecodeError(e)) => {
+                reject(format!("The client didn't say hello. {}", e));
+                return None;
+            }
+            Err(e) => {
+                error!("Can't negotiate with the client. {}", e);
+                return None;
+            }
+        }
+        match stream.recv().await {
+            Ok(PeeperHandshake::Hello {
+                version,
+                pid,
+                module,
+            }) => {
+                if version == PEEPER_PROTOCOL_VERSION {
+                    if let Err(e) = stream.send(&PeeperHandshake::Accepted).await {
+                        error!("{}", e);
+                        return None;
+                    }
+                    info!("Accepted the client {} ({}).", module, pid);
+                    return Some((stream.cast(), module, pid));
                 }
+                stream
+                    .send(&PeeperHandshake::Rejected(PEEPER_PROTOCOL_VERSION))
+                    .await
+                    .unwrap_or(());
+                reject(format!(
+                    "The client {} ({}) uses the protocol version {}, expected {}.",
+                    module, pid, version, PEEPER_PROTOCOL_VERSION
+                ));
             }
+            // 完成了管道握手但没有发送业务上的握手消息
+            Err(PipeStreamError::DecodeError(e)) => {
+                reject(format!("The client didn't say hello. {}", e))
+            }
+            Ok(x) => reject(format!("Unexpected handshake message {:?}.", x)),
+            Err(e) => error!("{}", e),
         }
+        None
     }
 
-    fn on_client(&self, mut stream: PipeStream<PeeperPacket, NamedPipeServer>) {
+    fn on_client(&self, stream: PipeStream<PeeperHandshake, Transport>) {
         let listeners = self.listeners.clone();
+        let incompatible_clients = self.incompatible_clients.clone();
+        let registry = self.registry.clone();
         self.work_runtime.spawn(async move {
+            let Some((mut stream, module, pid)) =
+                Self::accept_client(stream, &incompatible_clients).await
+            else {
+                return;
+            };
+            registry.lock().await.connect(&module, pid);
+            let mut limiter = RateLimiter::new(Instant::now());
+            // 客户端是否正在因为超出速率限制而被丢弃事件，用于避免重复输出日志
+            let mut flooding = false;
             loop {
                 let packet = stream.recv().await;
                 if let Err(PipeStreamError::ReadEof) = &packet {
@@ -86,6 +203,28 @@ impl PeeperServer {
                     continue;
                 }
                 let packet = packet.unwrap();
+                let dropped = match packet.data {
+                    PeeperData::Quit => false,
+                    _ => !limiter.acquire(Instant::now()),
+                };
+                registry
+                    .lock()
+                    .await
+                    .record(&module, packet.data.get_kind(), dropped);
+                if dropped != flooding {
+                    flooding = dropped;
+                    if flooding {
+                        warn!(
+                            "The client {} ({}) is sending too many events, some of them will be dropped.",
+                            module, pid
+                        );
+                    } else {
+                        info!("The client {} ({}) has calmed down.", module, pid);
+                    }
+                }
+                if dropped {
+                    continue;
+                }
                 match packet.data {
                     PeeperData::Log(msg) => info!("{}: {}", packet.name, msg),
                     PeeperData::Quit => break,
@@ -97,6 +236,11 @@ impl PeeperServer {
                     }
                 };
             }
+            let mut registry = registry.lock().await;
+            if let Some(info) = registry.get_clients().iter().find(|i| i.module == module) {
+                info!("The client {} ({}) has disconnected. {}", module, pid, info);
+            }
+            registry.disconnect(&module, pid);
         });
     }
 
@@ -114,6 +258,26 @@ impl PeeperServer {
                 PeeperData::ImeConversionMode(c) => (&*f)(c.clone()),
                 _ => {}
             },
+            ListenerType::OnImeComposition(f) => match data {
+                PeeperData::ImeComposition(c) => (&*f)(c.clone()),
+                _ => {}
+            },
+            ListenerType::OnImeCandidateWindow(f) => match data {
+                PeeperData::ImeCandidateWindow(c) => (&*f)(c.clone()),
+                _ => {}
+            },
+            ListenerType::OnWindowTextChanged(f) => match data {
+                PeeperData::WindowTextChanged(c) => (&*f)(c.clone()),
+                _ => {}
+            },
+            ListenerType::OnEditChanged(f) => match data {
+                PeeperData::EditChanged(c) => (&*f)(c.clone()),
+                _ => {}
+            },
+            ListenerType::OnGdiText(f) => match data {
+                PeeperData::GdiText(c) => (&*f)(c.clone()),
+                _ => {}
+            },
         }
     }
 
@@ -147,6 +311,61 @@ impl PeeperServer {
     ) {
         add_listener!(self, ListenerType::OnImeConversionMode, listener)
     }
+
+    /**
+     * 添加一个监听器，当输入法的组字串（例如拼音）改变时发出通知，组字结束时通知空字符串。
+     * `listener` 一个监听函数。
+     * */
+    pub async fn add_on_ime_composition_listener(
+        &self,
+        listener: impl Fn(String) + Send + Sync + 'static,
+    ) {
+        add_listener!(self, ListenerType::OnImeComposition, listener)
+    }
+
+    /**
+     * 添加一个监听器，当输入法的候选窗口打开或关闭时发出通知，参数为true表示打开。
+     * `listener` 一个监听函数。
+     * */
+    pub async fn add_on_ime_candidate_window_listener(
+        &self,
+        listener: impl Fn(bool) + Send + Sync + 'static,
+    ) {
+        add_listener!(self, ListenerType::OnImeCandidateWindow, listener)
+    }
+
+    /**
+     * 添加一个监听器，当前台窗口中的子窗口（例如状态栏和静态文本）通过WM_SETTEXT修改文字时发出通知。
+     * `listener` 一个监听函数。
+     * */
+    pub async fn add_on_window_text_changed_listener(
+        &self,
+        listener: impl Fn(WindowText) + Send + Sync + 'static,
+    ) {
+        add_listener!(self, ListenerType::OnWindowTextChanged, listener)
+    }
+
+    /**
+     * 添加一个监听器，当前台窗口中没有键盘焦点的编辑框内容改变时发出通知，用户自己输入的内容不会通知。
+     * `listener` 一个监听函数。
+     * */
+    pub async fn add_on_edit_changed_listener(
+        &self,
+        listener: impl Fn(WindowText) + Send + Sync + 'static,
+    ) {
+        add_listener!(self, ListenerType::OnEditChanged, listener)
+    }
+
+    /**
+     * 添加一个监听器，当程序通过GDI接口输出文字或擦除文字区域时发出通知。
+     * `listener` 一个监听函数。
+     * */
+    pub async fn add_on_gdi_text_listener(
+        &self,
+        listener: impl Fn(GdiText) + Send + Sync + 'static,
+    ) {
+        add_listener!(self, ListenerType::OnGdiText, listener)
+    }
 }
 
 impl Debug for PeeperServer {
@@ -154,3 +373,35 @@ impl Debug for PeeperServer {
         write!(f, "PeeperServer")
     }
 }
+
+#[cfg(test)]
+mod test_server {
+    use crate::{
+        model::{PeeperData, PeeperPacket},
+        server::PeeperServer,
+    };
+    use rigela_utils::pipe::{client_connect, PipeStream, TransportListener};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_legacy_client() {
+        let mut listener = TransportListener::bind("tcp://127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_endpoint();
+        // 旧版本的dll连接后不进行任何握手，直接发送业务数据
+        let mut client = client_connect::<PeeperPacket>(&endpoint).await;
+        client
+            .send(&PeeperPacket {
+                name: "legacy".to_string(),
+                data: PeeperData::Log("hello".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let stream = PipeStream::new(listener.accept().await.unwrap());
+        let incompatible_clients = AtomicU32::new(0);
+        assert!(PeeperServer::accept_client(stream, &incompatible_clients)
+            .await
+            .is_none());
+        assert_eq!(1, incompatible_clients.load(Ordering::Relaxed));
+    }
+}
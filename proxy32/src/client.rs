@@ -11,20 +11,42 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
-use crate::model::{IbmeciVoiceParams, Proxy32Data, Proxy32Packet};
+use crate::model::{EngineInfo, EngineParams, Proxy32Data, Proxy32Packet};
 use log::error;
-use rigela_utils::pipe::{client_connect, PipeStream, PipeStreamError};
+use rigela_utils::pipe::{
+    client_connect, PipeFormat, PipeStreamError, PipeStreamReader, PipeStreamWriter, Transport,
+};
 use std::{
     collections::HashMap,
-    sync::atomic::{AtomicU32, Ordering::SeqCst},
+    sync::{
+        atomic::{AtomicU32, Ordering::SeqCst},
+        Arc,
+    },
+};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        Mutex,
+    },
+    time::{timeout, Duration},
 };
-use tokio::{net::windows::named_pipe::NamedPipeClient, sync::Mutex};
+
+// 普通请求的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// 流式合成中等待下一个数据块的超时时间
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+// 心跳的超时时间
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(3);
+
+type PendingRequests = Arc<Mutex<HashMap<u32, UnboundedSender<Proxy32Data>>>>;
 
 #[derive(Debug)]
 pub struct Proxy32Client {
     task_id: AtomicU32,
-    cached: Mutex<HashMap<u32, Proxy32Data>>,
-    stream: Mutex<PipeStream<Proxy32Packet, NamedPipeClient>>,
+    pending: PendingRequests,
+    writer: Mutex<PipeStreamWriter<Proxy32Packet, Transport>>,
+    last_voices: Mutex<HashMap<String, String>>,
+    last_params: Mutex<HashMap<String, EngineParams>>,
 }
 
 impl Proxy32Client {
@@ -32,115 +54,333 @@ impl Proxy32Client {
      * 创建一个proxy32的客户端，可以下发各种请求命令。
      * */
     pub async fn new(pipe_name: &str) -> Self {
-        let stream = client_connect(pipe_name).await;
+        let mut stream = client_connect(pipe_name).await;
+        if let Err(e) = stream.handshake(PipeFormat::preferred()).await {
+            error!("Can't negotiate with the proxy32 server. {}", e);
+        }
+        let (reader, writer) = stream.split();
+        let pending = PendingRequests::default();
+        tokio::spawn(Self::dispatch(reader, pending.clone()));
         Self {
             task_id: AtomicU32::new(0),
-            cached: HashMap::new().into(),
-            stream: stream.into(),
+            pending,
+            writer: writer.into(),
+            last_voices: Default::default(),
+            last_params: Default::default(),
         }
     }
 
-    async fn exec(&self, data: &Proxy32Data) -> Option<Proxy32Data> {
-        self.task_id.fetch_add(1, SeqCst);
-        let id = self.task_id.load(SeqCst);
+    /**
+     * 不断地接收服务端返回的数据包，并根据请求id分发给等待中的请求。
+     * 流式合成的数据块不会结束请求，其他数据包都视为请求的最终响应。
+     * */
+    async fn dispatch(
+        mut reader: PipeStreamReader<Proxy32Packet, Transport>,
+        pending: PendingRequests,
+    ) {
+        loop {
+            match reader.recv().await {
+                Ok(p) => {
+                    let mut lock = pending.lock().await;
+                    let finished = !matches!(p.data, Proxy32Data::SynthChunk(_));
+                    if let Some(tx) = lock.get(&p.id) {
+                        tx.send(p.data).unwrap_or(());
+                    }
+                    if finished {
+                        lock.remove(&p.id);
+                    }
+                }
+                // 无法解析的数据包只影响他自己，之后的数据包仍然可以读取
+                Err(PipeStreamError::DecodeError(e)) => error!("{}", e),
+                Err(PipeStreamError::ReadEof) => break,
+                Err(e) => {
+                    error!("{}", e);
+                    break;
+                }
+            }
+        }
+        // 连接已经断开，丢弃所有等待中的请求，让他们立即返回
+        pending.lock().await.clear();
+    }
+
+    async fn request(&self, data: &Proxy32Data) -> Option<(u32, UnboundedReceiver<Proxy32Data>)> {
+        let id = self.task_id.fetch_add(1, SeqCst) + 1;
+        let (tx, rx) = unbounded_channel();
+        self.pending.lock().await.insert(id, tx);
         let packet = Proxy32Packet {
             id,
             data: data.clone(),
         };
-        {
-            if let Err(e) = self.stream.lock().await.send(&packet).await {
-                error!("Can't send `{}`. {}", data, e);
-                return None;
-            }
+        if let Err(e) = self.writer.lock().await.send(&packet).await {
+            error!("Can't send `{}`. {}", data, e);
+            self.pending.lock().await.remove(&id);
+            return None;
         }
-        let res = loop {
-            let lock = self.cached.lock().await;
-
-            match lock.get(&id) {
-                None => {
-                    drop(lock);
-                    let res = self.stream.lock().await.recv().await;
-                    match res {
-                        Err(PipeStreamError::ReadEof) => return None,
-                        Ok(p) if p.id == id => return Some(p.data),
-                        Ok(p) => {
-                            self.cached.lock().await.insert(p.id, p.data);
-                        }
-                        _ => {}
-                    }
-                }
-                Some(x) => break x.clone(),
+        Some((id, rx))
+    }
+
+    async fn exec(&self, data: &Proxy32Data) -> Option<Proxy32Data> {
+        self.exec_timeout(data, REQUEST_TIMEOUT).await
+    }
+
+    async fn exec_timeout(&self, data: &Proxy32Data, duration: Duration) -> Option<Proxy32Data> {
+        let (id, mut rx) = self.request(data).await?;
+        match timeout(duration, rx.recv()).await {
+            Ok(r) => r,
+            Err(_) => {
+                error!("The request `{}` has timed out.", data);
+                self.pending.lock().await.remove(&id);
+                None
             }
-        };
-        self.cached.lock().await.remove(&id);
-        Some(res)
+        }
     }
 
     /**
-     * 通知服务器端退出程序。
+     * 发送心跳，检测服务端是否还能正常响应。
      * */
-    pub async fn quit(&self) {
-        self.exec(&Proxy32Data::Quit).await;
+    pub async fn heartbeat(&self) -> bool {
+        matches!(
+            self.exec_timeout(&Proxy32Data::Heartbeat, HEARTBEAT_TIMEOUT)
+                .await,
+            Some(Proxy32Data::Heartbeat)
+        )
     }
 
-    //noinspection SpellCheckingInspection
     /**
-     * 使用vvtts合成语音。
-     * `text` 文字内容。
+     * 把另一个客户端最后设置过的发音人和语音参数应用到当前客户端，用于proxy32重启之后恢复语音状态。
+     * `other` 之前使用的客户端。
      * */
-    pub async fn eci_synth(&self, text: &str) -> Vec<u8> {
-        if let Some(Proxy32Data::EciSynthResponse(r)) = self
-            .exec(&Proxy32Data::EciSynthRequest(text.to_string()))
-            .await
-        {
-            r
-        } else {
-            vec![]
+    pub async fn restore_voice_from(&self, other: &Self) {
+        let voices = other.last_voices.lock().await.clone();
+        for (engine, id) in voices.iter() {
+            self.set_voice(engine, id).await;
+        }
+        // 切换发音人可能会重置语音参数，所以参数要在发音人之后设置
+        let params = other.last_params.lock().await.clone();
+        for (engine, params) in params.iter() {
+            self.set_params(engine, params).await;
         }
     }
 
-    //noinspection SpellCheckingInspection
     /**
-     * 设置vvtts语音参数。
-     * `params` 参数数据。
+     * 通知服务器端退出程序。
      * */
-    pub async fn eci_set_voice_params(&self, params: &IbmeciVoiceParams) {
-        self.exec(&Proxy32Data::EciSetParamsRequest(params.clone()))
-            .await;
+    pub async fn quit(&self) {
+        self.exec(&Proxy32Data::Quit).await;
     }
 
-    //noinspection SpellCheckingInspection
     /**
-     * 获取vvtts语音参数。
+     * 获取proxy32中托管的所有引擎。
      * */
-    pub async fn eci_get_voice_params(&self) -> IbmeciVoiceParams {
-        if let Some(Proxy32Data::EciGetParamsResponse(r)) =
-            self.exec(&Proxy32Data::EciGetParamsRequest).await
+    pub async fn get_engines(&self) -> Vec<EngineInfo> {
+        if let Some(Proxy32Data::EngineListResponse(r)) =
+            self.exec(&Proxy32Data::EngineListRequest).await
         {
             return r;
         }
-        IbmeciVoiceParams::default()
+        vec![]
     }
 
-    //noinspection SpellCheckingInspection
     /**
-     * 获取vvtts发音人列表。
+     * 获取引擎的发音人列表，每一项是发音人的id和名称。
+     * `engine` 引擎名称。
      * */
-    pub async fn eci_get_voices(&self) -> Vec<(u32, String)> {
-        if let Some(Proxy32Data::EciGetVoiceListResponse(r)) =
-            self.exec(&Proxy32Data::EciGetVoiceListRequest).await
+    pub async fn get_voices(&self, engine: &str) -> Vec<(String, String)> {
+        if let Some(Proxy32Data::VoiceListResponse(r)) = self
+            .exec(&Proxy32Data::VoiceListRequest(engine.to_string()))
+            .await
         {
             return r;
         }
         vec![]
     }
 
-    //noinspection SpellCheckingInspection
     /**
-     * 设置vvtts发音人。
-     * `voice_id` 发音人id。
+     * 设置引擎的发音人。
+     * `engine` 引擎名称。
+     * `id` 发音人id。
+     * */
+    pub async fn set_voice(&self, engine: &str, id: &str) {
+        self.last_voices
+            .lock()
+            .await
+            .insert(engine.to_string(), id.to_string());
+        self.exec(&Proxy32Data::SetVoiceRequest(
+            engine.to_string(),
+            id.to_string(),
+        ))
+        .await;
+    }
+
+    /**
+     * 设置引擎的语音参数，值为None的参数保持不变。
+     * `engine` 引擎名称。
+     * `params` 参数数据。
+     * */
+    pub async fn set_params(&self, engine: &str, params: &EngineParams) {
+        self.last_params
+            .lock()
+            .await
+            .entry(engine.to_string())
+            .or_default()
+            .merge(params);
+        self.exec(&Proxy32Data::SetParamsRequest(
+            engine.to_string(),
+            params.clone(),
+        ))
+        .await;
+    }
+
+    /**
+     * 使用指定的引擎流式合成语音，服务端每合成出一块数据就会立即返回，不必等待全部合成完毕。
+     * `engine` 引擎名称。
+     * `text` 文字内容。
+     * */
+    pub async fn synth_stream(&self, engine: &str, text: &str) -> Option<SynthStream> {
+        let (id, rx) = self
+            .request(&Proxy32Data::SynthStreamRequest(
+                engine.to_string(),
+                text.to_string(),
+            ))
+            .await?;
+        Some(SynthStream { id, rx })
+    }
+
+    /**
+     * 取消流式合成。
+     * `id` 流式合成的id，可以通过`SynthStream::get_id`获取。
+     * */
+    pub async fn synth_cancel(&self, id: u32) {
+        // 先在本地停止分发，之后到达的数据块将被丢弃
+        self.pending.lock().await.remove(&id);
+        self.exec(&Proxy32Data::SynthCancelRequest(id)).await;
+    }
+}
+
+/**
+ * 流式合成的会话，可以逐块接收合成好的音频数据。
+ * */
+#[derive(Debug)]
+pub struct SynthStream {
+    id: u32,
+    rx: UnboundedReceiver<Proxy32Data>,
+}
+
+impl SynthStream {
+    /**
+     * 获取流式合成的id，可用于取消合成。
+     * */
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /**
+     * 接收下一块音频数据，如果合成已经结束、被取消、超时或者连接已经断开，则返回None。
      * */
-    pub async fn eci_set_voice(&self, voice_id: u32) {
-        self.exec(&Proxy32Data::EciSetVoiceRequest(voice_id)).await;
+    pub async fn next(&mut self) -> Option<Vec<u8>> {
+        match timeout(CHUNK_TIMEOUT, self.rx.recv()).await {
+            Ok(Some(Proxy32Data::SynthChunk(data))) => Some(data),
+            Ok(_) => None,
+            Err(_) => {
+                error!("The synthesis stream ({}) has timed out.", self.id);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_client {
+    use crate::{
+        client::Proxy32Client,
+        model::{EngineInfo, EngineParams, Proxy32Data, Proxy32Packet},
+    };
+    use rigela_utils::pipe::{PipeFormat, PipeStream, TransportListener};
+
+    #[tokio::test]
+    async fn test_synth_stream() {
+        let mut listener = TransportListener::bind("tcp://127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_endpoint();
+        // 模拟一个只托管了test引擎的proxy32服务端，把文字的每个字节作为一个数据块返回
+        tokio::spawn(async move {
+            let mut stream = PipeStream::<Proxy32Packet, _>::new(listener.accept().await.unwrap());
+            stream.accept_handshake(PipeFormat::Binary).await.unwrap();
+            while let Ok(p) = stream.recv().await {
+                let data = match p.data {
+                    Proxy32Data::EngineListRequest => {
+                        Proxy32Data::EngineListResponse(vec![EngineInfo {
+                            name: "test".to_string(),
+                            sample_rate: 16000,
+                            channels: 1,
+                        }])
+                    }
+                    Proxy32Data::SynthStreamRequest(engine, text) => {
+                        for i in text.bytes().filter(|_| engine == "test") {
+                            let chunk = Proxy32Packet {
+                                id: p.id,
+                                data: Proxy32Data::SynthChunk(vec![i]),
+                            };
+                            stream.send(&chunk).await.unwrap();
+                        }
+                        Proxy32Data::SynthStreamEnd
+                    }
+                    Proxy32Data::SetParamsRequest(..) => Proxy32Data::SetParamsResponse(()),
+                    x => x,
+                };
+                stream
+                    .send(&Proxy32Packet { id: p.id, data })
+                    .await
+                    .unwrap();
+            }
+        });
+        let client = Proxy32Client::new(&endpoint).await;
+        let engines = client.get_engines().await;
+        assert_eq!(1, engines.len());
+        assert_eq!("test", engines[0].name);
+        let mut stream = client.synth_stream("test", "abc").await.unwrap();
+        let mut data = vec![];
+        while let Some(chunk) = stream.next().await {
+            data.extend(chunk);
+        }
+        assert_eq!(b"abc".to_vec(), data);
+        // 不存在的引擎没有任何数据，但流式合成也会正常结束
+        let mut stream = client.synth_stream("unknown", "def").await.unwrap();
+        assert_eq!(None, stream.next().await);
+        // 多次设置的参数会合并起来，以便proxy32重启后恢复
+        let speed = EngineParams {
+            speed: Some(50),
+            ..Default::default()
+        };
+        let pitch = EngineParams {
+            pitch: Some(30),
+            ..Default::default()
+        };
+        client.set_params("test", &speed).await;
+        client.set_params("test", &pitch).await;
+        assert_eq!(
+            Some(&EngineParams {
+                speed: Some(50),
+                volume: None,
+                pitch: Some(30),
+            }),
+            client.last_params.lock().await.get("test")
+        );
+        assert!(client.heartbeat().await);
+        client.quit().await;
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_timeout() {
+        let mut listener = TransportListener::bind("tcp://127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_endpoint();
+        // 模拟一个卡死的proxy32服务端，只接收请求而从不响应
+        tokio::spawn(async move {
+            let mut stream = PipeStream::<Proxy32Packet, _>::new(listener.accept().await.unwrap());
+            stream.accept_handshake(PipeFormat::Binary).await.unwrap();
+            while stream.recv().await.is_ok() {}
+        });
+        let client = Proxy32Client::new(&endpoint).await;
+        assert!(!client.heartbeat().await);
+        assert!(client.pending.lock().await.is_empty());
     }
 }
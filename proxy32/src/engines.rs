@@ -0,0 +1,80 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+mod eci;
+mod sapi5;
+
+use crate::{
+    engines::{eci::EciAdapter, sapi5::Sapi5Adapter},
+    model::{EngineInfo, EngineParams},
+};
+use log::{error, info};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// 32位语音引擎的适配器，把各种引擎统一成相同的接口，供proxy32托管
+#[async_trait::async_trait]
+pub(crate) trait EngineAdapter: Send + Sync {
+    fn get_info(&self) -> EngineInfo;
+    fn get_voices(&self) -> Vec<(String, String)>;
+    fn set_voice(&self, id: &str);
+    fn set_params(&self, params: &EngineParams);
+    /**
+     * 流式合成，每合成出一块数据就通过`sender`发送出去，合成完毕后返回。
+     * 如果接收端已经被丢弃，说明合成被取消了，适配器应该尽快中止合成。
+     * */
+    async fn synth(&self, text: &str, sender: UnboundedSender<Vec<u8>>);
+}
+
+/// 引擎注册表，保存proxy32中所有可用的引擎适配器
+pub(crate) struct EngineRegistry {
+    adapters: Vec<Box<dyn EngineAdapter>>,
+}
+
+impl EngineRegistry {
+    /**
+     * 创建注册表并加载所有的引擎适配器，加载失败的引擎会被跳过。
+     * */
+    pub(crate) async fn new() -> Self {
+        let mut adapters: Vec<Box<dyn EngineAdapter>> = vec![];
+        match EciAdapter::new().await {
+            Ok(x) => adapters.push(Box::new(x)),
+            Err(e) => error!("{}", e),
+        }
+        match Sapi5Adapter::new().await {
+            Ok(x) => adapters.push(Box::new(x)),
+            Err(e) => error!("{}", e),
+        }
+        for i in adapters.iter() {
+            info!("Engine `{}` is loaded.", i.get_info().name);
+        }
+        Self { adapters }
+    }
+
+    /**
+     * 获取所有引擎的信息。
+     * */
+    pub(crate) fn get_engines(&self) -> Vec<EngineInfo> {
+        self.adapters.iter().map(|i| i.get_info()).collect()
+    }
+
+    /**
+     * 根据名称查找引擎。
+     * `name` 引擎名称。
+     * */
+    pub(crate) fn get(&self, name: &str) -> Option<&dyn EngineAdapter> {
+        self.adapters
+            .iter()
+            .find(|i| i.get_info().name == name)
+            .map(|i| i.as_ref())
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    engines::EngineAdapter,
+    model::{EngineInfo, EngineParams},
+};
+use rigela_utils::ibmeci::{
+    convert_pitch_param, convert_speed_param, Ibmeci, VP_PITCH_BASELINE, VP_PITCH_FLUCTUATION,
+    VP_SPEED, VP_VOLUME,
+};
+use std::str::FromStr;
+use tokio::sync::mpsc::UnboundedSender;
+
+//noinspection SpellCheckingInspection
+/// ibmeci（vvtts）引擎的适配器
+pub(crate) struct EciAdapter {
+    eci: &'static Ibmeci,
+}
+
+impl EciAdapter {
+    pub(crate) async fn new() -> Result<Self, String> {
+        Ok(Self {
+            eci: Ibmeci::get().await?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EngineAdapter for EciAdapter {
+    //noinspection SpellCheckingInspection
+    fn get_info(&self) -> EngineInfo {
+        EngineInfo {
+            name: "Vvtts".to_string(),
+            sample_rate: 11025,
+            channels: 1,
+        }
+    }
+
+    fn get_voices(&self) -> Vec<(String, String)> {
+        self.eci
+            .get_voices()
+            .into_iter()
+            .map(|(id, name)| (id.to_string(), name))
+            .collect()
+    }
+
+    fn set_voice(&self, id: &str) {
+        self.eci.set_voice(u32::from_str(id).unwrap_or(0))
+    }
+
+    fn set_params(&self, params: &EngineParams) {
+        if let Some(v) = params.speed {
+            self.eci.set_voice_param(VP_SPEED, convert_speed_param(v));
+        }
+        if let Some(v) = params.volume {
+            self.eci.set_voice_param(VP_VOLUME, v);
+        }
+        if let Some(v) = params.pitch {
+            let pitch = convert_pitch_param(v);
+            self.eci.set_voice_param(VP_PITCH_BASELINE, pitch);
+            self.eci.set_voice_param(VP_PITCH_FLUCTUATION, pitch);
+        }
+    }
+
+    async fn synth(&self, text: &str, sender: UnboundedSender<Vec<u8>>) {
+        self.eci.synth_stream(text, sender).await
+    }
+}
@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+use crate::{
+    engines::EngineAdapter,
+    model::{EngineInfo, EngineParams},
+};
+use log::error;
+use std::{
+    cell::Cell,
+    sync::mpsc::{channel, Sender},
+    thread,
+};
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
+use win_wrap::{
+    com::{co_initialize_multi_thread, co_uninitialize},
+    tts::Sapi5TtsSynthesizer,
+};
+
+// 交给合成线程执行的任务
+enum Sapi5Task {
+    SetVoice(String),
+    SetParams(EngineParams),
+    Synth(String, UnboundedSender<Vec<u8>>, oneshot::Sender<()>),
+}
+
+/**
+ * 32位sapi5引擎的适配器，只有32位的语音库需要通过proxy32加载。
+ * 合成器是COM对象，不能在多个线程之间共享，所以它只在一个专用的线程中创建和使用，适配器通过通道把任务按顺序交给这个线程。
+ * */
+pub(crate) struct Sapi5Adapter {
+    tasks: Sender<Sapi5Task>,
+    // 发音人列表在合成线程启动时获取，之后不需要再访问合成器
+    voices: Vec<(String, String)>,
+}
+
+impl Sapi5Adapter {
+    pub(crate) async fn new() -> Result<Self, String> {
+        let (tasks, receiver) = channel();
+        let (tx, rx) = oneshot::channel();
+        thread::Builder::new()
+            .name("sapi5".to_string())
+            .spawn(move || {
+                let res = co_initialize_multi_thread();
+                if res.is_err() {
+                    error!("Can't initialize the com environment. {}", res.message());
+                }
+                let synth = Sapi5TtsSynthesizer::new();
+                if tx.send(synth.get_voice_list()).is_ok() {
+                    // 适配器被丢弃以后通道关闭，线程随之退出
+                    for task in receiver {
+                        run_task(&synth, task);
+                    }
+                }
+                drop(synth);
+                if res.is_ok() {
+                    co_uninitialize();
+                }
+            })
+            .map_err(|e| format!("Can't start the sapi5 thread. {}", e))?;
+        let voices = rx
+            .await
+            .map_err(|_| "Can't create the sapi5 synthesizer.".to_string())?;
+        Ok(Self { tasks, voices })
+    }
+
+    // 把任务交给合成线程，线程已经退出时返回false
+    fn push(&self, task: Sapi5Task) -> bool {
+        let sent = self.tasks.send(task).is_ok();
+        if !sent {
+            error!("The sapi5 thread has exited.");
+        }
+        sent
+    }
+}
+
+// 在合成线程中执行一个任务
+fn run_task(synth: &Sapi5TtsSynthesizer, task: Sapi5Task) {
+    match task {
+        Sapi5Task::SetVoice(id) => synth.set_voice(id),
+        Sapi5Task::SetParams(params) => {
+            if let Some(v) = params.speed {
+                synth.set_speed(3.0 + (v as f64 - 50.0) * 0.06);
+            }
+            if let Some(v) = params.volume {
+                synth.set_volume(0.5 + (v as f64 - 50.0) * 0.01);
+            }
+            if let Some(v) = params.pitch {
+                synth.set_pitch(1.0 + (v as f64 - 50.0) * 0.01);
+            }
+        }
+        Sapi5Task::Synth(text, sender, done) => {
+            // 排队期间已经被取消的合成不需要再执行
+            if !sender.is_closed() {
+                let first = Cell::new(true);
+                synth.synth(&text, move |mut data| {
+                    if first.replace(false) {
+                        // 跳过开头的0.01秒，因为基本上他是静音的
+                        // sapi5语音的采样率是每秒16000个样本，320个字节等于160个样本（0.01秒）
+                        data.drain(..data.len().min(320));
+                    }
+                    sender.send(data).is_ok()
+                });
+            }
+            let _ = done.send(());
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EngineAdapter for Sapi5Adapter {
+    fn get_info(&self) -> EngineInfo {
+        EngineInfo {
+            name: "Sapi5x86".to_string(),
+            sample_rate: 16000,
+            channels: 1,
+        }
+    }
+
+    fn get_voices(&self) -> Vec<(String, String)> {
+        self.voices.clone()
+    }
+
+    fn set_voice(&self, id: &str) {
+        self.push(Sapi5Task::SetVoice(id.to_string()));
+    }
+
+    fn set_params(&self, params: &EngineParams) {
+        self.push(Sapi5Task::SetParams(params.clone()));
+    }
+
+    async fn synth(&self, text: &str, sender: UnboundedSender<Vec<u8>>) {
+        let (tx, rx) = oneshot::channel();
+        if self.push(Sapi5Task::Synth(text.to_string(), sender, tx)) {
+            // 合成线程在合成完毕或者被取消以后才会回复
+            let _ = rx.await;
+        }
+    }
+}
@@ -13,6 +13,8 @@
 
 #![windows_subsystem = "windows"]
 
+#[cfg(all(feature = "server", target_arch = "x86"))]
+mod engines;
 #[cfg(all(feature = "model", target_arch = "x86"))]
 mod model;
 #[cfg(all(feature = "server", target_arch = "x86"))]
@@ -25,10 +27,16 @@ async fn main() {
     use peeper;
     use rigela_utils::logger;
     use std::env;
+    use win_wrap::com::co_initialize_multi_thread;
 
     logger::init_logger(Some(format!("{}.log", module_path!()).as_str()));
     put_peeper32().await;
     peeper::mount();
+    // 初始化COM线程模型，sapi5等引擎需要使用COM
+    let res = co_initialize_multi_thread();
+    if res.is_err() {
+        log::error!("Can't initialize the com environment. {}", res.message());
+    }
 
     let mut server = Proxy32Server::new(env::args().nth(1).unwrap().as_str()).await;
     server.run().await;
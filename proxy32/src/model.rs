@@ -14,58 +14,79 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display, Formatter};
 
-//noinspection SpellCheckingInspection
+/**
+ * 托管在proxy32中的语音引擎的信息。
+ * */
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
-pub struct IbmeciVoiceParams {
-    pub gender: i32,
-    pub head_size: i32,
-    pub pitch_baseline: i32,
-    pub pitch_fluctuation: i32,
-    pub roughness: i32,
-    pub breathiness: i32,
-    pub speed: i32,
-    pub volume: i32,
+pub struct EngineInfo {
+    /// 引擎名称，同时也是请求中用来指定引擎的标识
+    pub name: String,
+    /// 合成的音频数据的采样率，音频格式都是16位的pcm
+    pub sample_rate: u32,
+    /// 合成的音频数据的声道数
+    pub channels: u32,
 }
 
-impl Default for IbmeciVoiceParams {
-    fn default() -> Self {
-        Self {
-            gender: 0,
-            head_size: 50,
-            pitch_baseline: 69,
-            pitch_fluctuation: 69,
-            roughness: 0,
-            breathiness: 0,
-            speed: 50,
-            volume: 92,
+/**
+ * 语音引擎的参数，取值范围都是1到100，和读屏的语音配置一致，由各个引擎的适配器转换成引擎自己的取值。
+ * 值为None的参数保持不变。
+ * */
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct EngineParams {
+    pub speed: Option<i32>,
+    pub volume: Option<i32>,
+    pub pitch: Option<i32>,
+}
+
+impl EngineParams {
+    /**
+     * 把另一组参数中设置过的值合并到当前参数中。
+     * `other` 另一组参数。
+     * */
+    pub fn merge(&mut self, other: &Self) {
+        if other.speed.is_some() {
+            self.speed = other.speed;
+        }
+        if other.volume.is_some() {
+            self.volume = other.volume;
+        }
+        if other.pitch.is_some() {
+            self.pitch = other.pitch;
         }
     }
 }
 
-//noinspection SpellCheckingInspection
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Proxy32Data {
     Quit,
     // 退出
-    EciSetParamsRequest(IbmeciVoiceParams),
-    // vvtts设置参数请求
-    EciSetParamsResponse(()),
-    // vvtts设置参数响应
-    EciGetParamsRequest,
-    // vvtts获取参数请求
-    EciGetParamsResponse(IbmeciVoiceParams),
-    // vvtts获取参数响应
-    EciSetVoiceRequest(u32),
-    // vvtts设置发音人请求
-    EciSetVoiceResponse(()),
-    // vvtts设置发音人响应
-    EciGetVoiceListRequest,
-    // vvtts获取发音人列表请求
-    EciGetVoiceListResponse(Vec<(u32, String)>),
-    // vvtts获取发音人列表响应
-    EciSynthRequest(String),
-    // vvtts合成请求
-    EciSynthResponse(Vec<u8>), // vvtts合成响应
+    EngineListRequest,
+    // 获取引擎列表请求
+    EngineListResponse(Vec<EngineInfo>),
+    // 获取引擎列表响应
+    VoiceListRequest(String),
+    // 获取发音人列表请求，参数是引擎名称
+    VoiceListResponse(Vec<(String, String)>),
+    // 获取发音人列表响应，每一项是发音人的id和名称
+    SetVoiceRequest(String, String),
+    // 设置发音人请求，参数是引擎名称和发音人id
+    SetVoiceResponse(()),
+    // 设置发音人响应
+    SetParamsRequest(String, EngineParams),
+    // 设置语音参数请求，参数是引擎名称和语音参数
+    SetParamsResponse(()),
+    // 设置语音参数响应
+    SynthStreamRequest(String, String),
+    // 流式合成请求，参数是引擎名称和文字，服务端会使用相同的id返回多个SynthChunk，最后返回SynthStreamEnd
+    SynthChunk(Vec<u8>),
+    // 流式合成的数据块
+    SynthStreamEnd,
+    // 流式合成结束（合成完毕、已经被取消或者引擎不存在）
+    SynthCancelRequest(u32),
+    // 取消合成请求，参数是流式合成请求的id
+    SynthCancelResponse(()),
+    // 取消合成响应
+    Heartbeat, // 心跳，服务端会原样返回，用于检测proxy32是否还能正常响应
 }
 
 impl Display for Proxy32Data {
@@ -12,23 +12,39 @@
  */
 
 use crate::client::Proxy32Client;
+use log::{error, info, warn};
 use std::{
+    fmt::{Debug, Formatter},
     future::Future,
     pin::Pin,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc, Mutex as StdMutex, RwLock as StdRwLock,
+    },
     task::{Context, Poll},
 };
 use tokio::{
     process::Child,
-    sync::{OnceCell, RwLock},
+    sync::RwLock,
+    time::{sleep, timeout, Duration},
 };
 
 const PIPE_NAME: &str = r"\\.\PIPE\RIGELA_PROXY32";
+// 检查proxy32是否存活的间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+// 连续多少次心跳失败才认为proxy32已经卡死
+const MAX_HEARTBEAT_FAILURES: u32 = 2;
+// 等待proxy32的管道连接成功的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// 重启proxy32的最短和最长等待时间，每次连续重启的等待时间都会翻倍
+const MIN_RESPAWN_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESPAWN_DELAY: Duration = Duration::from_secs(30);
 
-#[derive(Debug)]
 pub struct Proxy32Process {
     process: RwLock<Option<Child>>,
-    client: OnceCell<Arc<Proxy32Client>>,
+    client: StdRwLock<Option<Arc<Proxy32Client>>>,
+    quitting: AtomicBool,
+    outage_listeners: StdMutex<Vec<Box<dyn Fn() + Send + Sync>>>,
 }
 
 impl Proxy32Process {
@@ -38,7 +54,9 @@ impl Proxy32Process {
     pub fn new() -> Self {
         Self {
             process: None.into(),
-            client: OnceCell::new(),
+            client: None.into(),
+            quitting: false.into(),
+            outage_listeners: vec![].into(),
         }
     }
 
@@ -46,10 +64,8 @@ impl Proxy32Process {
      * 创建进程。
      * */
     pub async fn spawn(&self) -> &Self {
-        use log::{error, info};
         use rigela_utils::fs::{get_file_modified_duration, get_rigela_program_directory, write_file};
-        use std::time::Duration;
-        use tokio::{process::Command, time::sleep};
+        use tokio::process::Command;
 
         // 获取proxy32.exe的二进制数据并写入到用户目录中，原理是在编译时把proxy32的数据使用include_bytes!内嵌到64位的主程序内部，在运行时释放到磁盘。
         // 注意：这里使用条件编译的方法，确保include_bytes!仅出现一次，不能使用if语句，那样会多次包含bytes，main.exe的大小会成倍增长。
@@ -85,23 +101,100 @@ impl Proxy32Process {
             let mut process = self.process.write().await;
             *process = Some(cmd).into();
         }
-        if self
-            .client
-            .set(Proxy32Client::new(PIPE_NAME).await.into())
-            .is_err()
-        {
-            error!("Can't set client field of the proxy32.");
+        // 如果进程启动后立即崩溃，管道永远不会创建，所以连接需要超时，之后交给supervise重启
+        match timeout(CONNECT_TIMEOUT, Proxy32Client::new(PIPE_NAME)).await {
+            Ok(client) => *self.client.write().unwrap() = Some(client.into()),
+            Err(_) => error!("Can't connect to the proxy32 process."),
         }
         self
     }
 
+    /**
+     * 监督进程的运行状态，这会创建一个循环，定期发送心跳；
+     * 如果进程已经退出或者不再响应，就通知所有的故障监听器，然后按照退避策略重启进程，并恢复之前设置的发音人和语音参数。
+     * 调用kill方法后循环结束。
+     * */
+    pub async fn supervise(&self) {
+        let mut failures = 0;
+        let mut delay = MIN_RESPAWN_DELAY;
+        loop {
+            sleep(HEARTBEAT_INTERVAL).await;
+            if self.quitting.load(SeqCst) {
+                break;
+            }
+            let exited = match self.process.write().await.as_mut() {
+                Some(p) => !matches!(p.try_wait(), Ok(None)),
+                None => true,
+            };
+            let client = self.get_client();
+            let alive = !exited
+                && match client.as_ref() {
+                    Some(c) => c.heartbeat().await,
+                    None => false,
+                };
+            if alive {
+                failures = 0;
+                delay = MIN_RESPAWN_DELAY;
+                continue;
+            }
+            failures += 1;
+            if !exited && failures < MAX_HEARTBEAT_FAILURES {
+                warn!("The proxy32 process didn't respond to the heartbeat.");
+                continue;
+            }
+            if self.quitting.load(SeqCst) {
+                break;
+            }
+
+            let reason = if exited {
+                "exited"
+            } else {
+                "stopped responding"
+            };
+            error!(
+                "The proxy32 process has {}, it will be restarted in {:?}.",
+                reason, delay
+            );
+            for listener in self.outage_listeners.lock().unwrap().iter() {
+                listener();
+            }
+            if let Some(p) = self.process.write().await.as_mut() {
+                // 杀死卡死的进程，管道随之断开，旧的客户端上等待中的请求会立即返回
+                p.kill().await.unwrap_or(());
+            }
+            sleep(delay).await;
+            delay = (delay * 2).min(MAX_RESPAWN_DELAY);
+            failures = 0;
+            if self.quitting.load(SeqCst) {
+                break;
+            }
+            self.spawn().await;
+            if let (Some(old), Some(new)) = (client, self.get_client()) {
+                if !Arc::ptr_eq(&old, &new) {
+                    new.restore_voice_from(&old).await;
+                }
+            }
+        }
+    }
+
+    /**
+     * 添加一个监听器，当进程意外退出或者不再响应时发出通知。
+     * `listener` 一个监听函数。
+     * */
+    pub fn add_on_outage_listener(&self, listener: impl Fn() + Send + Sync + 'static) {
+        self.outage_listeners
+            .lock()
+            .unwrap()
+            .push(Box::new(listener));
+    }
+
     /**
      * 杀死进程。
      * */
     pub async fn kill(&self) -> &Self {
-        use log::{error, info};
-
-        if let Some(x) = self.client.get() {
+        // 先标记为正在退出，避免supervise把进程重新启动
+        self.quitting.store(true, SeqCst);
+        if let Some(x) = self.get_client() {
             x.quit().await;
         }
         let mut process = self.process.write().await;
@@ -110,7 +203,7 @@ impl Proxy32Process {
                 Ok(s) => {
                     info!(
                         "The process has exited successfully. Exit code is {}.",
-                        s.code().unwrap()
+                        s.code().unwrap_or(-1)
                     );
                 }
                 Err(e) => {
@@ -130,18 +223,28 @@ impl Proxy32Process {
             x.wait().await.unwrap();
         }
     }
+
+    fn get_client(&self) -> Option<Arc<Proxy32Client>> {
+        self.client.read().unwrap().clone()
+    }
+}
+
+impl Debug for Proxy32Process {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Proxy32Process")
+    }
 }
 
 impl Future for &Proxy32Process {
     type Output = Arc<Proxy32Client>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.client.get() {
+        match self.get_client() {
             None => {
                 cx.waker().wake_by_ref();
                 Poll::Pending
             }
-            Some(c) => Poll::Ready(c.clone()),
+            Some(c) => Poll::Ready(c),
         }
     }
 }
@@ -11,20 +11,26 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
-use crate::model::{IbmeciVoiceParams, Proxy32Data, Proxy32Packet};
+use crate::{
+    engines::EngineRegistry,
+    model::{EngineParams, Proxy32Data, Proxy32Packet},
+};
 use log::error;
-use rigela_utils::{
-    ibmeci::{
-        Ibmeci, VP_BREATHINESS, VP_GENDER, VP_HEAD_SIZE, VP_PITCH_BASELINE, VP_PITCH_FLUCTUATION,
-        VP_ROUGHNESS, VP_SPEED, VP_VOLUME,
-    },
-    pipe::{server_run, PipeStream, PipeStreamError},
+use rigela_utils::pipe::{
+    server_run, PipeFormat, PipeStreamError, PipeStreamReader, PipeStreamWriter, Transport,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{
+    join, select, spawn,
+    sync::{mpsc::unbounded_channel, oneshot, Mutex},
 };
-use tokio::net::windows::named_pipe::NamedPipeServer;
 
 //noinspection SpellCheckingInspection
 pub struct Proxy32Server {
-    stream: PipeStream<Proxy32Packet, NamedPipeServer>,
+    reader: PipeStreamReader<Proxy32Packet, Transport>,
+    writer: Arc<Mutex<PipeStreamWriter<Proxy32Packet, Transport>>>,
+    cancellations: Arc<Mutex<HashMap<u32, oneshot::Sender<()>>>>,
+    engines: Arc<EngineRegistry>,
 }
 
 impl Proxy32Server {
@@ -33,22 +39,38 @@ impl Proxy32Server {
      * `pipe_name` 管道名称，需要与客户端使用的名称相同。
      * */
     pub async fn new(pipe_name: &str) -> Self {
-        let stream = server_run(pipe_name).await;
-        Self { stream }
+        // 先加载引擎再创建管道，这样客户端连接成功后就能立即使用所有的引擎
+        let engines = EngineRegistry::new().await;
+        let mut stream = server_run(pipe_name).await;
+        if let Err(e) = stream.accept_handshake(PipeFormat::preferred()).await {
+            error!("Can't negotiate with the proxy32 client. {}", e);
+        }
+        let (reader, writer) = stream.split();
+        Self {
+            reader,
+            writer: Arc::new(writer.into()),
+            cancellations: Default::default(),
+            engines: engines.into(),
+        }
     }
 
     /**
      * 运行proxy32服务端，这会创建一个循环，不断地接收来自客户端的命令，然后把处理结果返回。
+     * 流式合成请求会在后台任务中处理，因此在合成的过程中依然可以接收取消等其他命令。
      * */
     pub async fn run(&mut self) {
         loop {
-            let packet = self.stream.recv().await;
+            let packet = self.reader.recv().await;
             match packet {
                 Err(PipeStreamError::ReadEof) => break,
+                Ok(Proxy32Packet {
+                    id,
+                    data: Proxy32Data::SynthStreamRequest(engine, text),
+                }) => self.synth_stream(id, engine, text).await,
                 Ok(p) => {
                     let data = self.on_exec(&p.data).await;
                     let packet = Proxy32Packet { id: p.id, data };
-                    if let Err(e) = self.stream.send(&packet).await {
+                    if let Err(e) = self.writer.lock().await.send(&packet).await {
                         error!("{}", e);
                     }
                     if Proxy32Data::Quit == packet.data {
@@ -62,63 +84,95 @@ impl Proxy32Server {
 
     async fn on_exec(&self, data: &Proxy32Data) -> Proxy32Data {
         match data {
-            Proxy32Data::EciSynthRequest(text) => {
-                Proxy32Data::EciSynthResponse(self.eci_synth(text).await)
+            Proxy32Data::EngineListRequest => {
+                Proxy32Data::EngineListResponse(self.engines.get_engines())
             }
-            Proxy32Data::EciSetParamsRequest(params) => {
-                Proxy32Data::EciSetParamsResponse(self.eci_set_voice_params(params).await)
+            Proxy32Data::VoiceListRequest(engine) => {
+                Proxy32Data::VoiceListResponse(self.get_voices(engine))
             }
-            Proxy32Data::EciGetParamsRequest => {
-                Proxy32Data::EciGetParamsResponse(self.eci_get_voice_params().await)
+            Proxy32Data::SetVoiceRequest(engine, id) => {
+                Proxy32Data::SetVoiceResponse(self.set_voice(engine, id))
             }
-            Proxy32Data::EciSetVoiceRequest(v) => {
-                Proxy32Data::EciSetVoiceResponse(self.eci_set_voice(v.clone()).await)
+            Proxy32Data::SetParamsRequest(engine, params) => {
+                Proxy32Data::SetParamsResponse(self.set_params(engine, params))
             }
-            Proxy32Data::EciGetVoiceListRequest => {
-                Proxy32Data::EciGetVoiceListResponse(self.eci_get_voices().await)
+            Proxy32Data::SynthCancelRequest(id) => {
+                Proxy32Data::SynthCancelResponse(self.synth_cancel(*id).await)
             }
+            Proxy32Data::Heartbeat => Proxy32Data::Heartbeat,
             _ => data.clone(),
         }
     }
 
-    async fn eci_synth(&self, text: &str) -> Vec<u8> {
-        let eci = Ibmeci::get().await.unwrap();
-        eci.synth(text).await
+    fn get_voices(&self, engine: &str) -> Vec<(String, String)> {
+        match self.engines.get(engine) {
+            Some(e) => e.get_voices(),
+            None => vec![],
+        }
     }
 
-    async fn eci_set_voice_params(&self, params: &IbmeciVoiceParams) {
-        let eci = Ibmeci::get().await.unwrap();
-        eci.set_voice_param(VP_GENDER, params.gender);
-        eci.set_voice_param(VP_VOLUME, params.volume);
-        eci.set_voice_param(VP_SPEED, params.speed);
-        eci.set_voice_param(VP_PITCH_FLUCTUATION, params.pitch_fluctuation);
-        eci.set_voice_param(VP_PITCH_BASELINE, params.pitch_baseline);
-        eci.set_voice_param(VP_BREATHINESS, params.breathiness);
-        eci.set_voice_param(VP_HEAD_SIZE, params.head_size);
-        eci.set_voice_param(VP_ROUGHNESS, params.roughness);
+    fn set_voice(&self, engine: &str, id: &str) {
+        if let Some(e) = self.engines.get(engine) {
+            e.set_voice(id);
+        }
     }
 
-    async fn eci_get_voice_params(&self) -> IbmeciVoiceParams {
-        let eci = Ibmeci::get().await.unwrap();
-        IbmeciVoiceParams {
-            gender: eci.get_voice_param(VP_GENDER),
-            head_size: eci.get_voice_param(VP_HEAD_SIZE),
-            pitch_baseline: eci.get_voice_param(VP_PITCH_BASELINE),
-            pitch_fluctuation: eci.get_voice_param(VP_PITCH_FLUCTUATION),
-            roughness: eci.get_voice_param(VP_ROUGHNESS),
-            breathiness: eci.get_voice_param(VP_BREATHINESS),
-            speed: eci.get_voice_param(VP_SPEED),
-            volume: eci.get_voice_param(VP_VOLUME),
+    fn set_params(&self, engine: &str, params: &EngineParams) {
+        if let Some(e) = self.engines.get(engine) {
+            e.set_params(params);
         }
     }
 
-    async fn eci_get_voices(&self) -> Vec<(u32, String)> {
-        let eci = Ibmeci::get().await.unwrap();
-        eci.get_voices()
+    async fn synth_stream(&self, id: u32, engine: String, text: String) {
+        let (cancel_tx, mut cancel_rx) = oneshot::channel();
+        self.cancellations.lock().await.insert(id, cancel_tx);
+        let writer = self.writer.clone();
+        let cancellations = self.cancellations.clone();
+        let engines = self.engines.clone();
+        spawn(async move {
+            let (tx, mut rx) = unbounded_channel();
+            let forward = async {
+                loop {
+                    select! {
+                        _ = &mut cancel_rx => break,
+                        chunk = rx.recv() => {
+                            let Some(chunk) = chunk else {
+                                break;
+                            };
+                            let packet = Proxy32Packet {
+                                id,
+                                data: Proxy32Data::SynthChunk(chunk),
+                            };
+                            if let Err(e) = writer.lock().await.send(&packet).await {
+                                error!("{}", e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                // 丢弃接收端，引擎发现后会中止剩余的合成工作
+                drop(rx);
+            };
+            match engines.get(&engine) {
+                Some(e) => {
+                    join!(e.synth(&text, tx), forward);
+                }
+                None => error!("The engine `{}` is not found.", engine),
+            }
+            cancellations.lock().await.remove(&id);
+            let packet = Proxy32Packet {
+                id,
+                data: Proxy32Data::SynthStreamEnd,
+            };
+            if let Err(e) = writer.lock().await.send(&packet).await {
+                error!("{}", e);
+            }
+        });
     }
 
-    async fn eci_set_voice(&self, voice_id: u32) {
-        let eci = Ibmeci::get().await.unwrap();
-        eci.set_voice(voice_id)
+    async fn synth_cancel(&self, id: u32) {
+        if let Some(tx) = self.cancellations.lock().await.remove(&id) {
+            tx.send(()).unwrap_or(());
+        }
     }
 }
@@ -12,9 +12,11 @@
  */
 
 use std::{
-    fs::create_dir,
+    env::{current_exe, var_os},
+    fs::{create_dir_all, read_to_string},
     io::Error,
-    path::{Path, PathBuf},
+    path::{absolute, Path, PathBuf},
+    sync::OnceLock,
 };
 use tokio::{
     fs::{metadata, OpenOptions},
@@ -24,16 +26,78 @@ use win_wrap::shell::{get_known_folder_path, FOLDERID_Profile, KF_FLAG_DEFAULT};
 
 pub const DIR_NAME: &str = ".rigela";
 
-/// 获取程序存储目录
+/// 指定数据目录的环境变量名称
+pub const DATA_DIR_ENV: &str = "RIGELA_DATA_DIR";
+
+/// 便携模式的标记文件名称，放在可执行文件旁边，文件内容可以写数据目录的路径（相对路径相对于可执行文件所在的目录），留空则使用`PORTABLE_DIR_NAME`
+pub const PORTABLE_MARKER: &str = "rigela.portable";
+
+/// 便携模式默认的数据目录名称
+pub const PORTABLE_DIR_NAME: &str = "data";
+
+static PROGRAM_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/**
+ 指定程序存储目录，通常在解析命令行参数时调用，优先级高于环境变量和便携模式的标记文件。
+ 相对路径会转换成基于当前目录的绝对路径，并写入环境变量，这样启动的子进程也使用同一个目录。
+ 必须在第一次获取程序存储目录之前调用，否则不会生效并返回false。
+ `path` 数据目录的路径。
+ */
+pub fn set_rigela_program_directory(path: impl AsRef<Path>) -> bool {
+    let dir = to_absolute(path.as_ref());
+    if PROGRAM_DIRECTORY.set(dir.clone()).is_err() {
+        return false;
+    }
+    std::env::set_var(DATA_DIR_ENV, &dir);
+    true
+}
+
+/**
+ 获取程序存储目录，配置、日志、动态库和资源文件都保存在这里。
+ 按以下顺序确定：`set_rigela_program_directory`指定的目录、环境变量`RIGELA_DATA_DIR`、可执行文件旁边的便携模式标记文件，最后是用户目录下的`.rigela`。
+ 确定以后会写入环境变量，这样启动的子进程（例如proxy32和升级程序）也使用同一个目录。
+ */
 pub fn get_rigela_program_directory() -> PathBuf {
-    let home_path = get_known_folder_path(&FOLDERID_Profile, KF_FLAG_DEFAULT, None).unwrap();
-    let program_dir = Path::new(&home_path).join(DIR_NAME);
+    let program_dir = PROGRAM_DIRECTORY.get_or_init(|| {
+        let dir = var_os(DATA_DIR_ENV)
+            .filter(|v| !v.is_empty())
+            .map(|v| to_absolute(Path::new(&v)))
+            .or_else(get_portable_directory)
+            .unwrap_or_else(|| {
+                let home_path =
+                    get_known_folder_path(&FOLDERID_Profile, KF_FLAG_DEFAULT, None).unwrap();
+                Path::new(&home_path).join(DIR_NAME)
+            });
+        std::env::set_var(DATA_DIR_ENV, &dir);
+        dir
+    });
 
     if !program_dir.exists() {
-        create_dir(&program_dir).expect("Can't create the root directory.");
+        create_dir_all(program_dir).expect("Can't create the root directory.");
     }
 
-    program_dir
+    program_dir.clone()
+}
+
+// 转换成绝对路径，子进程的当前目录可能不同，相对路径在子进程中会指向别的目录
+fn to_absolute(path: &Path) -> PathBuf {
+    absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// 读取可执行文件旁边的便携模式标记文件，获取数据目录
+fn get_portable_directory() -> Option<PathBuf> {
+    let exe_dir = current_exe().ok()?.parent()?.to_path_buf();
+    let content = read_to_string(exe_dir.join(PORTABLE_MARKER)).ok()?;
+    Some(parse_portable_marker(&exe_dir, &content))
+}
+
+// 根据便携模式标记文件的内容确定数据目录，相对路径相对于可执行文件所在的目录
+fn parse_portable_marker(exe_dir: &Path, content: &str) -> PathBuf {
+    let name = match content.trim() {
+        "" => PORTABLE_DIR_NAME,
+        x => x,
+    };
+    to_absolute(&exe_dir.join(name))
 }
 
 /**
@@ -83,3 +147,39 @@ pub async fn read_file(path: &PathBuf) -> Result<String, Error> {
         .await?;
     Ok(result)
 }
+
+#[cfg(test)]
+mod test_fs {
+    use crate::fs::{parse_portable_marker, to_absolute, PORTABLE_DIR_NAME};
+    use std::{env::current_dir, path::Path};
+
+    #[test]
+    fn test_portable_marker() {
+        let exe_dir = current_dir().unwrap().join("rigela");
+        assert_eq!(
+            exe_dir.join(PORTABLE_DIR_NAME),
+            parse_portable_marker(&exe_dir, "")
+        );
+        // 只有空白字符也视为空
+        assert_eq!(
+            exe_dir.join(PORTABLE_DIR_NAME),
+            parse_portable_marker(&exe_dir, " \r\n")
+        );
+        assert_eq!(
+            exe_dir.join("profile"),
+            parse_portable_marker(&exe_dir, "profile\n")
+        );
+        let absolute = current_dir().unwrap().join("elsewhere");
+        assert_eq!(
+            absolute,
+            parse_portable_marker(&exe_dir, absolute.to_str().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_to_absolute() {
+        let path = to_absolute(Path::new("data"));
+        assert!(path.is_absolute());
+        assert_eq!(current_dir().unwrap().join("data"), path);
+    }
+}
@@ -18,10 +18,14 @@ use std::{
     alloc::{alloc_zeroed, dealloc, Layout},
     borrow::Cow,
     ffi::{c_char, CString},
+    mem::take,
     sync::OnceLock,
     thread,
 };
-use tokio::sync::oneshot::{self, channel, Sender};
+use tokio::sync::{
+    mpsc::UnboundedSender,
+    oneshot::{self, channel, Sender},
+};
 use win_wrap::{
     common::{free_library, get_proc_address, load_library, FARPROC, LPARAM, WPARAM},
     message::{message_loop, post_thread_message, register_window_message},
@@ -146,7 +150,6 @@ const MSG_WORD_INDEX_REPLY: u32 = 4;
 const RETURN_DATA_NOT_PROCESSED: u32 = 0;
 #[allow(unused)]
 const RETURN_DATA_PROCESSED: u32 = 1;
-#[allow(unused)]
 const RETURN_DATA_ABORT: u32 = 2;
 
 // Voice params
@@ -160,6 +163,26 @@ pub const VP_BREATHINESS: u32 = 5;
 pub const VP_SPEED: u32 = 6;
 pub const VP_VOLUME: u32 = 7;
 
+/**
+把读屏的语速（0到100）转换成`VP_SPEED`的取值（0到250）。
+`value` 读屏的语速。
+*/
+pub fn convert_speed_param(value: i32) -> i32 {
+    (value as f32 * 2.5) as i32
+}
+
+/**
+把读屏的音调（0到100）转换成`VP_PITCH_BASELINE`和`VP_PITCH_FLUCTUATION`的取值，50对应引擎默认的69，100对应最大值100。
+`value` 读屏的音调。
+*/
+pub fn convert_pitch_param(value: i32) -> i32 {
+    if value > 50 {
+        (69f32 + (value as f32 - 50f32) / 50f32 * 31f32) as i32
+    } else {
+        ((value as f32) / 50f32 * 69f32) as i32
+    }
+}
+
 //noinspection SpellCheckingInspection
 static mut IBMECI: OnceLock<Ibmeci> = OnceLock::new();
 static SYNTH_TASK: OnceLock<u32> = OnceLock::new();
@@ -184,17 +207,30 @@ extern "system" fn _callback_internal(
         for i in 0..(param * 2) {
             vec.push(*eci.buffer_ptr.wrapping_add(i as usize));
         }
-        eci.data.extend(vec);
+        match eci.chunk_sender.as_ref() {
+            // 流式合成时直接把数据块发送出去，如果接收端已经被丢弃，说明合成已经被取消，需要中止剩余的合成
+            Some(tx) if tx.send(vec).is_err() => return RETURN_DATA_ABORT,
+            Some(_) => {}
+            None => eci.data.extend(vec),
+        }
     }
     RETURN_DATA_PROCESSED
 }
 
+// 合成任务，通过线程消息传递到合成线程中执行
+struct SynthTask {
+    text: Vec<u8>,
+    chunk_sender: Option<UnboundedSender<Vec<u8>>>,
+    finished: Sender<Vec<u8>>,
+}
+
 //noinspection SpellCheckingInspection
 #[derive(Debug)]
 pub struct Ibmeci {
     buffer_layout: Layout,
     buffer_ptr: *mut u8,
     data: Vec<u8>,
+    chunk_sender: Option<UnboundedSender<Vec<u8>>>,
     h_module: SafeModuleHandle,
     h_eci: i32,
     thread: u32,
@@ -237,6 +273,7 @@ impl Ibmeci {
                 buffer_layout,
                 buffer_ptr,
                 data: vec![],
+                chunk_sender: None,
                 h_module: h_module.clone(),
                 h_eci,
                 thread: get_current_thread_id(),
@@ -257,12 +294,18 @@ impl Ibmeci {
             }
             message_loop(|m| {
                 if wm!(SYNTH_TASK) == m.message {
-                    let b = unsafe { Box::from_raw(m.wParam.0 as *mut Cow<[u8]>) };
-                    eci!(*h_module, add_text, h_eci, *b);
+                    let task = unsafe { Box::from_raw(m.wParam.0 as *mut SynthTask) };
+                    let Some(eci) = (unsafe { IBMECI.get_mut() }) else {
+                        return;
+                    };
+                    // 回调函数在本线程的synchronize中被调用，所以在这里切换数据的去向是安全的
+                    eci.data.clear();
+                    eci.chunk_sender = task.chunk_sender;
+                    eci!(*h_module, add_text, h_eci, task.text);
                     eci!(*h_module, synthesize, h_eci);
                     eci!(*h_module, synchronize, h_eci);
-                    let b = unsafe { Box::from_raw(m.lParam.0 as *mut Sender<()>) };
-                    b.send(()).unwrap_or(());
+                    eci.chunk_sender = None;
+                    task.finished.send(take(&mut eci.data)).unwrap_or(());
                 }
             });
         });
@@ -277,60 +320,79 @@ impl Ibmeci {
     `text` 要合成的文字。
     */
     pub async fn synth(&self, text: &str) -> Vec<u8> {
+        self.post_synth_task(text, None).await
+    }
+
+    /**
+    流式合成语音，每当合成出一块数据就立即发送出去，合成完毕后本方法才返回。
+    如果在合成过程中丢弃了接收端，剩余的合成工作将被中止。
+    `text` 要合成的文字。
+    `sender` 用于发送音频数据块的通道。
+    */
+    pub async fn synth_stream(&self, text: &str, sender: UnboundedSender<Vec<u8>>) {
+        self.post_synth_task(text, Some(sender)).await;
+    }
+
+    async fn post_synth_task(
+        &self,
+        text: &str,
+        chunk_sender: Option<UnboundedSender<Vec<u8>>>,
+    ) -> Vec<u8> {
         eci!(*self.h_module, stop, self.h_eci);
+        let (tx, rx) = channel();
+        let task = SynthTask {
+            text: Self::encode_text(text).into_owned(),
+            chunk_sender,
+            finished: tx,
+        };
+        post_thread_message(
+            self.thread,
+            wm!(SYNTH_TASK),
+            WPARAM(Box::into_raw(Box::new(task)) as usize),
+            LPARAM::default(),
+        );
+        rx.await.unwrap_or_default()
+    }
+
+    // 把文字编码成gbk，并过滤掉不能被编码的字符
+    fn encode_text(text: &str) -> Cow<[u8]> {
         let (text, _, unmapped) = GBK.encode(text);
-        let text = if unmapped {
-            // 如果有不能被编码成gbk的字符，我们需要过滤他们
-            let mut v = vec![];
-            let mut u = vec![];
-            let mut has_html_char = false;
-            let mut last_char = 0u8;
-            for i in text.iter() {
-                let i = i.clone();
-                if last_char == 38 {
-                    has_html_char = i == 35u8;
-                    if has_html_char {
-                        u.clear();
-                        u.push(last_char);
-                        u.push(i);
-                    } else {
-                        v.push(last_char);
-                        v.push(i);
-                    }
+        if !unmapped {
+            return text;
+        }
+        // 如果有不能被编码成gbk的字符，我们需要过滤他们
+        let mut v = vec![];
+        let mut u = vec![];
+        let mut has_html_char = false;
+        let mut last_char = 0u8;
+        for i in text.iter() {
+            let i = i.clone();
+            if last_char == 38 {
+                has_html_char = i == 35u8;
+                if has_html_char {
+                    u.clear();
+                    u.push(last_char);
+                    u.push(i);
                 } else {
-                    if has_html_char {
-                        u.push(i);
-                        if i == 59u8 {
-                            has_html_char = false;
-                        } else if !(i >= 48u8 && i <= 57u8) {
-                            v.extend(&u);
-                            has_html_char = false;
-                        }
-                    } else if i != 38 {
-                        v.push(i);
+                    v.push(last_char);
+                    v.push(i);
+                }
+            } else {
+                if has_html_char {
+                    u.push(i);
+                    if i == 59u8 {
+                        has_html_char = false;
+                    } else if !(i >= 48u8 && i <= 57u8) {
+                        v.extend(&u);
+                        has_html_char = false;
                     }
+                } else if i != 38 {
+                    v.push(i);
                 }
-                last_char = i;
             }
-            Cow::from(v)
-        } else {
-            text
-        };
-        if let Some(eci) = unsafe { IBMECI.get_mut() } {
-            eci.data.clear();
-            let (tx, rx) = channel();
-            let tx = Box::new(tx);
-            post_thread_message(
-                eci.thread,
-                wm!(SYNTH_TASK),
-                WPARAM(Box::into_raw(Box::new(text)) as usize),
-                LPARAM(Box::into_raw(tx) as isize),
-            );
-            rx.await.unwrap_or(());
-            eci.data.clone()
-        } else {
-            vec![]
+            last_char = i;
         }
+        Cow::from(v)
     }
 
     /**
@@ -11,18 +11,16 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
-use crate::pipe::{server_run, PipeStream};
+use crate::pipe::{connect, server_run, PipeStream};
 use log::error;
 use serde::{Deserialize, Serialize};
-use tokio::{
-    net::windows::named_pipe::ClientOptions,
-    time::{sleep, Duration},
-};
+use std::process;
+#[cfg(not(windows))]
+use tokio::time::{sleep, Duration};
+#[cfg(windows)]
 use win_wrap::{
     common::close_handle,
-    threading::{
-        get_current_process_id, open_process, wait_for_single_object, PROCESS_SYNCHRONIZE,
-    },
+    threading::{open_process, wait_for_single_object, PROCESS_SYNCHRONIZE},
 };
 
 const PIPE_NAME: &str = r"\\.\PIPE\RIGELA_KILLER";
@@ -31,34 +29,140 @@ const PIPE_NAME: &str = r"\\.\PIPE\RIGELA_KILLER";
 enum KillSignal {
     Request,
     Response(u32),
+    Ping,
 }
 
+/**
+等待其他进程请求当前进程退出，收到请求后返回，调用者负责退出程序。
+其他进程只是检测当前进程是否正在运行时，回复后继续等待。
+*/
 pub async fn wait_until_killed() {
-    let mut stream = server_run::<KillSignal>(PIPE_NAME).await;
-    while let Ok(_) = stream.recv().await {
-        break;
+    wait_until_killed_at(PIPE_NAME).await
+}
+
+async fn wait_until_killed_at(endpoint: &str) {
+    loop {
+        let mut stream = server_run::<KillSignal>(endpoint).await;
+        let Ok(signal) = stream.recv().await else {
+            continue;
+        };
+        stream
+            .send(&KillSignal::Response(process::id()))
+            .await
+            .unwrap_or(());
+        if let KillSignal::Request = signal {
+            break;
+        }
     }
-    stream
-        .send(&KillSignal::Response(get_current_process_id()))
-        .await
-        .unwrap_or(());
 }
 
-pub async fn kill() {
-    let mut stream = match ClientOptions::new().open(PIPE_NAME) {
-        Ok(x) => PipeStream::new(x),
-        Err(_) => return,
+/**
+检测是否有其他进程正在等待退出请求，也就是读屏是否已经在运行。
+*/
+pub async fn is_running() -> bool {
+    is_running_at(PIPE_NAME).await
+}
+
+async fn is_running_at(endpoint: &str) -> bool {
+    let mut stream = match connect(endpoint).await {
+        Ok(x) => PipeStream::<KillSignal, _>::new(x),
+        Err(_) => return false,
     };
+    if stream.send(&KillSignal::Ping).await.is_err() {
+        return false;
+    }
+    matches!(stream.recv().await, Ok(KillSignal::Response(_)))
+}
+
+/**
+请求正在运行的进程退出，并等待它退出。
+*/
+pub async fn kill() {
+    if let Some(pid) = request_kill(PIPE_NAME).await {
+        wait_for_exit(pid).await;
+    }
+}
+
+// 发送退出请求，返回对方的进程id
+async fn request_kill(endpoint: &str) -> Option<u32> {
+    let mut stream = PipeStream::new(connect(endpoint).await.ok()?);
 
     if let Err(e) = stream.send(&KillSignal::Request).await {
         error!("{}", e);
     }
-    if let Ok(KillSignal::Response(pid)) = stream.recv().await {
-        if let Ok(handle) = open_process(PROCESS_SYNCHRONIZE, false, pid) {
-            wait_for_single_object(handle, 5000);
-            close_handle(handle);
-        }
+    match stream.recv().await {
+        Ok(KillSignal::Response(pid)) => Some(pid),
+        _ => None,
     }
+}
 
+// 等待进程退出，最多等待5秒
+#[cfg(windows)]
+async fn wait_for_exit(pid: u32) {
+    if let Ok(handle) = open_process(PROCESS_SYNCHRONIZE, false, pid) {
+        wait_for_single_object(handle, 5000);
+        close_handle(handle);
+    }
+}
+
+// 其他平台上只能等待固定的时间
+#[cfg(not(windows))]
+async fn wait_for_exit(_pid: u32) {
     sleep(Duration::from_millis(1000)).await;
 }
+
+#[cfg(test)]
+mod test_killer {
+    use crate::killer::{is_running_at, request_kill, wait_until_killed_at};
+    use std::{process, time::Duration};
+    use tokio::time::{sleep, timeout};
+
+    fn endpoint(name: &str) -> String {
+        #[cfg(windows)]
+        return format!(r"\\.\PIPE\rigela-test-killer-{}-{}", name, process::id());
+        #[cfg(not(windows))]
+        return format!(
+            "unix://{}",
+            std::env::temp_dir()
+                .join(format!(
+                    "rigela-test-killer-{}-{}.sock",
+                    name,
+                    process::id()
+                ))
+                .display()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kill() {
+        let endpoint = endpoint("kill");
+        assert!(!is_running_at(&endpoint).await);
+        let ep = endpoint.clone();
+        let server = tokio::spawn(async move { wait_until_killed_at(&ep).await });
+        // 等待服务器开始监听，检测是否正在运行不会让它退出
+        let mut running = false;
+        for _ in 0..50 {
+            if is_running_at(&endpoint).await {
+                running = true;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(running);
+        assert!(!server.is_finished());
+        // 服务器每次回复以后重新监听，连接失败时重试；请求退出时回复自己的进程id，然后结束等待
+        let mut pid = None;
+        for _ in 0..50 {
+            pid = request_kill(&endpoint).await;
+            if pid.is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(Some(process::id()), pid);
+        timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -32,6 +32,10 @@ pub mod library;
 pub mod logger;
 #[cfg(feature = "pipe")]
 pub mod pipe;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "rpc")]
+pub mod rpc;
 #[cfg(feature = "screen")]
 pub mod screen;
 
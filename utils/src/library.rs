@@ -14,7 +14,7 @@
 use crate::fs::get_rigela_program_directory;
 use log::error;
 use std::{
-    fs::{create_dir, OpenOptions},
+    fs::{create_dir, read, remove_file, rename, OpenOptions},
     io::Write,
     path::PathBuf,
 };
@@ -32,15 +32,36 @@ pub fn get_rigela_library_path() -> PathBuf {
 
 /**
 安装一个动态库文件到指定的目录。
+如果已经存在的文件与库的数据不同（通常是升级后遗留的旧版本），会替换成新的版本。
 `path` 动态库路径。
 `lib_bin` 库的二进制数据。
 */
 pub fn setup_library(path: &PathBuf, lib_bin: &[u8]) {
-    if path.exists() {
-        return;
+    // 清理上次替换时遗留的旧版本
+    let old_path = path.with_extension("old");
+    remove_file(&old_path).unwrap_or(());
+    match read(path) {
+        Ok(data) if data == lib_bin => return,
+        Ok(_) => {
+            // 正在被其他进程加载的库无法覆盖，但是可以改名，所以先把旧版本改名再写入新版本
+            if let Err(e) = rename(path, &old_path) {
+                error!(
+                    "Can't replace the stale `{}` library. {}",
+                    path.display(),
+                    e
+                );
+                return;
+            }
+        }
+        Err(_) => {}
     }
 
-    match OpenOptions::new().write(true).create(true).open(&path) {
+    match OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+    {
         Ok(mut f) => match f.write_all(lib_bin) {
             Ok(_) => {}
             Err(e) => error!("Can't setup the `{}` library. {}", path.display(), e),
@@ -12,35 +12,122 @@
  */
 
 use crate::fs::get_rigela_program_directory;
-use log::LevelFilter;
+use log::{error, LevelFilter};
 use log4rs::{
     append::{
         console::{ConsoleAppender, Target},
         rolling_file::{
             policy::compound::{
-                roll::delete::DeleteRoller, trigger::size::SizeTrigger, CompoundPolicy,
+                roll::{delete::DeleteRoller, fixed_window::FixedWindowRoller, Roll},
+                trigger::size::SizeTrigger,
+                CompoundPolicy,
             },
             RollingFileAppender,
         },
     },
-    config::{Appender, Config, Root},
+    config::{Appender, Config, Logger, Root},
     encode::pattern::PatternEncoder,
     filter::threshold::ThresholdFilter,
-    init_config,
+    init_config, Handle,
+};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
 const LOG_FILE_NAME: &str = "run.log";
 
+// 日志文件超过这个大小后滚动
+const ROLL_SIZE: u64 = 1024 * 1024;
+
+static LOGGER: OnceLock<(Handle, PathBuf)> = OnceLock::new();
+
+/// 日志的选项，可以在运行时修改
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogOptions {
+    /// 记录到日志文件的最低级别
+    pub level: LevelFilter,
+    /// 按模块设置的级别，键是模块路径，例如`rigela_main::performer`，它的子模块也使用这个级别
+    pub modules: BTreeMap<String, LevelFilter>,
+    /// 日志文件滚动后保留的归档数量，0表示滚动时直接删除
+    pub archives: u32,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Trace,
+            modules: Default::default(),
+            archives: 0,
+        }
+    }
+}
+
 /**
 初始化日志收集器。
 `path` 日志文件存放的文件夹路径，是相对于本项目用户目录的路径。
 */
 pub fn init_logger(path: Option<&str>) {
-    let level = LevelFilter::Info;
     let file_path = get_rigela_program_directory()
         .join("logs")
         .join(path.unwrap_or(LOG_FILE_NAME));
+    let config = build_config(&file_path, &LogOptions::default()).unwrap();
+
+    // 保留句柄，以便在运行时更改日志级别
+    let handle = init_config(config).expect("Can't initialize the logger.");
+    LOGGER.set((handle, file_path)).unwrap_or(());
+}
 
+/**
+修改日志的选项，立即生效，需要先调用`init_logger`。
+`options` 新的日志选项。
+*/
+pub fn set_log_options(options: &LogOptions) {
+    let Some((handle, file_path)) = LOGGER.get() else {
+        return;
+    };
+    match build_config(file_path, options) {
+        Ok(c) => handle.set_config(c),
+        Err(e) => error!("Can't change the log options. {}", e),
+    }
+}
+
+/**
+获取所有的日志文件，包括滚动的归档，按照从旧到新的顺序排列。
+*/
+pub fn get_log_files() -> Vec<PathBuf> {
+    let Some((_, file_path)) = LOGGER.get() else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = (0..)
+        .map(|i| get_archive_path(file_path, i))
+        .take_while(|p| p.exists())
+        .collect();
+    files.reverse();
+    if file_path.exists() {
+        files.push(file_path.clone());
+    }
+    files
+}
+
+// 获取第index个归档的路径，例如`run.0.log`，0是最新的归档
+fn get_archive_path(file_path: &Path, index: u32) -> PathBuf {
+    get_archive_pattern(file_path)
+        .replace("{}", &index.to_string())
+        .into()
+}
+
+fn get_archive_pattern(file_path: &Path) -> String {
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match file_path.extension() {
+        Some(ext) => format!("{}.{{}}.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.{{}}", stem),
+    };
+    file_path.with_file_name(name).to_string_lossy().to_string()
+}
+
+fn build_config(file_path: &Path, options: &LogOptions) -> Result<Config, String> {
     // 创建一个标准错误日志器
     let stderr = ConsoleAppender::builder()
         // Pattern: https://docs.rs/log4rs/*/log4rs/encode/pattern/index.html
@@ -48,33 +135,42 @@ pub fn init_logger(path: Option<&str>) {
         .target(Target::Stderr)
         .build();
 
-    //输出到文件
+    // 输出到文件，超过大小后滚动，保留指定数量的归档
+    let roller: Box<dyn Roll> = match options.archives {
+        0 => Box::new(DeleteRoller::new()),
+        n => Box::new(
+            FixedWindowRoller::builder()
+                .build(&get_archive_pattern(file_path), n)
+                .map_err(|e| e.to_string())?,
+        ),
+    };
     let logfile = RollingFileAppender::builder()
         .build(
             file_path,
             Box::new(CompoundPolicy::new(
-                Box::new(SizeTrigger::new(1024 * 1024)), // 超过1MB后滚动
-                Box::new(DeleteRoller::new()),
+                Box::new(SizeTrigger::new(ROLL_SIZE)),
+                roller,
             )),
         )
-        .unwrap();
+        .map_err(|e| e.to_string())?;
 
-    // 将跟踪级别输出记录到文件中，其中跟踪是默认级别，以编程方式指定的级别记录到stderr。
-    let config = Config::builder()
+    // 将选项中的级别记录到文件中，标准错误只输出info以上的级别
+    let mut builder = Config::builder()
         .appender(Appender::builder().build("logfile", Box::new(logfile)))
         .appender(
             Appender::builder()
-                .filter(Box::new(ThresholdFilter::new(level)))
+                .filter(Box::new(ThresholdFilter::new(LevelFilter::Info)))
                 .build("stderr", Box::new(stderr)),
-        )
+        );
+    for (module, level) in options.modules.iter() {
+        builder = builder.logger(Logger::builder().build(module, *level));
+    }
+    builder
         .build(
             Root::builder()
                 .appender("logfile")
                 .appender("stderr")
-                .build(LevelFilter::Trace),
+                .build(options.level),
         )
-        .unwrap();
-
-    // 使用此选项可以在运行时更改日志级别。这意味着您可以更改默认日志级别以进行跟踪，如果您正在尝试调试某个问题，并且需要打开更多日志，则在完成后将其关闭。
-    init_config(config).expect("Can't initialize the logger.");
+        .map_err(|e| e.to_string())
 }
@@ -11,21 +11,81 @@
  * See the License for the specific language governing permissions and limitations under the License.
  */
 
+mod transport;
+
+pub use transport::{connect, Endpoint, Transport, TransportListener};
+
+use bincode::{deserialize, serialize};
 use log::error;
 use serde::{Deserialize, Serialize};
 use serde_json_bytes::serde_json::{from_slice, to_vec};
-use std::fmt::{Display, Formatter};
+use std::{
+    env::var,
+    fmt::{Display, Formatter},
+};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
-    net::windows::named_pipe::{ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions},
+    io::{
+        split, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+        ReadHalf, WriteHalf,
+    },
     time::{sleep, Duration},
 };
 
+/// 管道通信协议的版本号，握手时双方的版本号必须相同
+pub const PIPE_PROTOCOL_VERSION: u32 = 1;
+
+// 二进制帧的最大长度，超过这个长度的帧被认为是损坏的数据
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
 /**
- * 连接到一个管道。
- * `pipe_name` 管道名称。
+ * 管道中数据包的编码格式。
  * */
-pub async fn client_connect<T>(pipe_name: &str) -> PipeStream<T, NamedPipeClient>
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum PipeFormat {
+    /// 以换行分隔的json文本，便于调试时观察
+    #[default]
+    Json,
+    /// 四字节长度前缀加上紧凑的二进制编码，字节数组按原样传输
+    Binary,
+}
+
+impl PipeFormat {
+    /**
+     * 获取本进程偏好的编码格式，默认使用二进制格式。
+     * 如果设置了环境变量`RIGELA_PIPE_FORMAT=json`，则使用json格式，方便调试。
+     * */
+    pub fn preferred() -> Self {
+        match var("RIGELA_PIPE_FORMAT") {
+            Ok(v) if v.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Binary,
+        }
+    }
+
+    /**
+     * 协商出双方都能接受的格式，只有双方都偏好二进制格式时才使用二进制格式。
+     * `other` 对方偏好的格式。
+     * */
+    pub fn negotiate(self, other: Self) -> Self {
+        if self == Self::Binary && other == Self::Binary {
+            Self::Binary
+        } else {
+            Self::Json
+        }
+    }
+}
+
+// 握手消息，无论协商结果如何，握手本身总是使用json格式
+#[derive(Deserialize, Serialize)]
+struct PipeHandshake {
+    version: u32,
+    format: PipeFormat,
+}
+
+/**
+ * 连接到一个管道，如果连接失败会一直重试。
+ * `endpoint` 端点的uri，可以是命名管道、Unix域套接字或者TCP回环地址，参考`Endpoint`。
+ * */
+pub async fn client_connect<T>(endpoint: &str) -> PipeStream<T, Transport>
 where
     T: for<'de> Deserialize<'de> + Serialize,
 {
@@ -34,10 +94,10 @@ where
         // 推迟一秒连接，尽量确保管道创建完毕
         sleep(Duration::from_millis(1000)).await;
 
-        match ClientOptions::new().open(pipe_name) {
+        match connect(endpoint).await {
             Ok(x) => break x,
             Err(e) => {
-                error!("Can't open the named pipe ({}). {}", pipe_name, e);
+                error!("Can't open the pipe ({}). {}", endpoint, e);
                 continue;
             }
         }
@@ -47,15 +107,14 @@ where
 
 /**
  * 创建一个管道服务器，并等待一个客户端连接。
- * `pipe_name` 管道名称。
+ * `endpoint` 端点的uri，可以是命名管道、Unix域套接字或者TCP回环地址，参考`Endpoint`。
  * */
-pub async fn server_run<T>(pipe_name: &str) -> PipeStream<T, NamedPipeServer>
+pub async fn server_run<T>(endpoint: &str) -> PipeStream<T, Transport>
 where
     T: for<'de> Deserialize<'de> + Serialize,
 {
-    let server = ServerOptions::new().create(pipe_name).unwrap();
-    server.connect().await.unwrap();
-    PipeStream::new(server)
+    let mut listener = TransportListener::bind(endpoint).await.unwrap();
+    PipeStream::new(listener.accept().await.unwrap())
 }
 
 #[derive(Debug)]
@@ -65,6 +124,7 @@ where
     T: AsyncRead + AsyncWrite,
 {
     _packet: Option<R>,
+    format: PipeFormat,
     reader: BufReader<T>,
 }
 
@@ -75,56 +135,273 @@ where
 {
     /**
      * 创建一个管道的流，用于发送和接收数据。
-     * 其中传输的数据是实现了Deserialize 和 Serialize接口的struct。
+     * 其中传输的数据是实现了Deserialize 和 Serialize接口的struct，在握手之前使用json格式编码。
      * */
     pub fn new(stream: T) -> Self {
         let reader = BufReader::new(stream);
         Self {
             _packet: None,
+            format: PipeFormat::Json,
             reader,
         }
     }
 
+    /**
+     * 获取当前使用的编码格式。
+     * */
+    pub fn get_format(&self) -> PipeFormat {
+        self.format
+    }
+
+    /**
+     * 作为发起连接的一方进行握手，协商双方使用的协议版本和编码格式，应该在连接成功后立即调用。
+     * 如果对方的协议版本与本方不同，返回`PipeStreamError::Incompatible`。
+     * `preferred` 本方偏好的编码格式。
+     * */
+    pub async fn handshake(
+        &mut self,
+        preferred: PipeFormat,
+    ) -> Result<PipeFormat, PipeStreamError> {
+        let hello = PipeHandshake {
+            version: PIPE_PROTOCOL_VERSION,
+            format: preferred,
+        };
+        write_packet(&mut self.reader, &hello, PipeFormat::Json).await?;
+        let reply: PipeHandshake = read_packet(&mut self.reader, PipeFormat::Json).await?;
+        if reply.version != PIPE_PROTOCOL_VERSION {
+            return Err(PipeStreamError::Incompatible(reply.version));
+        }
+        self.format = reply.format;
+        Ok(self.format)
+    }
+
+    /**
+     * 作为接受连接的一方进行握手，应该在对方连接成功后立即调用。
+     * 即使对方的协议版本不同，也会先回复本方的版本号，让对方知道不兼容的原因，然后返回`PipeStreamError::Incompatible`。
+     * `preferred` 本方偏好的编码格式。
+     * */
+    pub async fn accept_handshake(
+        &mut self,
+        preferred: PipeFormat,
+    ) -> Result<PipeFormat, PipeStreamError> {
+        let hello: PipeHandshake = read_packet(&mut self.reader, PipeFormat::Json).await?;
+        let compatible = hello.version == PIPE_PROTOCOL_VERSION;
+        let format = if compatible {
+            preferred.negotiate(hello.format)
+        } else {
+            PipeFormat::Json
+        };
+        let reply = PipeHandshake {
+            version: PIPE_PROTOCOL_VERSION,
+            format,
+        };
+        write_packet(&mut self.reader, &reply, PipeFormat::Json).await?;
+        if !compatible {
+            return Err(PipeStreamError::Incompatible(hello.version));
+        }
+        self.format = format;
+        Ok(format)
+    }
+
     /**
      * 接收一个数据包。
      * */
     pub async fn recv(&mut self) -> Result<R, PipeStreamError> {
-        let mut buf = Vec::new();
-        if let Ok(x) = self.reader.read_until(b'\n', &mut buf).await {
-            if x < 1 {
-                return Err(PipeStreamError::ReadEof);
-            }
-        };
-        let r = from_slice(&buf);
-        if let Err(e) = r {
-            return Err(PipeStreamError::DecodeError(e.to_string()));
+        read_packet(&mut self.reader, self.format).await
+    }
+
+    /**
+     * 发送一个数据包。
+     * `packet` 实现了序列化接口的数据。
+     * */
+    pub async fn send(&mut self, packet: &R) -> Result<(), PipeStreamError> {
+        write_packet(&mut self.reader, packet, self.format).await
+    }
+
+    /**
+     * 转换管道流中传输的数据类型，协商好的编码格式和已经缓冲的数据都会保留。
+     * 通常用于先使用一种固定的数据类型完成业务上的握手，然后再切换到真正的业务数据。
+     * */
+    pub fn cast<R2>(self) -> PipeStream<R2, T>
+    where
+        R2: for<'de> Deserialize<'de> + Serialize,
+    {
+        PipeStream {
+            _packet: None,
+            format: self.format,
+            reader: self.reader,
         }
-        Ok(r.unwrap())
     }
 
+    /**
+     * 把管道的流拆分成读取端和写入端，两端可以在不同的任务中同时使用，例如一边接收请求一边发送响应。
+     * 注意：拆分前请不要接收数据，否则已经缓冲但还没有解析的数据会丢失。
+     * */
+    pub fn split(self) -> (PipeStreamReader<R, T>, PipeStreamWriter<R, T>) {
+        let (reader, writer) = split(self.reader.into_inner());
+        (
+            PipeStreamReader {
+                _packet: None,
+                format: self.format,
+                reader: BufReader::new(reader),
+            },
+            PipeStreamWriter {
+                _packet: None,
+                format: self.format,
+                writer,
+            },
+        )
+    }
+}
+
+/// 管道流的读取端，通过PipeStream::split获得
+#[derive(Debug)]
+pub struct PipeStreamReader<R, T>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+    T: AsyncRead + AsyncWrite,
+{
+    _packet: Option<R>,
+    format: PipeFormat,
+    reader: BufReader<ReadHalf<T>>,
+}
+
+impl<R, T> PipeStreamReader<R, T>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /**
+     * 接收一个数据包。
+     * */
+    pub async fn recv(&mut self) -> Result<R, PipeStreamError> {
+        read_packet(&mut self.reader, self.format).await
+    }
+}
+
+/// 管道流的写入端，通过PipeStream::split获得
+#[derive(Debug)]
+pub struct PipeStreamWriter<R, T>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+    T: AsyncRead + AsyncWrite,
+{
+    _packet: Option<R>,
+    format: PipeFormat,
+    writer: WriteHalf<T>,
+}
+
+impl<R, T> PipeStreamWriter<R, T>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
     /**
      * 发送一个数据包。
      * `packet` 实现了序列化接口的数据。
      * */
     pub async fn send(&mut self, packet: &R) -> Result<(), PipeStreamError> {
-        let mut data = to_vec(&packet).unwrap();
-        data.push(b'\n');
-        if let Err(e) = self.reader.write_all(&data).await {
-            Err(PipeStreamError::WriteError(format!(
-                "Can't send the data. {}",
-                e
-            )))
-        } else {
-            Ok(())
+        write_packet(&mut self.writer, packet, self.format).await
+    }
+}
+
+async fn read_packet<R>(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+    format: PipeFormat,
+) -> Result<R, PipeStreamError>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+{
+    match format {
+        PipeFormat::Json => read_json_packet(reader).await,
+        PipeFormat::Binary => read_binary_packet(reader).await,
+    }
+}
+
+async fn write_packet<R>(
+    writer: &mut (impl AsyncWriteExt + Unpin),
+    packet: &R,
+    format: PipeFormat,
+) -> Result<(), PipeStreamError>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+{
+    let data = match format {
+        PipeFormat::Json => {
+            let mut data = to_vec(&packet).unwrap();
+            data.push(b'\n');
+            data
         }
+        PipeFormat::Binary => match serialize(packet) {
+            Ok(body) => {
+                let mut data = Vec::with_capacity(body.len() + 4);
+                data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+                data.extend(body);
+                data
+            }
+            Err(e) => {
+                return Err(PipeStreamError::WriteError(format!(
+                    "Can't encode the data. {}",
+                    e
+                )))
+            }
+        },
+    };
+    if let Err(e) = writer.write_all(&data).await {
+        Err(PipeStreamError::WriteError(format!(
+            "Can't send the data. {}",
+            e
+        )))
+    } else {
+        Ok(())
     }
 }
 
+async fn read_json_packet<R>(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+) -> Result<R, PipeStreamError>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+{
+    let mut buf = Vec::new();
+    // 读取出错和读到结尾一样，说明连接已经不可用
+    match reader.read_until(b'\n', &mut buf).await {
+        Ok(0) | Err(_) => return Err(PipeStreamError::ReadEof),
+        Ok(_) => {}
+    }
+    from_slice(&buf).map_err(|e| PipeStreamError::DecodeError(e.to_string()))
+}
+
+async fn read_binary_packet<R>(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+) -> Result<R, PipeStreamError>
+where
+    R: for<'de> Deserialize<'de> + Serialize,
+{
+    let mut len = [0u8; 4];
+    if reader.read_exact(&mut len).await.is_err() {
+        return Err(PipeStreamError::ReadEof);
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(PipeStreamError::DecodeError(format!(
+            "The frame is too large ({} bytes).",
+            len
+        )));
+    }
+    let mut buf = vec![0u8; len];
+    if reader.read_exact(&mut buf).await.is_err() {
+        return Err(PipeStreamError::ReadEof);
+    }
+    deserialize(&buf).map_err(|e| PipeStreamError::DecodeError(e.to_string()))
+}
+
 #[derive(Debug)]
 pub enum PipeStreamError {
     ReadEof,
     WriteError(String),
     DecodeError(String),
+    Incompatible(u32),
 }
 
 impl Display for PipeStreamError {
@@ -133,7 +410,138 @@ impl Display for PipeStreamError {
             PipeStreamError::ReadEof => "Read eof.".to_string(),
             PipeStreamError::DecodeError(e) => e.to_string(),
             PipeStreamError::WriteError(e) => e.to_string(),
+            PipeStreamError::Incompatible(v) => format!(
+                "Incompatible protocol version {}, expected {}.",
+                v, PIPE_PROTOCOL_VERSION
+            ),
         };
         write!(f, "{}", msg)
     }
 }
+
+#[cfg(test)]
+mod test_pipe {
+    use crate::pipe::{
+        client_connect, read_binary_packet, read_json_packet, Endpoint, PipeFormat, PipeStream,
+        PipeStreamError, TransportListener,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum TestPacket {
+        Text(String),
+        Data(Vec<u8>),
+        Quit,
+    }
+
+    // 读取时总是出错的连接
+    struct BrokenReader;
+
+    impl AsyncRead for BrokenReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()))
+        }
+    }
+
+    async fn echo(endpoint: &str, format: PipeFormat) {
+        let mut listener = TransportListener::bind(endpoint).await.unwrap();
+        let endpoint = listener.local_endpoint();
+        let server = tokio::spawn(async move {
+            let mut stream = PipeStream::<TestPacket, _>::new(listener.accept().await.unwrap());
+            stream.accept_handshake(PipeFormat::Binary).await.unwrap();
+            while let Ok(p) = stream.recv().await {
+                stream.send(&p).await.unwrap();
+                if p == TestPacket::Quit {
+                    break;
+                }
+            }
+        });
+        let mut stream = client_connect::<TestPacket>(&endpoint).await;
+        assert_eq!(format, stream.handshake(format).await.unwrap());
+        let packets = [
+            TestPacket::Text("你好\n世界".to_string()),
+            TestPacket::Data((0..=255).collect()),
+            TestPacket::Quit,
+        ];
+        for p in packets.iter() {
+            stream.send(p).await.unwrap();
+            assert_eq!(p, &stream.recv().await.unwrap());
+        }
+        server.await.unwrap();
+        assert!(matches!(stream.recv().await, Err(PipeStreamError::ReadEof)));
+    }
+
+    #[tokio::test]
+    async fn test_cast() {
+        let mut listener = TransportListener::bind("tcp://127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_endpoint();
+        let server = tokio::spawn(async move {
+            let mut stream = PipeStream::<u32, _>::new(listener.accept().await.unwrap());
+            stream.accept_handshake(PipeFormat::Binary).await.unwrap();
+            let version = stream.recv().await.unwrap();
+            // 连续发送两个不同类型的数据包，确保切换类型时不会丢失已经缓冲的数据
+            let mut stream = stream.cast::<TestPacket>();
+            stream
+                .send(&TestPacket::Data(vec![version as u8]))
+                .await
+                .unwrap();
+            stream.send(&TestPacket::Quit).await.unwrap();
+        });
+        let mut stream = client_connect::<u32>(&endpoint).await;
+        stream.handshake(PipeFormat::Binary).await.unwrap();
+        stream.send(&7).await.unwrap();
+        let mut stream = stream.cast::<TestPacket>();
+        assert_eq!(PipeFormat::Binary, stream.get_format());
+        assert_eq!(TestPacket::Data(vec![7]), stream.recv().await.unwrap());
+        assert_eq!(TestPacket::Quit, stream.recv().await.unwrap());
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(
+            Endpoint::NamedPipe(r"\\.\PIPE\peeper".to_string()),
+            Endpoint::parse("pipe://peeper").unwrap()
+        );
+        assert!(Endpoint::parse("tcp://127.0.0.1:8000").is_ok());
+        assert!(Endpoint::parse("tcp://8.8.8.8:8000").is_err());
+        assert!(Endpoint::parse("http://127.0.0.1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp() {
+        echo("tcp://127.0.0.1:0", PipeFormat::Binary).await;
+        echo("tcp://127.0.0.1:0", PipeFormat::Json).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix() {
+        let path = std::env::temp_dir().join(format!("rigela-test-{}.sock", std::process::id()));
+        echo(&format!("unix://{}", path.display()), PipeFormat::Binary).await;
+    }
+
+    #[tokio::test]
+    async fn test_read_error() {
+        // 两种格式在读取出错时都当作连接已经断开
+        let mut reader = BufReader::new(BrokenReader);
+        assert!(matches!(
+            read_json_packet::<TestPacket>(&mut reader).await,
+            Err(PipeStreamError::ReadEof)
+        ));
+        assert!(matches!(
+            read_binary_packet::<TestPacket>(&mut reader).await,
+            Err(PipeStreamError::ReadEof)
+        ));
+    }
+}
@@ -0,0 +1,273 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+#[cfg(unix)]
+use std::{fs::remove_file, path::PathBuf};
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{
+    ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+};
+
+/**
+ * 通信的端点，由一个uri来描述，支持以下几种形式：
+ * `pipe://name` 或 `\\.\PIPE\name` Windows命名管道；
+ * `unix:///path/to/socket` Unix域套接字；
+ * `tcp://127.0.0.1:port` TCP回环地址，为了安全，不允许使用非回环地址。
+ * */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    NamedPipe(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl Endpoint {
+    /**
+     * 解析一个端点的uri。
+     * `uri` 端点的uri。
+     * */
+    pub fn parse(uri: &str) -> Result<Self> {
+        if let Some(name) = uri.strip_prefix("pipe://") {
+            return Ok(Self::NamedPipe(format!(r"\\.\PIPE\{}", name)));
+        }
+        if uri.starts_with(r"\\.\") {
+            return Ok(Self::NamedPipe(uri.to_string()));
+        }
+        if let Some(path) = uri.strip_prefix("unix://") {
+            #[cfg(unix)]
+            return Ok(Self::Unix(path.into()));
+            #[cfg(not(unix))]
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Unix domain sockets are not supported ({}).", path),
+            ));
+        }
+        if let Some(addr) = uri.strip_prefix("tcp://") {
+            let addr: SocketAddr = addr.parse().map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid address ({}). {}", addr, e),
+                )
+            })?;
+            if !addr.ip().is_loopback() {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("Only loopback addresses are allowed ({}).", addr),
+                ));
+            }
+            return Ok(Self::Tcp(addr));
+        }
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown endpoint ({}).", uri),
+        ))
+    }
+}
+
+/**
+ * 连接的传输层，统一了不同的连接方式，可以直接交给PipeStream使用。
+ * */
+#[derive(Debug)]
+pub enum Transport {
+    #[cfg(windows)]
+    NamedPipeClient(NamedPipeClient),
+    #[cfg(windows)]
+    NamedPipeServer(NamedPipeServer),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+macro_rules! delegate {
+    ($self:expr, $stream:ident => $e:expr) => {
+        match $self.get_mut() {
+            #[cfg(windows)]
+            Transport::NamedPipeClient($stream) => $e,
+            #[cfg(windows)]
+            Transport::NamedPipeServer($stream) => $e,
+            #[cfg(unix)]
+            Transport::Unix($stream) => $e,
+            Transport::Tcp($stream) => $e,
+        }
+    };
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        delegate!(self, s => Pin::new(s).poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        delegate!(self, s => Pin::new(s).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        delegate!(self, s => Pin::new(s).poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        delegate!(self, s => Pin::new(s).poll_shutdown(cx))
+    }
+}
+
+/**
+ * 连接到一个端点，只尝试一次。
+ * `endpoint` 端点的uri，参考`Endpoint`。
+ * */
+pub async fn connect(endpoint: &str) -> Result<Transport> {
+    match Endpoint::parse(endpoint)? {
+        #[cfg(windows)]
+        Endpoint::NamedPipe(name) => {
+            Ok(Transport::NamedPipeClient(ClientOptions::new().open(name)?))
+        }
+        #[cfg(not(windows))]
+        Endpoint::NamedPipe(name) => Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("Named pipes are only supported on Windows ({}).", name),
+        )),
+        #[cfg(unix)]
+        Endpoint::Unix(path) => Ok(Transport::Unix(UnixStream::connect(path).await?)),
+        Endpoint::Tcp(addr) => {
+            let stream = TcpStream::connect(addr).await?;
+            // 数据包通常很小，关闭nagle算法以降低延迟
+            stream.set_nodelay(true)?;
+            Ok(Transport::Tcp(stream))
+        }
+    }
+}
+
+/**
+ * 创建命名管道的一个实例，只允许本机的进程连接，其他计算机通过网络访问会被拒绝。
+ * `name` 管道名称。
+ * */
+#[cfg(windows)]
+fn create_pipe_server(name: &str) -> Result<NamedPipeServer> {
+    ServerOptions::new()
+        .reject_remote_clients(true)
+        .create(name)
+}
+
+enum Listener {
+    #[cfg(windows)]
+    NamedPipe {
+        name: String,
+        next: NamedPipeServer,
+    },
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+/**
+ * 在一个端点上监听连接，可以不断地接受多个客户端。
+ * */
+pub struct TransportListener {
+    listener: Listener,
+}
+
+impl TransportListener {
+    /**
+     * 绑定一个端点，开始监听。
+     * `endpoint` 端点的uri，参考`Endpoint`。
+     * */
+    pub async fn bind(endpoint: &str) -> Result<Self> {
+        let listener = match Endpoint::parse(endpoint)? {
+            #[cfg(windows)]
+            Endpoint::NamedPipe(name) => {
+                let next = create_pipe_server(&name)?;
+                Listener::NamedPipe { name, next }
+            }
+            #[cfg(not(windows))]
+            Endpoint::NamedPipe(name) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Named pipes are only supported on Windows ({}).", name),
+                ))
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                // 上次异常退出时可能遗留了套接字文件，不删除的话无法绑定
+                remove_file(&path).unwrap_or(());
+                Listener::Unix(UnixListener::bind(&path)?, path)
+            }
+            Endpoint::Tcp(addr) => Listener::Tcp(TcpListener::bind(addr).await?),
+        };
+        Ok(Self { listener })
+    }
+
+    /**
+     * 获取实际监听的端点uri，绑定tcp的0端口时可以通过此方法得到系统分配的端口。
+     * */
+    pub fn local_endpoint(&self) -> String {
+        match &self.listener {
+            #[cfg(windows)]
+            Listener::NamedPipe { name, .. } => name.clone(),
+            #[cfg(unix)]
+            Listener::Unix(_, path) => format!("unix://{}", path.display()),
+            Listener::Tcp(l) => match l.local_addr() {
+                Ok(addr) => format!("tcp://{}", addr),
+                Err(_) => String::new(),
+            },
+        }
+    }
+
+    /**
+     * 等待并接受一个客户端的连接。
+     * */
+    pub async fn accept(&mut self) -> Result<Transport> {
+        match &mut self.listener {
+            #[cfg(windows)]
+            Listener::NamedPipe { name, next } => {
+                next.connect().await?;
+                // 命名管道的每个实例只能服务一个客户端，所以要先创建好下一个实例再把当前实例交出去
+                let server = create_pipe_server(name)?;
+                Ok(Transport::NamedPipeServer(std::mem::replace(next, server)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(l, _) => Ok(Transport::Unix(l.accept().await?.0)),
+            Listener::Tcp(l) => {
+                let (stream, _) = l.accept().await?;
+                stream.set_nodelay(true)?;
+                Ok(Transport::Tcp(stream))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for TransportListener {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = &self.listener {
+            remove_file(path).unwrap_or(());
+        }
+    }
+}
@@ -0,0 +1,483 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+//! 远程协助的加密通道。
+//!
+//! 两个读屏实例通过TCP直接连接（不经过中转服务器），一方是领导者（协助者），另一方是跟随者（被协助者）。
+//! 握手过程如下：
+//! 1. 双方交换协议版本、角色、临时的X25519公钥和随机数；
+//! 2. 使用双方都知道的会话密码校验值和握手记录派生出密码密钥，和X25519协商出的共享密钥一起通过HKDF派生出两个方向的加密密钥和确认密钥；
+//! 3. 跟随者（接受连接的一方）先发送握手记录的HMAC，证明自己知道会话密码；领导者验证通过后才发送自己的HMAC，验证失败则直接断开连接。
+//!
+//! 领导者主动连接配置的地址，对方可能是冒充的跟随者或者中间人，所以领导者不能在验证对方之前发出自己的确认码，否则攻击者可以用拿到的确认码离线猜测会话密码。
+//! 跟随者先发出的确认码同样可以被用来离线猜测，但跟随者只在用户开始会话以后才监听，并且密码校验值使用PBKDF2计算，每次猜测的代价都很高，所以会话密码仍然应该足够长。
+//!
+//! 配置文件中只保存会话密码的校验值（参见`hash_password`），不保存明文。
+//!
+//! 之后的每一帧数据都是四字节长度前缀加上ChaCha20-Poly1305加密的bincode数据，每个方向使用单独的密钥和递增的计数器作为nonce。
+
+use bincode::{deserialize, serialize};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305},
+    agreement::{agree_ephemeral, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf::{Salt, HKDF_SHA256},
+    hmac::{self, HMAC_SHA256},
+    pbkdf2::{self, PBKDF2_HMAC_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    num::NonZeroU32,
+};
+use tokio::io::{split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+
+/// 远程协助默认使用的端口
+pub const DEFAULT_REMOTE_PORT: u16 = 6837;
+/// 远程协助协议的版本，协议有不兼容的改动时需要增加
+pub const REMOTE_PROTOCOL_VERSION: u32 = 3;
+
+// 单帧数据的最大长度，超过这个长度的帧视为错误
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+// 计算密码校验值时的迭代次数
+const PBKDF2_ITERATIONS: u32 = 100_000;
+// 握手记录的前缀，同时也是计算密码校验值时的盐，区分其他使用相同密码的协议
+const TRANSCRIPT_LABEL: &[u8] = b"rigela-remote";
+// 密码校验值的前缀，用于区分配置文件中的明文密码
+const PASSWORD_HASH_PREFIX: &str = "pbkdf2-sha256$";
+
+/// 远程会话中的角色
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum RemoteRole {
+    /// 领导者，收听对方的语音并控制对方的键盘
+    Leader,
+    /// 跟随者，把自己的语音和音效发送给对方，接受对方的键盘输入
+    Follower,
+}
+
+impl RemoteRole {
+    fn label(&self) -> &'static [u8] {
+        match self {
+            Self::Leader => b"leader",
+            Self::Follower => b"follower",
+        }
+    }
+}
+
+/// 远程会话中传输的消息
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum RemoteMessage {
+    /// 跟随者朗读的文字
+    Speech(String),
+    /// 跟随者播放的音效，内容是资源名称
+    Sound(String),
+    /// 跟随者停止了朗读
+    StopSpeech,
+    /// 领导者的按键
+    Key {
+        vk_code: u32,
+        scan_code: u32,
+        extended: bool,
+        pressed: bool,
+    },
+}
+
+/// 远程会话的错误
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteError {
+    /// 连接断开或者读写失败
+    Io(String),
+    /// 对方的协议版本不兼容
+    Incompatible(u32),
+    /// 双方的角色相同
+    SameRole,
+    /// 会话密码不正确，或者数据被篡改
+    AuthFailed,
+    /// 无法解析的数据
+    Decode(String),
+}
+
+impl Display for RemoteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error. {}", e),
+            Self::Incompatible(v) => write!(
+                f,
+                "Incompatible protocol version {}, expected {}.",
+                v, REMOTE_PROTOCOL_VERSION
+            ),
+            Self::SameRole => write!(f, "Both sides have the same role."),
+            Self::AuthFailed => write!(f, "Authentication failed."),
+            Self::Decode(e) => write!(f, "Can't decode the data. {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for RemoteError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value.to_string())
+    }
+}
+
+/// 握手时明文发送的问候
+#[derive(Debug, Deserialize, Serialize)]
+struct RemoteHello {
+    version: u32,
+    role: RemoteRole,
+    public_key: Vec<u8>,
+    nonce: [u8; 16],
+}
+
+async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    data: &[u8],
+) -> Result<(), RemoteError> {
+    let mut frame = Vec::with_capacity(data.len() + 4);
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    frame.extend_from_slice(data);
+    writer.write_all(&frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>, RemoteError> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(RemoteError::Decode(format!(
+            "The frame is too large ({} bytes).",
+            len
+        )));
+    }
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+// 使用计数器构造nonce，同一个密钥的计数器不会重复
+fn make_nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/**
+ * 远程会话的接收端，通过`handshake`获得。
+ * */
+pub struct RemoteReader<T> {
+    reader: ReadHalf<T>,
+    key: LessSafeKey,
+    counter: u64,
+}
+
+impl<T: AsyncRead + AsyncWrite> RemoteReader<T> {
+    /**
+     * 接收并解密一个消息。
+     * */
+    pub async fn recv(&mut self) -> Result<RemoteMessage, RemoteError> {
+        let mut data = read_frame(&mut self.reader).await?;
+        let nonce = make_nonce(self.counter);
+        self.counter += 1;
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| RemoteError::AuthFailed)?;
+        deserialize(plain).map_err(|e| RemoteError::Decode(e.to_string()))
+    }
+}
+
+/**
+ * 远程会话的发送端，通过`handshake`获得。
+ * */
+pub struct RemoteWriter<T> {
+    writer: WriteHalf<T>,
+    key: LessSafeKey,
+    counter: u64,
+}
+
+impl<T: AsyncRead + AsyncWrite> RemoteWriter<T> {
+    /**
+     * 加密并发送一个消息。
+     * `message` 要发送的消息。
+     * */
+    pub async fn send(&mut self, message: &RemoteMessage) -> Result<(), RemoteError> {
+        let mut data = serialize(message).map_err(|e| RemoteError::Decode(e.to_string()))?;
+        let nonce = make_nonce(self.counter);
+        self.counter += 1;
+        self.key
+            .seal_in_place_append_tag(nonce, Aad::empty(), &mut data)
+            .map_err(|_| RemoteError::AuthFailed)?;
+        write_frame(&mut self.writer, &data).await
+    }
+}
+
+/**
+ * 计算会话密码的校验值，配置文件中只保存这个值。双方的密码相同时，校验值也相同。
+ * `password` 明文的会话密码。
+ * */
+pub fn hash_password(password: &str) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::derive(
+        PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+        TRANSCRIPT_LABEL,
+        password.as_bytes(),
+        &mut hash,
+    );
+    let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", PASSWORD_HASH_PREFIX, hex)
+}
+
+/**
+ * 判断一个字符串是否是`hash_password`计算出的密码校验值。
+ * `value` 要判断的字符串。
+ * */
+pub fn is_password_hash(value: &str) -> bool {
+    value
+        .strip_prefix(PASSWORD_HASH_PREFIX)
+        .is_some_and(|h| h.len() == 64 && h.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/**
+ * 在一个已经建立的连接上进行握手，成功后返回加密通道的接收端和发送端。
+ * 双方必须使用相同的会话密码和不同的角色，否则握手失败。
+ * `stream` 连接，通常是TcpStream。
+ * `role` 本方的角色。
+ * `password_hash` 会话密码的校验值，由`hash_password`计算。
+ * */
+pub async fn handshake<T: AsyncRead + AsyncWrite>(
+    stream: T,
+    role: RemoteRole,
+    password_hash: &str,
+) -> Result<(RemoteReader<T>, RemoteWriter<T>), RemoteError> {
+    let (mut reader, mut writer) = split(stream);
+    let rng = SystemRandom::new();
+    let private_key =
+        EphemeralPrivateKey::generate(&X25519, &rng).map_err(|_| RemoteError::AuthFailed)?;
+    let public_key = private_key
+        .compute_public_key()
+        .map_err(|_| RemoteError::AuthFailed)?;
+    let mut nonce = [0u8; 16];
+    rng.fill(&mut nonce).map_err(|_| RemoteError::AuthFailed)?;
+
+    let hello = RemoteHello {
+        version: REMOTE_PROTOCOL_VERSION,
+        role,
+        public_key: public_key.as_ref().to_vec(),
+        nonce,
+    };
+    write_frame(&mut writer, &serialize(&hello).unwrap()).await?;
+    let peer: RemoteHello = deserialize(&read_frame(&mut reader).await?)
+        .map_err(|e| RemoteError::Decode(e.to_string()))?;
+    if peer.version != REMOTE_PROTOCOL_VERSION {
+        return Err(RemoteError::Incompatible(peer.version));
+    }
+    if peer.role == role {
+        return Err(RemoteError::SameRole);
+    }
+
+    // 握手记录按照领导者在前、跟随者在后的顺序组织，这样双方得到的记录相同
+    let (leader, follower) = match role {
+        RemoteRole::Leader => (&hello, &peer),
+        RemoteRole::Follower => (&peer, &hello),
+    };
+    let mut transcript = TRANSCRIPT_LABEL.to_vec();
+    transcript.extend_from_slice(&REMOTE_PROTOCOL_VERSION.to_le_bytes());
+    transcript.extend_from_slice(&leader.public_key);
+    transcript.extend_from_slice(&follower.public_key);
+    transcript.extend_from_slice(&leader.nonce);
+    transcript.extend_from_slice(&follower.nonce);
+
+    // 密码密钥包含双方的随机数，每次会话都不同
+    let password_key = hmac::sign(
+        &hmac::Key::new(HMAC_SHA256, password_hash.as_bytes()),
+        &transcript,
+    );
+    let prk = agree_ephemeral(
+        private_key,
+        &UnparsedPublicKey::new(&X25519, &peer.public_key),
+        |shared| Salt::new(HKDF_SHA256, password_key.as_ref()).extract(shared),
+    )
+    .map_err(|_| RemoteError::AuthFailed)?;
+
+    let aead_key = |label: &[u8]| -> Result<LessSafeKey, RemoteError> {
+        let info = [label, &transcript];
+        let okm = prk
+            .expand(&info, &CHACHA20_POLY1305)
+            .map_err(|_| RemoteError::AuthFailed)?;
+        Ok(LessSafeKey::new(UnboundKey::from(okm)))
+    };
+    let to_follower = aead_key(b"leader to follower")?;
+    let to_leader = aead_key(b"follower to leader")?;
+    let confirm_key: hmac::Key = prk
+        .expand(&[b"confirm", &transcript], HMAC_SHA256)
+        .map_err(|_| RemoteError::AuthFailed)?
+        .into();
+
+    // 交换确认码，证明双方知道相同的会话密码，跟随者先发送，领导者验证通过后才回复自己的确认码
+    let tag = hmac::sign(&confirm_key, &[role.label(), &transcript].concat());
+    let verify = |peer_tag: &[u8]| {
+        hmac::verify(
+            &confirm_key,
+            &[peer.role.label(), &transcript].concat(),
+            peer_tag,
+        )
+        .map_err(|_| RemoteError::AuthFailed)
+    };
+    match role {
+        RemoteRole::Leader => {
+            verify(&read_frame(&mut reader).await?)?;
+            write_frame(&mut writer, tag.as_ref()).await?;
+        }
+        RemoteRole::Follower => {
+            write_frame(&mut writer, tag.as_ref()).await?;
+            // 领导者验证失败时直接断开连接，不会回复确认码
+            let peer_tag = read_frame(&mut reader)
+                .await
+                .map_err(|_| RemoteError::AuthFailed)?;
+            verify(&peer_tag)?;
+        }
+    }
+
+    let (send_key, recv_key) = match role {
+        RemoteRole::Leader => (to_follower, to_leader),
+        RemoteRole::Follower => (to_leader, to_follower),
+    };
+    Ok((
+        RemoteReader {
+            reader,
+            key: recv_key,
+            counter: 0,
+        },
+        RemoteWriter {
+            writer,
+            key: send_key,
+            counter: 0,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod test_remote {
+    use crate::remote::{
+        handshake, hash_password, is_password_hash, read_frame, write_frame, RemoteError,
+        RemoteHello, RemoteMessage, RemoteReader, RemoteRole, RemoteWriter,
+        REMOTE_PROTOCOL_VERSION,
+    };
+    use bincode::serialize;
+    use ring::{
+        agreement::{EphemeralPrivateKey, X25519},
+        rand::SystemRandom,
+    };
+    use tokio::{
+        io::duplex,
+        net::{TcpListener, TcpStream},
+    };
+
+    type Channel = (RemoteReader<TcpStream>, RemoteWriter<TcpStream>);
+
+    async fn connect_pair(
+        leader_password: &'static str,
+        follower_password: &'static str,
+        follower_role: RemoteRole,
+    ) -> (Result<Channel, RemoteError>, Result<Channel, RemoteError>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let follower = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handshake(stream, follower_role, &hash_password(follower_password)).await
+        });
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let leader = handshake(stream, RemoteRole::Leader, &hash_password(leader_password)).await;
+        (leader, follower.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_session() {
+        let (leader, follower) = connect_pair("secret", "secret", RemoteRole::Follower).await;
+        let (mut leader_reader, mut leader_writer) = leader.unwrap();
+        let (mut follower_reader, mut follower_writer) = follower.unwrap();
+
+        let speech = RemoteMessage::Speech("你好".to_string());
+        follower_writer.send(&speech).await.unwrap();
+        follower_writer
+            .send(&RemoteMessage::StopSpeech)
+            .await
+            .unwrap();
+        assert_eq!(speech, leader_reader.recv().await.unwrap());
+        assert_eq!(
+            RemoteMessage::StopSpeech,
+            leader_reader.recv().await.unwrap()
+        );
+
+        let key = RemoteMessage::Key {
+            vk_code: 0x41,
+            scan_code: 0x1e,
+            extended: false,
+            pressed: true,
+        };
+        leader_writer.send(&key).await.unwrap();
+        assert_eq!(key, follower_reader.recv().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password() {
+        let (leader, follower) = connect_pair("secret", "guess", RemoteRole::Follower).await;
+        assert_eq!(Some(RemoteError::AuthFailed), leader.err());
+        assert_eq!(Some(RemoteError::AuthFailed), follower.err());
+    }
+
+    #[tokio::test]
+    async fn test_leader_confirms_last() {
+        // 不知道密码的一方冒充跟随者，领导者拒绝之前不能发出自己的确认码
+        let (attacker, stream) = duplex(4096);
+        let leader = tokio::spawn(async move {
+            handshake(stream, RemoteRole::Leader, &hash_password("secret")).await
+        });
+        let (mut reader, mut writer) = tokio::io::split(attacker);
+        let rng = SystemRandom::new();
+        let private_key = EphemeralPrivateKey::generate(&X25519, &rng).unwrap();
+        let hello = RemoteHello {
+            version: REMOTE_PROTOCOL_VERSION,
+            role: RemoteRole::Follower,
+            public_key: private_key.compute_public_key().unwrap().as_ref().to_vec(),
+            nonce: [0u8; 16],
+        };
+        write_frame(&mut writer, &serialize(&hello).unwrap())
+            .await
+            .unwrap();
+        read_frame(&mut reader).await.unwrap();
+        write_frame(&mut writer, &[0u8; 32]).await.unwrap();
+
+        assert_eq!(Some(RemoteError::AuthFailed), leader.await.unwrap().err());
+        assert!(read_frame(&mut reader).await.is_err());
+    }
+
+    #[test]
+    fn test_password_hash() {
+        let hash = hash_password("secret");
+        assert!(is_password_hash(&hash));
+        assert_eq!(hash, hash_password("secret"));
+        assert_ne!(hash, hash_password("guess"));
+        assert!(!is_password_hash("secret"));
+        assert!(!is_password_hash(""));
+    }
+
+    #[tokio::test]
+    async fn test_same_role() {
+        let (leader, follower) = connect_pair("secret", "secret", RemoteRole::Leader).await;
+        assert_eq!(Some(RemoteError::SameRole), leader.err());
+        assert_eq!(Some(RemoteError::SameRole), follower.err());
+    }
+}
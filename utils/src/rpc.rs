@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) 2024. The RigelA open source project team and
+ * its contributors reserve all rights.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed under the
+ * License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and limitations under the License.
+ */
+
+pub use serde_json_bytes::serde_json::{from_value, to_value, Value};
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// 自动化接口默认的端点，外部程序可以通过这个命名管道控制读屏
+pub const AUTOMATION_ENDPOINT: &str = "pipe://rigela-automation";
+
+/// JSON-RPC协议的版本
+pub const JSON_RPC_VERSION: &str = "2.0";
+
+/// 无法解析的json
+pub const PARSE_ERROR: i32 = -32700;
+/// 不是有效的请求对象
+pub const INVALID_REQUEST: i32 = -32600;
+/// 方法不存在
+pub const METHOD_NOT_FOUND: i32 = -32601;
+/// 参数无效
+pub const INVALID_PARAMS: i32 = -32602;
+/// 内部错误
+pub const INTERNAL_ERROR: i32 = -32603;
+/// 访问策略不允许调用此方法
+pub const ACCESS_DENIED: i32 = -32001;
+
+/**
+ * JSON-RPC的请求，没有id的请求是通知，服务端不会回复。
+ * */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+impl RpcRequest {
+    /**
+     * 创建一个请求。
+     * `id` 请求的id，响应中会带有相同的id。
+     * `method` 方法名称。
+     * `params` 参数，通常是一个对象。
+     * */
+    pub fn new(id: u64, method: &str, params: Value) -> Self {
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_string(),
+            id: Some(id.into()),
+            method: method.to_string(),
+            params,
+        }
+    }
+}
+
+/**
+ * JSON-RPC的错误对象。
+ * */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl RpcError {
+    /**
+     * 创建一个错误对象。
+     * `code` 错误代码，参考本模块中的常量。
+     * `message` 错误的描述。
+     * */
+    pub fn new(code: i32, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/**
+ * JSON-RPC的响应，result和error有且只有一个。
+ * */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    /**
+     * 创建一个响应。
+     * `id` 对应请求的id，无法得知请求的id时（例如无法解析请求）使用null。
+     * `result` 调用的结果。
+     * */
+    pub fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(r) => (Some(r), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_string(),
+            id,
+            result,
+            error,
+        }
+    }
+
+    /**
+     * 获取调用的结果。
+     * */
+    pub fn into_result(self) -> Result<Value, RpcError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.result.unwrap_or_default()),
+        }
+    }
+}
+
+/**
+ * 管道中传输的JSON-RPC消息，使用PipeFormat::Json格式，也就是每行一个json对象，这样任何语言都可以很容易地实现客户端。
+ * 有method字段的是请求，否则是响应。
+ * */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum RpcMessage {
+    Request(RpcRequest),
+    Response(RpcResponse),
+}
+
+#[cfg(test)]
+mod test_rpc {
+    use crate::rpc::{RpcError, RpcMessage, RpcRequest, RpcResponse, Value, METHOD_NOT_FOUND};
+    use serde_json_bytes::serde_json::{from_str, json, to_string};
+
+    #[test]
+    fn test_message() {
+        let request: RpcMessage =
+            from_str(r#"{"jsonrpc":"2.0","id":1,"method":"speak","params":{"text":"你好"}}"#)
+                .unwrap();
+        assert_eq!(
+            RpcMessage::Request(RpcRequest::new(1, "speak", json!({"text": "你好"}))),
+            request
+        );
+        // 通知没有id和参数
+        let notification: RpcMessage = from_str(r#"{"jsonrpc":"2.0","method":"cancel"}"#).unwrap();
+        let RpcMessage::Request(notification) = notification else {
+            panic!("Expected a request.");
+        };
+        assert_eq!(None, notification.id);
+        assert_eq!(Value::Null, notification.params);
+
+        let response = RpcResponse::new(
+            1.into(),
+            Err(RpcError::new(METHOD_NOT_FOUND, "Method not found.")),
+        );
+        let text = to_string(&RpcMessage::Response(response.clone())).unwrap();
+        assert!(!text.contains("result"));
+        let RpcMessage::Response(r) = from_str(&text).unwrap() else {
+            panic!("Expected a response.");
+        };
+        assert_eq!(response, r);
+        assert_eq!(METHOD_NOT_FOUND, r.into_result().unwrap_err().code);
+    }
+}